@@ -0,0 +1,139 @@
+//! Alignment of two sequences of vehicles
+//!
+//! [`align`] returns the minimum edit script turning a sequence into another : the longest common
+//! subsequence is kept in place, and an element removed from one place and inserted at another is
+//! reported as moved rather than deleted and inserted again.
+//!
+//! ```rust
+//! use aei_tag_parser::align::{align, Op};
+//!
+//! let ops = align(&["A", "B", "C"], &["B", "A", "D"]);
+//! assert_eq!(
+//!     ops,
+//!     vec![
+//!         Op::Keep { from: 1, to: 0 },
+//!         Op::Delete { from: 2 },
+//!         Op::Move { from: 0, to: 1 },
+//!         Op::Insert { to: 2 },
+//!     ]
+//! );
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+/// Operation of an edit script, positions being the indexes in the two sequences
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Op {
+    /// Element in the common order of both sequences
+    Keep { from: usize, to: usize },
+    /// Element of the first sequence only
+    Delete { from: usize },
+    /// Element of the second sequence only
+    Insert { to: usize },
+    /// Element of both sequences, out of their common order
+    Move { from: usize, to: usize },
+}
+
+/// Minimum edit script turning `from` into `to`, in the order of `to`. A deleted element comes
+/// after the elements kept which precede it in `from`
+pub fn align<T: PartialEq>(from: &[T], to: &[T]) -> Vec<Op> {
+    let (n, m) = (from.len(), to.len());
+
+    // lengths[i][j] : length of the longest common subsequence of from[i..] and to[j..]
+    let mut lengths = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i][j] = if from[i] == to[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut ops = Vec::with_capacity(n.max(m));
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && from[i] == to[j] {
+            ops.push(Some(Op::Keep { from: i, to: j }));
+            i += 1;
+            j += 1;
+        } else if i < n && (j == m || lengths[i + 1][j] >= lengths[i][j + 1]) {
+            ops.push(Some(Op::Delete { from: i }));
+            i += 1;
+        } else {
+            ops.push(Some(Op::Insert { to: j }));
+            j += 1;
+        }
+    }
+
+    // An element deleted and inserted elsewhere has moved
+    for k in 0..ops.len() {
+        if let Some(Op::Insert { to: j }) = ops[k] {
+            let deleted = ops
+                .iter()
+                .position(|op| matches!(*op, Some(Op::Delete { from: i }) if from[i] == to[j]));
+            if let Some(d) = deleted {
+                if let Some(Op::Delete { from: i }) = ops[d] {
+                    ops[k] = Some(Op::Move { from: i, to: j });
+                    ops[d] = None;
+                }
+            }
+        }
+    }
+    ops.into_iter().flatten().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical() {
+        assert_eq!(
+            align(&[1, 2], &[1, 2]),
+            vec![Op::Keep { from: 0, to: 0 }, Op::Keep { from: 1, to: 1 }]
+        );
+        assert!(align::<u8>(&[], &[]).is_empty());
+    }
+
+    #[test]
+    fn inserted_and_deleted() {
+        assert_eq!(
+            align(&[1, 2, 3], &[1, 4, 3, 5]),
+            vec![
+                Op::Keep { from: 0, to: 0 },
+                Op::Delete { from: 1 },
+                Op::Insert { to: 1 },
+                Op::Keep { from: 2, to: 2 },
+                Op::Insert { to: 3 },
+            ]
+        );
+    }
+
+    #[test]
+    fn moved() {
+        // Moving the last car to the head is a single operation
+        let ops = align(&[1, 2, 3, 4], &[4, 1, 2, 3]);
+        assert_eq!(
+            ops.iter()
+                .filter(|op| matches!(op, Op::Move { .. }))
+                .collect::<Vec<_>>(),
+            vec![&Op::Move { from: 3, to: 0 }]
+        );
+        assert_eq!(ops.len(), 4);
+    }
+
+    #[test]
+    fn repeated_elements() {
+        assert_eq!(
+            align(&[1, 1, 2], &[2, 1]),
+            vec![
+                Op::Delete { from: 1 },
+                Op::Keep { from: 2, to: 0 },
+                Op::Move { from: 0, to: 1 },
+            ]
+        );
+    }
+}
@@ -0,0 +1,284 @@
+//! Asynchronous decoding and writing of tags, available with the `async` feature
+//!
+//! [`TagCodec`] frames the bytes received from an [`AsyncRead`], a socket for instance, into
+//! lines and decodes them as [`TagStream`](crate::stream::TagStream) does, partial reads being
+//! kept until the end of the line is received. [`RecordCodec`] writes the decoded tags in one of
+//! the output formats of the CLI. Both are used through the `tokio_util` framed adaptors, reading
+//! only when the stream is polled and waiting for the writer when its buffer is full.
+//!
+//! ```rust
+//! use aei_tag_parser::codec::{decode_tags, record_sink, RecordFormat};
+//! use aei_tag_parser::length::Units;
+//! use futures::{SinkExt, StreamExt};
+//!
+//! # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+//! let input: &[u8] = b"9EA488C030426A179000000000000331\n";
+//! let mut tags = decode_tags(input);
+//! let mut sink = record_sink(Vec::new(), RecordFormat::Csv, Units::Metric);
+//!
+//! while let Some(read) = tags.next().await {
+//!     let (_, tag) = read.unwrap().unwrap();
+//!     sink.send(tag).await.unwrap();
+//! }
+//! assert_eq!(
+//!     sink.get_ref(),
+//!     b"9EA488C030426A179000000000000331;IOCC;3088;Railcar;19;Right;10.6 m;Railroad\n"
+//! );
+//! # });
+//! ```
+
+use crate::{
+    length::Units,
+    stream::{parse_line, LineError},
+    AEITagData,
+};
+use bytes::{Buf, BufMut, BytesMut};
+use std::io;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_util::codec::{Decoder, Encoder, FramedRead, FramedWrite};
+
+/// Longest line kept by default. A tag is 32 characters long, longer lines are garbage
+pub const DEFAULT_MAX_LINE_LENGTH: usize = 1024;
+
+/// Decoder of the tags of a byte stream, one per line. Blank lines and lines starting with `#`
+/// are skipped. Invalid lines are yielded as errors without ending the stream
+#[derive(Clone, Debug)]
+pub struct TagCodec {
+    line: usize,
+    max_line_length: usize,
+    /// A line too long is being dropped until its end
+    discarding: bool,
+}
+
+impl TagCodec {
+    pub fn new() -> TagCodec {
+        TagCodec::with_max_line_length(DEFAULT_MAX_LINE_LENGTH)
+    }
+
+    /// Codec dropping the lines longer than `max_line_length` bytes instead of buffering them
+    pub fn with_max_line_length(max_line_length: usize) -> TagCodec {
+        TagCodec {
+            line: 0,
+            max_line_length,
+            discarding: false,
+        }
+    }
+
+    /// Number of the last line received
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+impl Default for TagCodec {
+    fn default() -> Self {
+        TagCodec::new()
+    }
+}
+
+impl Decoder for TagCodec {
+    type Item = Result<(usize, AEITagData), LineError>;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        loop {
+            let end = src.iter().position(|&b| b == b'\n');
+            if self.discarding {
+                // The line was already reported, drop it until its end
+                match end {
+                    Some(end) => {
+                        src.advance(end + 1);
+                        self.discarding = false;
+                        continue;
+                    }
+                    None => {
+                        src.clear();
+                        return Ok(None);
+                    }
+                }
+            }
+
+            let too_long = end.unwrap_or(src.len()) > self.max_line_length;
+            if too_long {
+                match end {
+                    Some(end) => src.advance(end + 1),
+                    None => {
+                        src.clear();
+                        self.discarding = true;
+                    }
+                }
+                self.line += 1;
+                return Ok(Some(Err(LineError::Io(
+                    self.line,
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("line longer than {} bytes", self.max_line_length),
+                    ),
+                ))));
+            }
+
+            let line = match end {
+                Some(end) => src.split_to(end + 1),
+                None => return Ok(None),
+            };
+            self.line += 1;
+            if let Some(read) = parse_line(self.line, &line) {
+                return Ok(Some(read));
+            }
+        }
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if let Some(read) = self.decode(src)? {
+            return Ok(Some(read));
+        }
+
+        // Last line without a line feed
+        let line = src.split();
+        if line.is_empty() || self.discarding {
+            return Ok(None);
+        }
+        self.line += 1;
+        Ok(parse_line(self.line, &line))
+    }
+}
+
+/// Format of the records written by [`RecordCodec`], as the output formats of the CLI
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum RecordFormat {
+    Short,
+    Csv,
+    Json,
+}
+
+/// Encoder writing each tag on its own line
+#[derive(Copy, Clone, Debug)]
+pub struct RecordCodec {
+    format: RecordFormat,
+    units: Units,
+}
+
+impl RecordCodec {
+    pub fn new(format: RecordFormat, units: Units) -> RecordCodec {
+        RecordCodec { format, units }
+    }
+}
+
+impl Encoder<AEITagData> for RecordCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, tag: AEITagData, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let record = match self.format {
+            RecordFormat::Short => tag.to_short_string_in(self.units),
+            RecordFormat::Csv => tag.to_csv_in(self.units),
+            RecordFormat::Json => tag.to_json_in(self.units),
+        };
+        dst.reserve(record.len() + 1);
+        dst.put_slice(record.as_bytes());
+        dst.put_u8(b'\n');
+        Ok(())
+    }
+}
+
+/// Stream of the tags received on `reader`
+pub fn decode_tags<R: AsyncRead>(reader: R) -> FramedRead<R, TagCodec> {
+    FramedRead::new(reader, TagCodec::new())
+}
+
+/// Sink writing the tags sent to it on `writer`
+pub fn record_sink<W: AsyncWrite>(
+    writer: W,
+    format: RecordFormat,
+    units: Units,
+) -> FramedWrite<W, RecordCodec> {
+    FramedWrite::new(writer, RecordCodec::new(format, units))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{SinkExt, StreamExt};
+    use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+
+    static TAG2: &str = "9EA488C030426A179000000000000331";
+    static TAG3: &str = "9EA488C5320CC01B9000000000000331";
+
+    #[tokio::test]
+    async fn partial_reads() {
+        // A small buffer splits the tags across several reads
+        let (mut client, server) = duplex(7);
+        let writer = async move {
+            let data = format!("# reader 1\r\n{}\r\n\nZZ\n{}", TAG2, TAG3);
+            for chunk in data.as_bytes().chunks(5) {
+                client.write_all(chunk).await.unwrap();
+            }
+        };
+        let reader = decode_tags(server).collect::<Vec<_>>();
+        let ((), reads) = tokio::join!(writer, reader);
+
+        assert_eq!(reads.len(), 3);
+        let (line, tag) = reads[0].as_ref().unwrap().as_ref().unwrap();
+        assert_eq!((*line, tag.car_number()), (2, 3088));
+        assert_eq!(reads[1].as_ref().unwrap().as_ref().unwrap_err().line(), 4);
+        let (line, tag) = reads[2].as_ref().unwrap().as_ref().unwrap();
+        assert_eq!((*line, tag.car_number()), (5, 85123));
+    }
+
+    #[tokio::test]
+    async fn long_lines_are_dropped() {
+        let mut data = vec![b'A'; 100];
+        data.extend_from_slice(format!("\n{}\n", TAG2).as_bytes());
+        let reads: Vec<_> = FramedRead::new(&data[..], TagCodec::with_max_line_length(40))
+            .collect()
+            .await;
+
+        assert_eq!(reads.len(), 2);
+        assert!(matches!(reads[0], Ok(Err(LineError::Io(1, _)))));
+        assert_eq!(reads[1].as_ref().unwrap().as_ref().unwrap().0, 2);
+    }
+
+    #[tokio::test]
+    async fn long_line_across_reads() {
+        let (mut client, server) = duplex(8);
+        let writer = async move {
+            client.write_all(&[b'A'; 100]).await.unwrap();
+            client
+                .write_all(format!("\n{}\n", TAG3).as_bytes())
+                .await
+                .unwrap();
+        };
+        let reader =
+            FramedRead::new(server, TagCodec::with_max_line_length(40)).collect::<Vec<_>>();
+        let ((), reads) = tokio::join!(writer, reader);
+
+        assert_eq!(reads.len(), 2);
+        assert!(matches!(reads[0], Ok(Err(LineError::Io(1, _)))));
+        assert_eq!(reads[1].as_ref().unwrap().as_ref().unwrap().0, 2);
+    }
+
+    #[tokio::test]
+    async fn sink_backpressure() {
+        // The sink waits for the reader to empty the pipe
+        let (client, mut server) = duplex(16);
+        let writer = async move {
+            let mut sink = record_sink(client, RecordFormat::Json, Units::Imperial);
+            for tag in [TAG2, TAG3] {
+                sink.send(AEITagData::new(tag).unwrap()).await.unwrap();
+            }
+        };
+        let reader = async move {
+            let mut out = String::new();
+            server.read_to_string(&mut out).await.unwrap();
+            out
+        };
+        let ((), out) = tokio::join!(writer, reader);
+
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            AEITagData::new(TAG2).unwrap().to_json_in(Units::Imperial)
+        );
+        assert!(lines[1].contains("\"car_number\":85123"));
+    }
+}
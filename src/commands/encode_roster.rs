@@ -0,0 +1,89 @@
+use aei_tag_parser::{encode::plan_roster, roster::Roster};
+use clap::{arg, value_parser, ArgMatches, Command};
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::PathBuf,
+    process,
+};
+
+#[cfg(not(tarpaulin_include))]
+pub fn command() -> Command<'static> {
+    Command::new("encode-roster")
+        .about("Generate the left and right tags to program for every car of a fleet roster")
+        .arg(
+            arg!(--roster <FILE> "CSV file listing the cars of the fleet")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--format <FORMAT> "Format of the programming plan")
+                .required(false)
+                .value_parser(["csv", "json", "binary"])
+                .default_value("csv"),
+        )
+        .arg(
+            arg!(-o --output <FILE> "File to write the plan to instead of stdout")
+                .required(false)
+                .value_parser(value_parser!(PathBuf)),
+        )
+}
+
+#[cfg(not(tarpaulin_include))]
+pub fn run(matches: &ArgMatches) -> i32 {
+    let path = matches
+        .get_one::<PathBuf>("roster")
+        .expect("the roster is required");
+    let roster = Roster::from_path(path).unwrap_or_else(|e| {
+        eprintln!(
+            "Couldn't load roster : {} because {}",
+            path.to_string_lossy(),
+            e
+        );
+        process::exit(1);
+    });
+
+    let mut cars = Vec::new();
+    let mut failed = false;
+    for planned in plan_roster(&roster) {
+        match planned {
+            Ok(car) => cars.push(car),
+            Err(e) => {
+                eprintln!("{}", e);
+                failed = true;
+            }
+        }
+    }
+    // Don't produce a partial plan
+    if failed {
+        return 1;
+    }
+
+    let output = match matches.get_one::<String>("format").map(String::as_str) {
+        Some("json") => {
+            let mut json =
+                serde_json::to_vec(&cars).expect("a programming plan can always be serialized");
+            json.push(b'\n');
+            json
+        }
+        Some("binary") => cars.iter().flat_map(|car| car.raw()).flatten().collect(),
+        _ => {
+            let mut csv = String::from("mark;number;side;tag\n");
+            for car in &cars {
+                csv += &format!("{};{};Left;{}\n", car.mark, car.number, car.left);
+                csv += &format!("{};{};Right;{}\n", car.mark, car.number, car.right);
+            }
+            csv.into_bytes()
+        }
+    };
+
+    let written = match matches.get_one::<PathBuf>("output") {
+        Some(path) => File::create(path).and_then(|mut f| f.write_all(&output)),
+        None => io::stdout().write_all(&output),
+    };
+    if let Err(e) = written {
+        eprintln!("Couldn't write the plan because {}", e);
+        return 1;
+    }
+
+    0
+}
@@ -0,0 +1,112 @@
+use aei_tag_parser::{
+    edi418::{self, Envelope, LoadStatus},
+    t94::T94Report,
+};
+use chrono::Utc;
+use clap::{arg, value_parser, ArgGroup, ArgMatches, Command};
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+#[cfg(not(tarpaulin_include))]
+pub fn command() -> Command<'static> {
+    Command::new("export")
+        .about("Write the consist of every train passage in an interchange format")
+        .args(crate::input_args())
+        .args(super::passage_args())
+        .arg(arg!(--t94 "Write AAR T-94 style train consist reports").requires("site"))
+        .arg(
+            arg!(--edi418 "Write an X12 EDI 418 advance interchange consist")
+                .requires_all(&["sender", "receiver"]),
+        )
+        .group(
+            ArgGroup::new("export-format")
+                .args(&["t94", "edi418"])
+                .required(true),
+        )
+        .arg(
+            arg!(--site <ID> "Identifier of the AEI site, up to 7 letters or digits")
+                .required(false),
+        )
+        .arg(arg!(--sender <ID> "Interchange ID of the sending railroad").required(false))
+        .arg(arg!(--receiver <ID> "Interchange ID of the receiving railroad").required(false))
+        .arg(
+            arg!(--"control-number" <N> "Control number of the interchange")
+                .required(false)
+                .value_parser(value_parser!(u32))
+                .default_value("1"),
+        )
+        .arg(
+            arg!(--"load-status" <STATUS> "Load status written for every vehicle, left out by default")
+                .required(false)
+                .value_parser(["loaded", "empty"]),
+        )
+        .arg(arg!(--"test-interchange" "Mark the interchange as a test"))
+        .arg(
+            arg!(-o --output <FILE> "File the reports are written to instead of stdout")
+                .required(false)
+                .value_parser(value_parser!(PathBuf)),
+        )
+}
+
+/// Writes the consists of the train passages and returns the exit code, 1 when they couldn't be
+/// written
+#[cfg(not(tarpaulin_include))]
+pub fn run(matches: &ArgMatches) -> i32 {
+    let consists: Vec<_> = super::consists(matches)
+        .into_iter()
+        .map(|(_, consist)| consist)
+        .collect();
+
+    let output = if matches.contains_id("edi418") {
+        let mut envelope = Envelope::new(
+            matches
+                .get_one::<String>("sender")
+                .expect("the sender is required"),
+            matches
+                .get_one::<String>("receiver")
+                .expect("the receiver is required"),
+            *matches.get_one::<u32>("control-number").unwrap_or(&1),
+            Utc::now(),
+        );
+        envelope.test = matches.contains_id("test-interchange");
+        let load_status = match matches.get_one::<String>("load-status").map(String::as_str) {
+            Some("loaded") => Some(LoadStatus::Loaded),
+            Some("empty") => Some(LoadStatus::Empty),
+            _ => None,
+        };
+        edi418::interchange(&consists, &envelope, load_status).map_err(|e| e.to_string())
+    } else {
+        let site = matches
+            .get_one::<String>("site")
+            .expect("the site is required");
+        consists
+            .iter()
+            .map(|consist| {
+                T94Report::from_consist(consist, site)
+                    .map(|report| report.to_records())
+                    .map_err(|e| e.to_string())
+            })
+            .collect()
+    };
+    let output = match output {
+        Ok(output) => output,
+        Err(e) => {
+            eprintln!("Couldn't write the consists because {}", e);
+            return 1;
+        }
+    };
+
+    let written = match matches.get_one::<PathBuf>("output") {
+        Some(path) => File::create(path).and_then(|mut f| f.write_all(output.as_bytes())),
+        None => io::stdout().write_all(output.as_bytes()),
+    };
+    if let Err(e) = written {
+        eprintln!("Couldn't write the consists because {}", e);
+        return 1;
+    }
+
+    0
+}
@@ -0,0 +1,114 @@
+use aei_tag_parser::{
+    lint::{LintConfig, Linter, Severity, Violation},
+    stream::LineError,
+};
+use clap::{arg, value_parser, ArgMatches, Command};
+use serde_json::json;
+use std::{fs, path::PathBuf, process};
+
+/// Exit code when a tag has a violation at or above the `--fail-on` severity
+const EXIT_VIOLATIONS: i32 = 1;
+/// Exit code when a tag couldn't be parsed
+const EXIT_INVALID_TAG: i32 = 2;
+
+#[cfg(not(tarpaulin_include))]
+pub fn command() -> Command<'static> {
+    Command::new("lint")
+        .about("Check the tags for programming mistakes")
+        .args(crate::input_args())
+        .arg(
+            arg!(--config <FILE> "TOML file configuring the rules thresholds and severities")
+                .required(false)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--"fail-on" <SEVERITY> "Lowest severity making the command fail")
+                .required(false)
+                .value_parser(["info", "warning", "error"])
+                .default_value("error"),
+        )
+        .arg(arg!(--json "Print the violations in JSON format"))
+}
+
+/// Lints the tags and returns the exit code : 0 when every tag passed, 1 when a violation
+/// reached the `--fail-on` severity and 2 when a tag couldn't be parsed
+#[cfg(not(tarpaulin_include))]
+pub fn run(matches: &ArgMatches) -> i32 {
+    let config = match matches.get_one::<PathBuf>("config") {
+        Some(path) => load_config(path),
+        None => LintConfig::default(),
+    };
+    let fail_on = match matches.get_one::<String>("fail-on").map(String::as_str) {
+        Some("info") => Severity::Info,
+        Some("warning") => Severity::Warning,
+        _ => Severity::Error,
+    };
+    let linter = Linter::new(config);
+
+    let mut exit_code = 0;
+    for read in crate::read_tags(matches) {
+        let (val, violations) = match read {
+            Ok(tag) => (hex::encode_upper(tag.raw()), linter.lint(&tag)),
+            Err(LineError::Tag(_, val, e) | LineError::Argument(_, val, e)) => {
+                print_invalid(&val, &e.to_string(), matches.contains_id("json"));
+                exit_code = EXIT_INVALID_TAG;
+                continue;
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                exit_code = EXIT_INVALID_TAG;
+                continue;
+            }
+        };
+
+        if violations.iter().any(|v| v.severity >= fail_on) && exit_code == 0 {
+            exit_code = EXIT_VIOLATIONS;
+        }
+        print_violations(&val, &violations, matches.contains_id("json"));
+    }
+
+    exit_code
+}
+
+#[cfg(not(tarpaulin_include))]
+fn load_config(path: &PathBuf) -> LintConfig {
+    let config = fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!(
+            "Couldn't open file : {} because {}",
+            path.to_string_lossy(),
+            e
+        );
+        process::exit(1);
+    });
+
+    LintConfig::from_toml(&config).unwrap_or_else(|e| {
+        eprintln!(
+            "Invalid lint configuration : {} because {}",
+            path.to_string_lossy(),
+            e
+        );
+        process::exit(1);
+    })
+}
+
+#[cfg(not(tarpaulin_include))]
+fn print_violations(tag: &str, violations: &[Violation], json: bool) {
+    if json {
+        println!("{}", json!({ "tag": tag, "violations": violations }));
+    } else if violations.is_empty() {
+        println!("{} : OK", tag);
+    } else {
+        for violation in violations {
+            println!("{} : {}", tag, violation);
+        }
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+fn print_invalid(tag: &str, error: &str, json: bool) {
+    if json {
+        println!("{}", json!({ "tag": tag, "error": error }));
+    } else {
+        println!("{} : {}", tag, error);
+    }
+}
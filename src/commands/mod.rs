@@ -0,0 +1,136 @@
+//! Subcommands of the CLI
+
+pub mod encode_roster;
+pub mod export;
+pub mod lint;
+pub mod reconcile;
+pub mod summary;
+pub mod verify_consist;
+
+use aei_tag_parser::{
+    consist::Consist,
+    orientation::{self, SiteConfig},
+    read::TagRead,
+    reader::transcore::{Message, MessageStream},
+    stream::LineError,
+    train::{self, Passage, Rules, Segmenter},
+    AEITagData,
+};
+use chrono::{Duration, Utc};
+use clap::{arg, value_parser, Arg, ArgMatches};
+use std::{collections::BTreeMap, io::BufRead, path::PathBuf, process};
+
+/// Arguments grouping the reads into train passages, shared by the commands working per train
+#[cfg(not(tarpaulin_include))]
+pub fn passage_args() -> Vec<Arg<'static>> {
+    vec![
+        arg!(--reads <FILE> "CSV file of timestamped reads, the tags of the input being stamped as they are received otherwise")
+            .required(false)
+            .value_parser(value_parser!(PathBuf))
+            .conflicts_with_all(&["file", "stdin", "tags"]),
+        arg!(--gap <SECONDS> "Longest time without a read within a train passage")
+            .required(false)
+            .value_parser(value_parser!(u32))
+            .default_value("30"),
+        arg!(--"site-config" <FILE> "TOML file giving the side of the track of each antenna")
+            .required(false)
+            .value_parser(value_parser!(PathBuf)),
+    ]
+}
+
+/// Train passages of the input, with their consist, in the order of time
+#[cfg(not(tarpaulin_include))]
+pub fn consists(matches: &ArgMatches) -> Vec<(Passage, Consist)> {
+    let rules = Rules {
+        max_gap: Duration::seconds(*matches.get_one::<u32>("gap").unwrap_or(&30) as i64),
+        ..Rules::default()
+    };
+
+    let mut passages = Vec::new();
+    let reads: Vec<TagRead> = match matches.get_one::<PathBuf>("reads") {
+        Some(path) => TagRead::from_path(path).unwrap_or_else(|e| {
+            eprintln!(
+                "Couldn't load reads : {} because {}",
+                path.to_string_lossy(),
+                e
+            );
+            process::exit(1);
+        }),
+        None => {
+            let tags: Box<dyn Iterator<Item = Result<AEITagData, LineError>>> =
+                if crate::is_transcore(matches) {
+                    // The reads of the reader keep their timestamp and its presence detector
+                    // delimits the passages
+                    if let Some((reader, name)) = crate::open_input(matches) {
+                        passages = transcore_passages(reader, &name, rules);
+                    }
+                    Box::new(crate::tag_args(matches))
+                } else {
+                    crate::read_tags(matches)
+                };
+            tags.filter_map(|read| match read {
+                Ok(tag) => Some(TagRead::new(tag, Utc::now(), "input")),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    None
+                }
+            })
+            .collect()
+        }
+    };
+
+    let site = matches.get_one::<PathBuf>("site-config").map(|path| {
+        SiteConfig::from_path(path).unwrap_or_else(|e| {
+            eprintln!(
+                "Couldn't load site configuration : {} because {}",
+                path.to_string_lossy(),
+                e
+            );
+            process::exit(1);
+        })
+    });
+
+    // The passages are segmented per reader, from its reads in the order of time
+    let mut by_reader: BTreeMap<String, Vec<TagRead>> = BTreeMap::new();
+    for read in reads {
+        by_reader.entry(read.reader.clone()).or_default().push(read);
+    }
+    passages.extend(by_reader.into_values().flat_map(|mut reads| {
+        reads.sort();
+        train::segment(reads, rules)
+    }));
+    passages.sort_by_key(|passage| passage.start);
+
+    passages
+        .into_iter()
+        .map(|passage| {
+            let mut consist = Consist::from_passage(&passage);
+            if let Some(site) = &site {
+                orientation::orient(&mut consist, &passage.reads, site);
+            }
+            (passage, consist)
+        })
+        .collect()
+}
+
+/// Passages of the messages of a TransCore reader, ended by a gap without reads or when its
+/// presence detector turns off
+#[cfg(not(tarpaulin_include))]
+fn transcore_passages<R: BufRead>(reader: R, name: &str, rules: Rules) -> Vec<Passage> {
+    let mut segmenter = Segmenter::new(rules);
+    let mut passages = Vec::new();
+    for message in MessageStream::new(reader, name) {
+        let ended = match message {
+            Ok((_, Message::Tag(read) | Message::Buffered(read))) => segmenter.push(read),
+            Ok((_, Message::Presence { present, .. })) => segmenter.presence(present),
+            Ok(_) => None,
+            Err(e) => {
+                eprintln!("{}", e);
+                None
+            }
+        };
+        passages.extend(ended);
+    }
+    passages.extend(segmenter.finish());
+    passages
+}
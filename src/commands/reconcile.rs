@@ -0,0 +1,73 @@
+use aei_tag_parser::{reconcile::Reconciler, roster::Roster};
+use clap::{arg, value_parser, ArgMatches, Command};
+use std::{path::PathBuf, process};
+
+#[cfg(not(tarpaulin_include))]
+pub fn command() -> Command<'static> {
+    Command::new("reconcile")
+        .about("Compare the tags with a fleet roster")
+        .args(crate::input_args())
+        .arg(
+            arg!(--roster <FILE> "CSV file listing the cars of the fleet")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--"length-tolerance" <DM> "Largest difference of length not reported, in decimeters")
+                .required(false)
+                .value_parser(value_parser!(u16))
+                .default_value("0"),
+        )
+        .arg(arg!(--json "Print the result in JSON format"))
+}
+
+#[cfg(not(tarpaulin_include))]
+pub fn run(matches: &ArgMatches) -> i32 {
+    let path = matches
+        .get_one::<PathBuf>("roster")
+        .expect("the roster is required");
+    let roster = Roster::from_path(path).unwrap_or_else(|e| {
+        eprintln!(
+            "Couldn't load roster : {} because {}",
+            path.to_string_lossy(),
+            e
+        );
+        process::exit(1);
+    });
+
+    let tags = crate::read_tags(matches).filter_map(|read| match read {
+        Ok(tag) => Some(tag),
+        Err(e) => {
+            eprintln!("{}", e);
+            None
+        }
+    });
+    let result = Reconciler::new(&roster)
+        .length_tolerance(*matches.get_one::<u16>("length-tolerance").unwrap_or(&0))
+        .reconcile(tags);
+
+    if matches.contains_id("json") {
+        println!(
+            "{}",
+            serde_json::to_string(&result).expect("a reconciliation can always be serialized")
+        );
+    } else {
+        for mismatch in &result.mismatches {
+            println!("MISMATCH : {}", mismatch);
+        }
+        for car in &result.unseen {
+            println!("UNSEEN   : {}", car);
+        }
+        for seen in &result.unknown {
+            println!("UNKNOWN  : {}", seen);
+        }
+        println!(
+            "{} tag(s) matching the roster, {} mismatch(es), {} car(s) not seen, {} tag(s) not on the roster",
+            result.matched,
+            result.mismatches.len(),
+            result.unseen.len(),
+            result.unknown.len()
+        );
+    }
+
+    0
+}
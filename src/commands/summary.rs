@@ -0,0 +1,28 @@
+use aei_tag_parser::summary::Summary;
+use clap::{arg, Command};
+
+#[cfg(not(tarpaulin_include))]
+pub fn command() -> Command<'static> {
+    Command::new("summary")
+        .about("Print the vehicle, axle and length totals of every train passage")
+        .args(crate::input_args())
+        .args(super::passage_args())
+        .arg(arg!(--json "Print the summaries in JSON format, one per line"))
+}
+
+#[cfg(not(tarpaulin_include))]
+pub fn run(matches: &clap::ArgMatches) -> i32 {
+    for (passage, consist) in super::consists(matches) {
+        let summary = Summary {
+            start: Some(passage.start),
+            end: Some(passage.end),
+            ..Summary::from_consist(&consist)
+        };
+        if matches.contains_id("json") {
+            println!("{}", summary.to_json());
+        } else {
+            println!("{}", summary);
+        }
+    }
+    0
+}
@@ -0,0 +1,68 @@
+use aei_tag_parser::{roster::Roster, verify::Verifier};
+use chrono::SecondsFormat;
+use clap::{arg, value_parser, ArgMatches, Command};
+use std::{path::PathBuf, process};
+
+/// Exit code when a consist differs from the train list
+const EXIT_DIFFERENCES: i32 = 1;
+
+#[cfg(not(tarpaulin_include))]
+pub fn command() -> Command<'static> {
+    Command::new("verify-consist")
+        .about("Compare the consist of every train passage with the planned train list")
+        .args(crate::input_args())
+        .args(super::passage_args())
+        .arg(
+            arg!(--plan <FILE> "CSV file listing the planned cars in order, with the columns of a roster")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--"length-tolerance" <DM> "Largest difference of length not reported, in decimeters")
+                .required(false)
+                .value_parser(value_parser!(u16))
+                .default_value("0"),
+        )
+        .arg(arg!(--json "Print the verifications in JSON format, one per line"))
+}
+
+/// Verifies the consists and returns the exit code : 0 when every consist is the planned one, 1
+/// otherwise
+#[cfg(not(tarpaulin_include))]
+pub fn run(matches: &ArgMatches) -> i32 {
+    let path = matches
+        .get_one::<PathBuf>("plan")
+        .expect("the train list is required");
+    let plan = Roster::from_path(path).unwrap_or_else(|e| {
+        eprintln!(
+            "Couldn't load train list : {} because {}",
+            path.to_string_lossy(),
+            e
+        );
+        process::exit(1);
+    });
+    let verifier = Verifier::new(&plan)
+        .length_tolerance(*matches.get_one::<u16>("length-tolerance").unwrap_or(&0));
+
+    let mut exit_code = 0;
+    for (passage, consist) in super::consists(matches) {
+        let result = verifier.verify(&consist);
+        if !result.is_clean() {
+            exit_code = EXIT_DIFFERENCES;
+        }
+
+        if matches.contains_id("json") {
+            println!(
+                "{}",
+                serde_json::to_string(&result).expect("a verification can always be serialized")
+            );
+        } else {
+            println!(
+                "{} - {} :\n{}",
+                passage.start.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+                passage.end.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+                result
+            );
+        }
+    }
+    exit_code
+}
@@ -0,0 +1,414 @@
+//! Vehicles of a train, built from the reads of its passage
+//!
+//! Each vehicle carries two tags, one on each side. A [`ConsistBuilder`] takes the reads of one
+//! train in time order, merges the repeated reads of a tag and pairs the two tags of each vehicle.
+//! The vehicles are numbered in the order they passed the reader, those of which a single tag was
+//! read being flagged.
+//!
+//! ```rust
+//! use aei_tag_parser::{consist::ConsistBuilder, read::TagRead, AEITagData};
+//! use chrono::{Duration, TimeZone, Utc};
+//!
+//! let start = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+//! let tags = [
+//!     "9EA488C030426A179000000000000331", // IOCC 3088 right
+//!     "9EA488C030426A179000000000000331",
+//!     "9EA488C030406A1F9000000000000331", // IOCC 3088 left
+//!     "9EA488C5320CC01B9000000000000331", // IOCC 85123 left
+//! ];
+//! let mut builder = ConsistBuilder::new();
+//! for (i, tag) in tags.iter().enumerate() {
+//!     let read = TagRead::new(AEITagData::new(tag).unwrap(), start + Duration::seconds(i as i64), "R1");
+//!     builder.push(read);
+//! }
+//! let consist = builder.build();
+//!
+//! assert_eq!(consist.vehicles.len(), 2);
+//! assert_eq!(consist.vehicles[0].read_count, 3);
+//! assert!(consist.vehicles[1].is_single_tag());
+//! ```
+
+use crate::{
+    orientation::{Direction, Hood, Orientation, LOCOMOTIVE_GROUP},
+    read::TagRead,
+    train::Passage,
+    AEITagData, Side,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+/// Header of the CSV representation of a consist
+pub const CSV_HEADER: &str = "position;mark;number;group;sides;left;right;read_count;first_seen;last_seen;orientation;hood_forward";
+
+/// Identity of a vehicle, shared by its two tags
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+pub struct VehicleId {
+    pub mark: String,
+    pub number: u32,
+    /// Equipment group code
+    pub group: u8,
+}
+
+impl VehicleId {
+    /// Identity of the vehicle carrying the tag, the mark being stripped of the padding of the
+    /// marks shorter than 4 letters
+    pub fn of(tag: &AEITagData) -> VehicleId {
+        VehicleId {
+            mark: String::from(tag.equipment_initial().trim_end()),
+            number: tag.car_number(),
+            group: tag.equipment_group_code(),
+        }
+    }
+}
+
+impl Display for VehicleId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.mark, self.number)
+    }
+}
+
+/// Tags of a vehicle which were read
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sides {
+    Both,
+    Left,
+    Right,
+}
+
+impl Display for Sides {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Sides::Both => write!(f, "Both"),
+            Sides::Left => write!(f, "Left"),
+            Sides::Right => write!(f, "Right"),
+        }
+    }
+}
+
+/// Vehicle of a consist
+#[derive(Clone, PartialEq, Debug)]
+pub struct Vehicle {
+    /// Position in the train, from 1, in the order the vehicles passed the reader
+    pub position: usize,
+    pub id: VehicleId,
+    /// First read of the left side tag
+    pub left: Option<TagRead>,
+    /// First read of the right side tag
+    pub right: Option<TagRead>,
+    /// Number of reads of the two tags
+    pub read_count: u32,
+    /// Timestamp of the last read of either tag
+    pub last_seen: DateTime<Utc>,
+    /// Leading end, once inferred by [`orient`](crate::orientation::orient)
+    pub orientation: Option<Orientation>,
+}
+
+impl Vehicle {
+    fn new(read: TagRead) -> Vehicle {
+        let mut vehicle = Vehicle {
+            position: 0,
+            id: VehicleId::of(&read.tag),
+            left: None,
+            right: None,
+            read_count: 0,
+            last_seen: read.timestamp,
+            orientation: None,
+        };
+        vehicle.push(read);
+        vehicle
+    }
+
+    /// Merges a read of one of the tags of the vehicle
+    fn push(&mut self, read: TagRead) {
+        self.read_count += read.read_count;
+        self.last_seen = self.last_seen.max(read.timestamp);
+        let side = match read.tag.side_indicator() {
+            Side::LEFT => &mut self.left,
+            Side::RIGHT => &mut self.right,
+        };
+        match side {
+            Some(first) if first.timestamp <= read.timestamp => (),
+            _ => *side = Some(read),
+        }
+    }
+
+    /// Tags read
+    pub fn sides(&self) -> Sides {
+        match (&self.left, &self.right) {
+            (Some(_), None) => Sides::Left,
+            (None, Some(_)) => Sides::Right,
+            _ => Sides::Both,
+        }
+    }
+
+    /// Only one of the tags of the vehicle was read
+    pub fn is_single_tag(&self) -> bool {
+        self.sides() != Sides::Both
+    }
+
+    /// Timestamp of the first read of either tag
+    pub fn first_seen(&self) -> DateTime<Utc> {
+        self.reads()
+            .map(|read| read.timestamp)
+            .min()
+            .expect("a vehicle has at least one tag read")
+    }
+
+    /// Tag of the vehicle, the left one if both were read
+    pub fn tag(&self) -> &AEITagData {
+        &self
+            .reads()
+            .next()
+            .expect("a vehicle has at least one tag read")
+            .tag
+    }
+
+    /// Hood forward of a locomotive of known orientation
+    pub fn hood_forward(&self) -> Option<Hood> {
+        if self.id.group == LOCOMOTIVE_GROUP {
+            self.orientation.map(Hood::forward)
+        } else {
+            None
+        }
+    }
+
+    /// First read of each tag read, left first
+    pub fn reads(&self) -> impl Iterator<Item = &TagRead> {
+        self.left.iter().chain(self.right.iter())
+    }
+
+    /// Returns a line in CSV format, with the columns of [`CSV_HEADER`]
+    pub fn to_csv(&self) -> String {
+        let record = VehicleRecord::from(self);
+        format!(
+            "{};{};{};{};{};{};{};{};{};{};{};{}",
+            record.position,
+            record.mark,
+            record.number,
+            record.group,
+            record.sides,
+            record.left.unwrap_or_default(),
+            record.right.unwrap_or_default(),
+            record.read_count,
+            self.first_seen()
+                .to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            self.last_seen.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            self.orientation.map(|o| o.to_string()).unwrap_or_default(),
+            self.hood_forward()
+                .map(|h| h.to_string())
+                .unwrap_or_default()
+        )
+    }
+}
+
+/// Exported representation of a vehicle, the tags being written in hexadecimal
+#[derive(Serialize)]
+struct VehicleRecord {
+    position: usize,
+    mark: String,
+    number: u32,
+    group: u8,
+    sides: Sides,
+    single_tag: bool,
+    left: Option<String>,
+    right: Option<String>,
+    read_count: u32,
+    first_seen: DateTime<Utc>,
+    last_seen: DateTime<Utc>,
+    orientation: Option<Orientation>,
+    hood_forward: Option<Hood>,
+}
+
+impl From<&Vehicle> for VehicleRecord {
+    fn from(vehicle: &Vehicle) -> Self {
+        let hex = |read: &Option<TagRead>| read.as_ref().map(|r| hex::encode_upper(r.tag.raw()));
+        VehicleRecord {
+            position: vehicle.position,
+            mark: vehicle.id.mark.clone(),
+            number: vehicle.id.number,
+            group: vehicle.id.group,
+            sides: vehicle.sides(),
+            single_tag: vehicle.is_single_tag(),
+            left: hex(&vehicle.left),
+            right: hex(&vehicle.right),
+            read_count: vehicle.read_count,
+            first_seen: vehicle.first_seen(),
+            last_seen: vehicle.last_seen,
+            orientation: vehicle.orientation,
+            hood_forward: vehicle.hood_forward(),
+        }
+    }
+}
+
+/// Ordered vehicles of a train
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Consist {
+    pub vehicles: Vec<Vehicle>,
+    /// Direction of travel, once inferred by [`orient`](crate::orientation::orient)
+    pub direction: Option<Direction>,
+}
+
+impl Consist {
+    /// Consist of the reads of a passage
+    pub fn from_passage(passage: &Passage) -> Consist {
+        let mut builder = ConsistBuilder::new();
+        for read in &passage.reads {
+            builder.push(read.clone());
+        }
+        builder.build()
+    }
+
+    /// Vehicles of which a single tag was read
+    pub fn single_tag_vehicles(&self) -> impl Iterator<Item = &Vehicle> {
+        self.vehicles
+            .iter()
+            .filter(|vehicle| vehicle.is_single_tag())
+    }
+
+    /// Returns the consist in CSV format, a header line followed by one line per vehicle
+    pub fn to_csv(&self) -> String {
+        let mut out = format!("{}\n", CSV_HEADER);
+        for vehicle in &self.vehicles {
+            out.push_str(&vehicle.to_csv());
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Returns the vehicles serialized in a JSON array
+    pub fn to_json(&self) -> String {
+        let records: Vec<VehicleRecord> = self.vehicles.iter().map(VehicleRecord::from).collect();
+        serde_json::to_string(&records).expect("a consist can always be serialized")
+    }
+}
+
+/// Builds a consist from the reads of a train
+#[derive(Clone, Debug, Default)]
+pub struct ConsistBuilder {
+    vehicles: Vec<Vehicle>,
+}
+
+impl ConsistBuilder {
+    pub fn new() -> ConsistBuilder {
+        ConsistBuilder::default()
+    }
+
+    /// Adds a read, merged with the reads of the other tag of the vehicle if it was already read
+    pub fn push(&mut self, read: TagRead) {
+        let id = VehicleId::of(&read.tag);
+        match self.vehicles.iter_mut().find(|vehicle| vehicle.id == id) {
+            Some(vehicle) => vehicle.push(read),
+            None => self.vehicles.push(Vehicle::new(read)),
+        }
+    }
+
+    /// Numbers the vehicles in the order they were first seen
+    pub fn build(mut self) -> Consist {
+        self.vehicles.sort_by_key(|vehicle| vehicle.first_seen());
+        for (i, vehicle) in self.vehicles.iter_mut().enumerate() {
+            vehicle.position = i + 1;
+        }
+        Consist {
+            vehicles: self.vehicles,
+            direction: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    /// QNSL 502 right
+    static TAG1: &str = "2F3E06C007DB1E139000000000000331";
+    /// IOCC 3088 right
+    static TAG2: &str = "9EA488C030426A179000000000000331";
+    /// IOCC 85123 left
+    static TAG3: &str = "9EA488C5320CC01B9000000000000331";
+    /// QNSL 502 left
+    static TAG1_LEFT: &str = "2F3E06C007D91E1B9000000000000331";
+    /// IOCC 3088 left
+    static TAG2_LEFT: &str = "9EA488C030406A1F9000000000000331";
+
+    fn read(tag: &str, seconds: i64) -> TagRead {
+        let start = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        TagRead::new(
+            AEITagData::new(tag).unwrap(),
+            start + Duration::seconds(seconds),
+            "R1",
+        )
+    }
+
+    fn consist(reads: &[(&str, i64)]) -> Consist {
+        let mut builder = ConsistBuilder::new();
+        for (tag, seconds) in reads {
+            builder.push(read(tag, *seconds));
+        }
+        builder.build()
+    }
+
+    #[test]
+    fn pairing() {
+        let consist = consist(&[
+            (TAG1, 0),
+            (TAG1, 1),
+            (TAG1_LEFT, 2),
+            (TAG2_LEFT, 5),
+            (TAG2, 6),
+            (TAG2_LEFT, 7),
+            (TAG3, 10),
+        ]);
+
+        let ids: Vec<String> = consist.vehicles.iter().map(|v| v.id.to_string()).collect();
+        assert_eq!(ids, vec!["QNSL 502", "IOCC 3088", "IOCC 85123"]);
+        let positions: Vec<usize> = consist.vehicles.iter().map(|v| v.position).collect();
+        assert_eq!(positions, vec![1, 2, 3]);
+
+        let locomotive = &consist.vehicles[0];
+        assert_eq!(locomotive.sides(), Sides::Both);
+        assert_eq!(locomotive.read_count, 3);
+        assert_eq!(
+            locomotive.right.as_ref().unwrap().timestamp,
+            read(TAG1, 0).timestamp
+        );
+        assert_eq!(locomotive.last_seen, read(TAG1, 2).timestamp);
+
+        assert_eq!(consist.vehicles[1].first_seen(), read(TAG2, 5).timestamp);
+        assert_eq!(consist.vehicles[2].sides(), Sides::Left);
+        let single: Vec<usize> = consist.single_tag_vehicles().map(|v| v.position).collect();
+        assert_eq!(single, vec![3]);
+    }
+
+    #[test]
+    fn late_reads() {
+        // A buffered read of the first vehicle arrives after the second vehicle
+        let consist = consist(&[(TAG2, 5), (TAG1, 0)]);
+
+        assert_eq!(consist.vehicles[0].id.number, 502);
+        assert_eq!(consist.vehicles[1].position, 2);
+    }
+
+    #[test]
+    fn export() {
+        let consist = consist(&[(TAG2, 0), (TAG2_LEFT, 1), (TAG3, 5)]);
+
+        assert_eq!(
+            consist.to_csv(),
+            format!(
+                "{}\n1;IOCC;3088;19;Both;{};{};2;2024-05-01T12:00:00Z;2024-05-01T12:00:01Z;;\n2;IOCC;85123;19;Left;{};;1;2024-05-01T12:00:05Z;2024-05-01T12:00:05Z;;\n",
+                CSV_HEADER, TAG2_LEFT, TAG2, TAG3
+            )
+        );
+
+        let json: serde_json::Value = serde_json::from_str(&consist.to_json()).unwrap();
+        assert_eq!(json[0]["mark"], "IOCC");
+        assert_eq!(json[0]["single_tag"], false);
+        assert_eq!(json[1]["sides"], "left");
+        assert_eq!(json[1]["right"], serde_json::Value::Null);
+        assert_eq!(json[1]["first_seen"], "2024-05-01T12:00:05Z");
+        assert_eq!(json[1]["orientation"], serde_json::Value::Null);
+    }
+}
@@ -0,0 +1,36 @@
+//! Reading of the CSV files supplied by the user (registries, rosters, train lists), and quoting
+//! of the free text fields written in the CSV output
+
+use std::borrow::Cow;
+
+/// CSV reader over `data`, with headers. The delimiter is `;`, as in the CSV output of the
+/// tags, when the first line contains one and `,` otherwise. Lines starting with `#` are ignored
+pub(crate) fn reader(data: &str) -> csv::Reader<&[u8]> {
+    let header = data
+        .lines()
+        .find(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .unwrap_or_default();
+    let delimiter = if header.contains(';') { b';' } else { b',' };
+
+    csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .comment(Some(b'#'))
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(data.as_bytes())
+}
+
+/// Field of a `;` separated line, quoted when it holds a separator, a quote or a line break. The
+/// quotes of a quoted field are doubled
+pub(crate) fn quote(field: &str) -> Cow<'_, str> {
+    if field.contains([';', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// Deserializes every record of the CSV data
+pub(crate) fn records<T: serde::de::DeserializeOwned>(data: &str) -> Result<Vec<T>, csv::Error> {
+    reader(data).deserialize().collect()
+}
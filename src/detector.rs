@@ -0,0 +1,469 @@
+//! Mapping of the wayside detector alarms to the vehicles of a train
+//!
+//! Hot bearing, hot wheel and wheel impact detectors report their alarms by axle, counted from the
+//! head of the train, and by side of the track. [`map_alarms`] finds the vehicle of the axle from
+//! the number of axles of the tags of the consist, and the axle within the vehicle counted from
+//! its leading end. Once the direction of the train and the orientation of the vehicle are known,
+//! the alarm is also given in the AAR designation of the wheel : the axles are numbered from the B
+//! end and the sides are seen standing at the B end facing the A end.
+//!
+//! The consist alone misses the vehicles of which no tag was read : the axles of such a vehicle
+//! shift every alarm behind it onto the wrong vehicle, and nothing tells it. When the site counts
+//! the axles, [`map_fused_alarms`] locates the vehicles from the axles counted for each of them by
+//! the [`fusion`](crate::fusion) instead. The alarms on an untagged vehicle are then mapped to it
+//! and the alarms at or behind an untagged vehicle or an axle count mismatch are flagged as
+//! unreliable, the axles of the gap being uncertain.
+//!
+//! ```rust
+//! use aei_tag_parser::{
+//!     consist::ConsistBuilder,
+//!     detector::{map_alarms, AlarmKind, DetectorAlarm},
+//!     orientation::TrackSide,
+//!     read::TagRead,
+//!     AEITagData,
+//! };
+//! use chrono::Utc;
+//!
+//! let mut builder = ConsistBuilder::new();
+//! builder.push(TagRead::new(AEITagData::new("9EA488C030426A179000000000000331").unwrap(), Utc::now(), "R1"));
+//! let alarm = DetectorAlarm { axle: 3, side: TrackSide::Left, kind: AlarmKind::HotBearing, reading: Some(95.0) };
+//!
+//! let alarms = map_alarms(&builder.build(), &[alarm]);
+//! assert_eq!(alarms[0].vehicle.as_ref().unwrap().number, 3088);
+//! assert_eq!(alarms[0].leading_axle, Some(3));
+//! ```
+
+use crate::{
+    consist::{Consist, Vehicle, VehicleId},
+    fusion::Fusion,
+    orientation::{Orientation, TrackSide},
+    summary::is_vehicle_group,
+    Side,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+/// Defect detected
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlarmKind {
+    HotBearing,
+    HotWheel,
+    WheelImpact,
+}
+
+impl Display for AlarmKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            AlarmKind::HotBearing => write!(f, "hot bearing"),
+            AlarmKind::HotWheel => write!(f, "hot wheel"),
+            AlarmKind::WheelImpact => write!(f, "wheel impact"),
+        }
+    }
+}
+
+/// Alarm of a wayside detector
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct DetectorAlarm {
+    /// Axle counted from the head of the train, from 1
+    pub axle: u32,
+    /// Side of the track, as seen from a train moving in the reference direction of the site
+    pub side: TrackSide,
+    pub kind: AlarmKind,
+    /// Measured value, in the unit of the detector
+    #[serde(default)]
+    pub reading: Option<f64>,
+}
+
+/// Alarm mapped to a vehicle of the consist
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct MappedAlarm {
+    pub alarm: DetectorAlarm,
+    /// Position of the vehicle in the consist, none when the axle is beyond the consist
+    pub position: Option<usize>,
+    pub vehicle: Option<VehicleId>,
+    /// Axle of the vehicle counted from its leading end, from 1
+    pub leading_axle: Option<u8>,
+    /// AAR axle number, counted from the B end, when the orientation of the vehicle is known
+    pub axle: Option<u8>,
+    /// Side of the vehicle, standing at the B end facing the A end, when the direction of the
+    /// train and the orientation of the vehicle are known
+    pub side: Option<Side>,
+    pub bearing_type_code: Option<u8>,
+    pub bearing_type: Option<String>,
+    /// An untagged vehicle or an axle count mismatch lies ahead of the alarm or on its vehicle,
+    /// which may then be wrong. Only known when the alarms are mapped with the axles counted
+    #[serde(default)]
+    pub unreliable: bool,
+}
+
+impl MappedAlarm {
+    fn unmapped(alarm: &DetectorAlarm) -> MappedAlarm {
+        MappedAlarm {
+            alarm: alarm.clone(),
+            position: None,
+            vehicle: None,
+            leading_axle: None,
+            axle: None,
+            side: None,
+            bearing_type_code: None,
+            bearing_type: None,
+            unreliable: false,
+        }
+    }
+
+    /// AAR designation of the wheel, the side followed by the axle number (`L1`, `R4`...)
+    pub fn wheel(&self) -> Option<String> {
+        let side = match self.side? {
+            Side::LEFT => 'L',
+            Side::RIGHT => 'R',
+        };
+        Some(format!("{}{}", side, self.axle?))
+    }
+}
+
+impl Display for MappedAlarm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} on axle {}", self.alarm.kind, self.alarm.axle)?;
+        if let Some(reading) = self.alarm.reading {
+            write!(f, " ({})", reading)?;
+        }
+        match (&self.vehicle, self.position, self.leading_axle) {
+            (Some(vehicle), Some(position), Some(leading_axle)) => write!(
+                f,
+                " : {} #{}, axle {} from the leading end",
+                vehicle, position, leading_axle
+            )?,
+            (None, _, Some(leading_axle)) => write!(
+                f,
+                " : untagged vehicle, axle {} from the leading end",
+                leading_axle
+            )?,
+            _ => return write!(f, " : beyond the vehicles of the consist"),
+        }
+        if self.vehicle.is_some() {
+            match self.wheel() {
+                Some(wheel) => write!(f, ", wheel {}", wheel)?,
+                None => write!(f, ", orientation unknown")?,
+            }
+        }
+        if let Some(bearing_type) = &self.bearing_type {
+            write!(f, ", {}", bearing_type)?;
+        }
+        if self.unreliable {
+            write!(f, ", unreliable behind an axle count gap")?;
+        }
+        Ok(())
+    }
+}
+
+/// Axles of a vehicle of the train
+struct Span<'a> {
+    /// Number of axles ahead of the vehicle
+    first: u32,
+    axles: u32,
+    /// Vehicle of the consist, none for an untagged vehicle
+    vehicle: Option<&'a Vehicle>,
+    unreliable: bool,
+}
+
+/// Maps the alarms to the vehicles of the consist, the tags of the end-of-train device and of the
+/// containers being left out as they have no axles, see [`is_vehicle_group`]. Every vehicle is
+/// assumed to be tagged : the alarms behind a vehicle missing from the consist are
+/// mapped to the wrong vehicles, see [`map_fused_alarms`]
+pub fn map_alarms(consist: &Consist, alarms: &[DetectorAlarm]) -> Vec<MappedAlarm> {
+    let mut ahead = 0u32;
+    let spans: Vec<Span> = consist
+        .vehicles
+        .iter()
+        .filter(|vehicle| is_vehicle_group(vehicle.id.group))
+        .map(|vehicle| {
+            let axles = u32::from(vehicle.tag().number_axles());
+            ahead += axles;
+            Span {
+                first: ahead - axles,
+                axles,
+                vehicle: Some(vehicle),
+                unreliable: false,
+            }
+        })
+        .collect();
+    map_spans(consist, &spans, alarms)
+}
+
+/// Maps the alarms to the vehicles located by the axles counted, `fusion` being the fusion of the
+/// consist with the axle counter. The alarms on an untagged vehicle have no vehicle identity, and
+/// the alarms at or behind an untagged vehicle or an axle count mismatch are unreliable
+pub fn map_fused_alarms(
+    consist: &Consist,
+    fusion: &Fusion,
+    alarms: &[DetectorAlarm],
+) -> Vec<MappedAlarm> {
+    let mut vehicles = consist
+        .vehicles
+        .iter()
+        .filter(|vehicle| is_vehicle_group(vehicle.id.group));
+    let mut ahead = 0u32;
+    let mut gap = false;
+    let spans: Vec<Span> = fusion
+        .vehicles
+        .iter()
+        .map(|fused| {
+            let axles = fused.axles.len() as u32;
+            ahead += axles;
+            gap = gap || fused.is_untagged() || fused.is_axle_mismatch();
+            // The tagged vehicles of the fusion are those of the consist, in order
+            let vehicle = match &fused.vehicle {
+                Some(id) => vehicles.find(|vehicle| &vehicle.id == id),
+                None => None,
+            };
+            Span {
+                first: ahead - axles,
+                axles,
+                vehicle,
+                unreliable: gap,
+            }
+        })
+        .collect();
+    map_spans(consist, &spans, alarms)
+}
+
+fn map_spans(consist: &Consist, spans: &[Span], alarms: &[DetectorAlarm]) -> Vec<MappedAlarm> {
+    alarms
+        .iter()
+        .map(|alarm| {
+            let span = match spans
+                .iter()
+                .find(|span| alarm.axle > span.first && alarm.axle <= span.first + span.axles)
+            {
+                Some(span) => span,
+                None => return MappedAlarm::unmapped(alarm),
+            };
+            let leading_axle = u8::try_from(alarm.axle - span.first).ok();
+            let vehicle = match span.vehicle {
+                Some(vehicle) => vehicle,
+                None => {
+                    return MappedAlarm {
+                        leading_axle,
+                        unreliable: span.unreliable,
+                        ..MappedAlarm::unmapped(alarm)
+                    }
+                }
+            };
+
+            let tag = vehicle.tag();
+            let axles = tag.number_axles();
+            // The axles counted beyond those of the tag have no AAR number
+            let axle = match (vehicle.orientation, leading_axle) {
+                (Some(Orientation::BEndLeading), Some(leading_axle)) if leading_axle <= axles => {
+                    Some(leading_axle)
+                }
+                (Some(Orientation::AEndLeading), Some(leading_axle)) if leading_axle <= axles => {
+                    Some(axles - leading_axle + 1)
+                }
+                _ => None,
+            };
+            // The left of the vehicle is on the left of the direction of travel when its A end
+            // leads
+            let side = match (consist.direction, vehicle.orientation) {
+                (Some(direction), Some(Orientation::AEndLeading)) => {
+                    Some(alarm.side.seen_in(direction))
+                }
+                (Some(direction), Some(Orientation::BEndLeading)) => {
+                    match alarm.side.seen_in(direction) {
+                        Side::LEFT => Some(Side::RIGHT),
+                        Side::RIGHT => Some(Side::LEFT),
+                    }
+                }
+                _ => None,
+            };
+
+            MappedAlarm {
+                alarm: alarm.clone(),
+                position: Some(vehicle.position),
+                vehicle: Some(vehicle.id.clone()),
+                leading_axle,
+                axle,
+                side,
+                bearing_type_code: Some(tag.bearing_type_code()),
+                bearing_type: Some(tag.bearing_type()),
+                unreliable: span.unreliable,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        consist::ConsistBuilder,
+        encode::TagBuilder,
+        fusion::{fuse, FusionRules},
+        orientation::Direction,
+        read::TagRead,
+        summary::EOT_GROUP,
+        AEITagData,
+    };
+    use chrono::{Duration, TimeZone, Utc};
+
+    /// QNSL 502 right, 4 axles, roller bearings
+    static TAG1: &str = "2F3E06C007DB1E139000000000000331";
+    /// IOCC 3088 right, 4 axles
+    static TAG2: &str = "9EA488C030426A179000000000000331";
+
+    fn consist() -> Consist {
+        let start = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        let eot = TagBuilder::new("QNSL", 9001)
+            .group(EOT_GROUP)
+            .build()
+            .unwrap();
+        // Container on IOCC 3088, which has no axles of its own
+        let container = TagBuilder::new("TTNU", 1234).group(10).build().unwrap();
+        let tags = [
+            AEITagData::new(TAG1).unwrap(),
+            AEITagData::new(TAG2).unwrap(),
+            container,
+            eot,
+        ];
+        let mut builder = ConsistBuilder::new();
+        for (i, tag) in tags.into_iter().enumerate() {
+            builder.push(TagRead::new(tag, start + Duration::seconds(i as i64), "R1"));
+        }
+        builder.build()
+    }
+
+    fn alarm(axle: u32, side: TrackSide) -> DetectorAlarm {
+        DetectorAlarm {
+            axle,
+            side,
+            kind: AlarmKind::HotBearing,
+            reading: None,
+        }
+    }
+
+    #[test]
+    fn orientation_unknown() {
+        let alarms = map_alarms(
+            &consist(),
+            &[alarm(2, TrackSide::Left), alarm(8, TrackSide::Right)],
+        );
+
+        assert_eq!(alarms[0].position, Some(1));
+        assert_eq!(alarms[0].leading_axle, Some(2));
+        assert_eq!((alarms[0].axle, alarms[0].side), (None, None));
+        assert_eq!(alarms[0].bearing_type_code, Some(1));
+        assert_eq!(
+            alarms[1].to_string(),
+            format!(
+                "hot bearing on axle 8 : IOCC 3088 #2, axle 4 from the leading end, orientation unknown, {}",
+                AEITagData::new(TAG2).unwrap().bearing_type()
+            )
+        );
+    }
+
+    #[test]
+    fn aar_wheels() {
+        let mut consist = consist();
+        consist.direction = Some(Direction::Reference);
+        consist.vehicles[0].orientation = Some(Orientation::AEndLeading);
+        consist.vehicles[1].orientation = Some(Orientation::BEndLeading);
+
+        let alarms = map_alarms(
+            &consist,
+            &[alarm(2, TrackSide::Left), alarm(5, TrackSide::Left)],
+        );
+        assert_eq!(alarms[0].wheel().as_deref(), Some("L3"));
+        assert_eq!(alarms[1].wheel().as_deref(), Some("R1"));
+
+        consist.direction = Some(Direction::Reverse);
+        let alarms = map_alarms(&consist, &[alarm(1, TrackSide::Left)]);
+        assert_eq!(alarms[0].wheel().as_deref(), Some("R4"));
+        assert_eq!(
+            alarms[0].to_string(),
+            "hot bearing on axle 1 : QNSL 502 #1, axle 1 from the leading end, wheel R4, Roller bearing"
+        );
+    }
+
+    /// Axles counted at the given milliseconds from the first read
+    fn axles(milliseconds: &[i64]) -> Vec<chrono::DateTime<Utc>> {
+        let start = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        milliseconds
+            .iter()
+            .map(|ms| start + Duration::milliseconds(*ms))
+            .collect()
+    }
+
+    #[test]
+    fn untagged_vehicle() {
+        let consist = consist();
+        // A car without tags follows QNSL 502
+        let fusion = fuse(
+            &consist,
+            &axles(&[-400, -300, -200, -100, 0, 100, 200, 300, 600, 700, 800, 900]),
+            &FusionRules::default(),
+        );
+        let found = [
+            alarm(2, TrackSide::Left),
+            alarm(6, TrackSide::Left),
+            alarm(10, TrackSide::Left),
+        ];
+
+        let alarms = map_fused_alarms(&consist, &fusion, &found);
+        assert_eq!(alarms[0].position, Some(1));
+        assert!(!alarms[0].unreliable);
+        assert_eq!(
+            (&alarms[1].vehicle, alarms[1].leading_axle),
+            (&None, Some(2))
+        );
+        assert_eq!(
+            alarms[1].to_string(),
+            "hot bearing on axle 6 : untagged vehicle, axle 2 from the leading end, unreliable behind an axle count gap"
+        );
+        assert_eq!(alarms[2].vehicle.as_ref().unwrap().number, 3088);
+        assert_eq!(alarms[2].leading_axle, Some(2));
+        assert!(alarms[2].unreliable);
+
+        // Without the axles counted, the alarm on the untagged car goes to the next one
+        let alarms = map_alarms(&consist, &found);
+        assert_eq!(alarms[1].vehicle.as_ref().unwrap().number, 3088);
+        assert!(!alarms[1].unreliable);
+    }
+
+    #[test]
+    fn axle_count_mismatch() {
+        let mut consist = consist();
+        consist.direction = Some(Direction::Reference);
+        consist.vehicles[0].orientation = Some(Orientation::AEndLeading);
+        // 5 axles counted for QNSL 502
+        let fusion = fuse(
+            &consist,
+            &axles(&[-400, -300, -200, -100, 0, 600, 700, 800, 900]),
+            &FusionRules::default(),
+        );
+
+        let alarms = map_fused_alarms(
+            &consist,
+            &fusion,
+            &[alarm(1, TrackSide::Left), alarm(5, TrackSide::Left)],
+        );
+        assert_eq!(alarms[0].wheel().as_deref(), Some("L4"));
+        assert!(alarms[0].unreliable);
+        assert_eq!(alarms[1].leading_axle, Some(5));
+        assert_eq!(alarms[1].axle, None);
+    }
+
+    #[test]
+    fn beyond_the_consist() {
+        let mut beyond = alarm(9, TrackSide::Left);
+        beyond.kind = AlarmKind::WheelImpact;
+        beyond.reading = Some(350.0);
+        let alarms = map_alarms(&consist(), &[alarm(0, TrackSide::Left), beyond]);
+
+        assert_eq!(alarms[0].vehicle, None);
+        assert_eq!(
+            alarms[1].to_string(),
+            "wheel impact on axle 9 (350) : beyond the vehicles of the consist"
+        );
+    }
+}
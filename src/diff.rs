@@ -0,0 +1,369 @@
+//! Changes of a train between two wayside sites
+//!
+//! The consists read at two sites are aligned on the identity of their vehicles, the equipment
+//! group, initial and car number compared by [`is_same_wagon`](crate::AEITagData::is_same_wagon).
+//! The vehicles of the first consist only were set out, those of the second only were picked up,
+//! and the fewest vehicles to move to restore the first order were moved. The tags of the vehicles
+//! read at both sites are compared as well, a tag read at one site only having failed at the
+//! other.
+//!
+//! ```rust
+//! use aei_tag_parser::{consist::ConsistBuilder, diff::{diff, Change}, read::TagRead, AEITagData};
+//! use chrono::Utc;
+//!
+//! let consist = |tags: &[&str]| {
+//!     let mut builder = ConsistBuilder::new();
+//!     for tag in tags {
+//!         builder.push(TagRead::new(AEITagData::new(tag).unwrap(), Utc::now(), "R1"));
+//!     }
+//!     builder.build()
+//! };
+//! let first = consist(&["2F3E06C007DB1E139000000000000331", "9EA488C030426A179000000000000331"]);
+//! let second = consist(&["2F3E06C007DB1E139000000000000331"]);
+//!
+//! let changes = diff(&first, &second);
+//! assert_eq!(changes.vehicles[1].change, Change::SetOut);
+//! ```
+
+use crate::{
+    align::{align, Op},
+    consist::{Consist, Sides, Vehicle, VehicleId},
+    Side,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+/// Change of a vehicle between the two sites
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+    /// Vehicle in the same order at both sites
+    Kept,
+    /// Vehicle of the first site only
+    SetOut,
+    /// Vehicle of the second site only
+    PickedUp,
+    /// Vehicle of both sites, out of the first order
+    Moved,
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Change::Kept => write!(f, "kept"),
+            Change::SetOut => write!(f, "set out"),
+            Change::PickedUp => write!(f, "picked up"),
+            Change::Moved => write!(f, "moved"),
+        }
+    }
+}
+
+/// Site of the train passage
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Site {
+    First,
+    Second,
+}
+
+/// Tags of a vehicle read at a site
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct ReadQuality {
+    pub sides: Sides,
+    pub read_count: u32,
+}
+
+impl From<&Vehicle> for ReadQuality {
+    fn from(vehicle: &Vehicle) -> Self {
+        ReadQuality {
+            sides: vehicle.sides(),
+            read_count: vehicle.read_count,
+        }
+    }
+}
+
+/// Tag read at one of the sites only
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct TagFailure {
+    pub side: Side,
+    /// Site which didn't read the tag
+    pub site: Site,
+}
+
+/// Vehicle of either consist
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct VehicleDiff {
+    pub vehicle: VehicleId,
+    pub change: Change,
+    /// Position in the first consist, from 1
+    pub first: Option<usize>,
+    /// Position in the second consist, from 1
+    pub second: Option<usize>,
+    pub first_reads: Option<ReadQuality>,
+    pub second_reads: Option<ReadQuality>,
+    /// Tags read at one site only, empty unless the vehicle passed both sites
+    pub failures: Vec<TagFailure>,
+}
+
+impl VehicleDiff {
+    fn new(change: Change, first: Option<&Vehicle>, second: Option<&Vehicle>) -> VehicleDiff {
+        let read = |sides: Sides, side: Side| {
+            matches!(
+                (sides, side),
+                (Sides::Both, _) | (Sides::Left, Side::LEFT) | (Sides::Right, Side::RIGHT)
+            )
+        };
+        let mut failures = Vec::new();
+        if let (Some(first), Some(second)) = (first, second) {
+            for side in [Side::LEFT, Side::RIGHT] {
+                match (read(first.sides(), side), read(second.sides(), side)) {
+                    (true, false) => failures.push(TagFailure {
+                        side,
+                        site: Site::Second,
+                    }),
+                    (false, true) => failures.push(TagFailure {
+                        side,
+                        site: Site::First,
+                    }),
+                    _ => (),
+                }
+            }
+        }
+
+        VehicleDiff {
+            vehicle: first
+                .or(second)
+                .expect("a vehicle of either consist")
+                .id
+                .clone(),
+            change,
+            first: first.map(|vehicle| vehicle.position),
+            second: second.map(|vehicle| vehicle.position),
+            first_reads: first.map(ReadQuality::from),
+            second_reads: second.map(ReadQuality::from),
+            failures,
+        }
+    }
+}
+
+impl Display for VehicleDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let position = |position: Option<usize>| match position {
+            Some(position) => format!("#{}", position),
+            None => String::from("-"),
+        };
+        write!(
+            f,
+            "{} {} ({} -> {})",
+            self.vehicle,
+            self.change,
+            position(self.first),
+            position(self.second)
+        )?;
+        for failure in &self.failures {
+            let site = match failure.site {
+                Site::First => "first",
+                Site::Second => "second",
+            };
+            write!(f, ", {} tag not read at the {} site", failure.side, site)?;
+        }
+        Ok(())
+    }
+}
+
+/// Vehicles of the two consists, in the order of the second one
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct ConsistDiff {
+    pub vehicles: Vec<VehicleDiff>,
+}
+
+impl ConsistDiff {
+    /// Vehicles which underwent the change
+    pub fn changed(&self, change: Change) -> impl Iterator<Item = &VehicleDiff> {
+        self.vehicles
+            .iter()
+            .filter(move |vehicle| vehicle.change == change)
+    }
+
+    /// Check if the same vehicles passed both sites in the same order, every tag being read twice
+    pub fn is_unchanged(&self) -> bool {
+        self.vehicles
+            .iter()
+            .all(|vehicle| vehicle.change == Change::Kept && vehicle.failures.is_empty())
+    }
+}
+
+impl Display for ConsistDiff {
+    /// Formats a line per vehicle changed or whose tags were read at one site only, followed by
+    /// the totals
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for vehicle in &self.vehicles {
+            if vehicle.change != Change::Kept || !vehicle.failures.is_empty() {
+                writeln!(f, "{}", vehicle)?;
+            }
+        }
+        write!(
+            f,
+            "{} kept, {} set out, {} picked up, {} moved, {} tag failure(s)",
+            self.changed(Change::Kept).count(),
+            self.changed(Change::SetOut).count(),
+            self.changed(Change::PickedUp).count(),
+            self.changed(Change::Moved).count(),
+            self.vehicles
+                .iter()
+                .map(|vehicle| vehicle.failures.len())
+                .sum::<usize>()
+        )
+    }
+}
+
+/// Changes of the train between the consist read at a first site and the one read at a second site
+pub fn diff(first: &Consist, second: &Consist) -> ConsistDiff {
+    let ids = |consist: &Consist| -> Vec<VehicleId> {
+        consist
+            .vehicles
+            .iter()
+            .map(|vehicle| vehicle.id.clone())
+            .collect()
+    };
+
+    let vehicles = align(&ids(first), &ids(second))
+        .into_iter()
+        .map(|op| match op {
+            Op::Keep { from, to } => VehicleDiff::new(
+                Change::Kept,
+                Some(&first.vehicles[from]),
+                Some(&second.vehicles[to]),
+            ),
+            Op::Move { from, to } => VehicleDiff::new(
+                Change::Moved,
+                Some(&first.vehicles[from]),
+                Some(&second.vehicles[to]),
+            ),
+            Op::Delete { from } => {
+                VehicleDiff::new(Change::SetOut, Some(&first.vehicles[from]), None)
+            }
+            Op::Insert { to } => {
+                VehicleDiff::new(Change::PickedUp, None, Some(&second.vehicles[to]))
+            }
+        })
+        .collect();
+    ConsistDiff { vehicles }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{consist::ConsistBuilder, encode::TagBuilder, read::TagRead, AEITagData};
+    use chrono::{Duration, TimeZone, Utc};
+
+    /// QNSL 502 right
+    static TAG1: &str = "2F3E06C007DB1E139000000000000331";
+    /// QNSL 502 left
+    static TAG1_LEFT: &str = "2F3E06C007D91E1B9000000000000331";
+    /// IOCC 3088 right
+    static TAG2: &str = "9EA488C030426A179000000000000331";
+    /// IOCC 3088 left
+    static TAG2_LEFT: &str = "9EA488C030406A1F9000000000000331";
+    /// IOCC 85123 left
+    static TAG3: &str = "9EA488C5320CC01B9000000000000331";
+
+    fn consist(tags: &[&str]) -> Consist {
+        consist_of(tags.iter().map(|tag| AEITagData::new(tag).unwrap()))
+    }
+
+    fn consist_of<I: IntoIterator<Item = AEITagData>>(tags: I) -> Consist {
+        let start = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        let mut builder = ConsistBuilder::new();
+        for (i, tag) in tags.into_iter().enumerate() {
+            builder.push(TagRead::new(tag, start + Duration::seconds(i as i64), "R1"));
+        }
+        builder.build()
+    }
+
+    #[test]
+    fn unchanged() {
+        let first = consist(&[TAG1, TAG1_LEFT, TAG2, TAG2_LEFT]);
+        let result = diff(&first, &first);
+
+        assert!(result.is_unchanged());
+        assert_eq!(result.changed(Change::Kept).count(), 2);
+        assert_eq!(
+            result.to_string(),
+            "2 kept, 0 set out, 0 picked up, 0 moved, 0 tag failure(s)"
+        );
+    }
+
+    #[test]
+    fn changes() {
+        let car = |number| TagBuilder::new("IOCC", number).build().unwrap();
+        let tag = |tag| AEITagData::new(tag).unwrap();
+        let first = consist_of([tag(TAG1), tag(TAG2), tag(TAG3), car(1)]);
+        let second = consist_of([car(1), tag(TAG1), tag(TAG1), tag(TAG3), car(2)]);
+        let result = diff(&first, &second);
+
+        assert_eq!(
+            result
+                .vehicles
+                .iter()
+                .map(|vehicle| (vehicle.vehicle.number, vehicle.change))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, Change::Moved),
+                (502, Change::Kept),
+                (3088, Change::SetOut),
+                (85123, Change::Kept),
+                (2, Change::PickedUp)
+            ]
+        );
+        assert_eq!(
+            (result.vehicles[0].first, result.vehicles[0].second),
+            (Some(4), Some(1))
+        );
+        assert_eq!(
+            result.vehicles[1].second_reads,
+            Some(ReadQuality {
+                sides: Sides::Right,
+                read_count: 2
+            })
+        );
+        assert_eq!(result.vehicles[2].second_reads, None);
+        assert!(!result.is_unchanged());
+        assert_eq!(
+            result.to_string(),
+            "IOCC 1 moved (#4 -> #1)\nIOCC 3088 set out (#2 -> -)\nIOCC 2 picked up (- -> #4)\n2 kept, 1 set out, 1 picked up, 1 moved, 0 tag failure(s)"
+        );
+    }
+
+    #[test]
+    fn tag_failures() {
+        let first = consist(&[TAG1, TAG1_LEFT, TAG2]);
+        let second = consist(&[TAG1, TAG2_LEFT]);
+        let result = diff(&first, &second);
+
+        assert_eq!(
+            result.vehicles[0].failures,
+            vec![TagFailure {
+                side: Side::LEFT,
+                site: Site::Second
+            }]
+        );
+        assert_eq!(
+            result.vehicles[1].failures,
+            vec![
+                TagFailure {
+                    side: Side::LEFT,
+                    site: Site::First
+                },
+                TagFailure {
+                    side: Side::RIGHT,
+                    site: Site::Second
+                }
+            ]
+        );
+        assert_eq!(
+            result.to_string(),
+            "QNSL 502 kept (#1 -> #1), Left tag not read at the second site\nIOCC 3088 kept (#2 -> #2), Left tag not read at the first site, Right tag not read at the second site\n2 kept, 0 set out, 0 picked up, 0 moved, 3 tag failure(s)"
+        );
+    }
+}
@@ -0,0 +1,457 @@
+//! X12 EDI 418 Rail Advance Interchange Consist
+//!
+//! [`interchange`] builds an interchange holding one 418 transaction set per consist, sent to the
+//! railroad receiving the trains. The interchange is enveloped by the ISA/IEA and GS/GE segments,
+//! version 004010. Each vehicle of a consist is written, in the order of the train, as :
+//! - an `LX` segment holding its position
+//! - a `W2` equipment identification segment holding its mark, number, equipment description
+//!   code (`LO` for locomotives and `RR` for the other vehicles) and load status, `L` loaded or
+//!   `E` empty, left out when unknown
+//!
+//! Every element is checked against the type and length of its definition before being written,
+//! as are the identifiers of the envelope. The elements are separated by `*`, the components by
+//! `>` and the segments are ended by `~` and a line feed.
+//!
+//! ```rust
+//! use aei_tag_parser::{consist::ConsistBuilder, edi418::{self, Envelope}, read::TagRead, AEITagData};
+//! use chrono::{TimeZone, Utc};
+//!
+//! let now = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+//! let mut builder = ConsistBuilder::new();
+//! builder.push(TagRead::new(AEITagData::new("9EA488C030426A179000000000000331").unwrap(), now, "R1"));
+//! let envelope = Envelope::new("IOCC", "QNSL", 1, now);
+//!
+//! let edi = edi418::interchange(&[builder.build()], &envelope, None).unwrap();
+//! assert!(edi.contains("ST*418*0001~\nLX*1~\nW2*IOCC*3088**RR~\nSE*4*0001~\n"));
+//! ```
+
+use crate::{consist::Consist, orientation::LOCOMOTIVE_GROUP};
+use chrono::{DateTime, Utc};
+use std::{error, fmt::Display};
+
+/// Separator of the elements
+pub const ELEMENT_SEPARATOR: char = '*';
+/// Separator of the components of a composite element
+pub const COMPONENT_SEPARATOR: char = '>';
+/// End of a segment
+pub const SEGMENT_TERMINATOR: char = '~';
+/// Identifier of the functional group of the 418 transaction sets
+pub const FUNCTIONAL_ID: &str = "AI";
+/// Version of the X12 standard
+pub const VERSION: &str = "004010";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EdiError {
+    /// An element doesn't match its definition : segment, position of the element, message
+    Element(&'static str, usize, String),
+    /// A consist has no vehicle
+    EmptyConsist(usize),
+}
+
+impl Display for EdiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EdiError::Element(segment, position, message) => {
+                write!(
+                    f,
+                    "invalid element {}{:02} : {}",
+                    segment, position, message
+                )
+            }
+            EdiError::EmptyConsist(index) => {
+                write!(f, "the consist {} has no vehicle", index + 1)
+            }
+        }
+    }
+}
+
+impl error::Error for EdiError {}
+
+/// Load status of the vehicles
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum LoadStatus {
+    Loaded,
+    Empty,
+}
+
+impl LoadStatus {
+    fn code(self) -> &'static str {
+        match self {
+            LoadStatus::Loaded => "L",
+            LoadStatus::Empty => "E",
+        }
+    }
+}
+
+/// Type of an element
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Kind {
+    /// Alphanumeric string
+    AN,
+    /// Identifier, a code made of letters and digits
+    ID,
+    /// Numeric without decimals
+    N0,
+}
+
+/// Segment being built, whose elements are checked as they are added
+struct Segment {
+    id: &'static str,
+    elements: Vec<String>,
+}
+
+impl Segment {
+    fn new(id: &'static str) -> Segment {
+        Segment {
+            id,
+            elements: Vec::new(),
+        }
+    }
+
+    /// Adds an element of `min` to `max` characters. An empty value is an element left out
+    fn element(
+        mut self,
+        value: &str,
+        kind: Kind,
+        min: usize,
+        max: usize,
+    ) -> Result<Segment, EdiError> {
+        let position = self.elements.len() + 1;
+        let error = |message: String| EdiError::Element(self.id, position, message);
+
+        if !value.is_empty() {
+            let length = value.chars().count();
+            if length < min || length > max {
+                return Err(error(format!(
+                    "{} is {} characters long, expected {} to {}",
+                    value, length, min, max
+                )));
+            }
+            let valid = match kind {
+                Kind::AN => value.chars().all(|c| {
+                    c.is_ascii()
+                        && !c.is_ascii_control()
+                        && ![ELEMENT_SEPARATOR, COMPONENT_SEPARATOR, SEGMENT_TERMINATOR]
+                            .contains(&c)
+                }),
+                Kind::ID => value.chars().all(|c| c.is_ascii_alphanumeric()),
+                Kind::N0 => value.chars().all(|c| c.is_ascii_digit()),
+            };
+            if !valid {
+                return Err(error(format!("{} isn't a valid {:?} element", value, kind)));
+            }
+        }
+        self.elements.push(String::from(value));
+        Ok(self)
+    }
+
+    /// Adds a required element
+    fn required(
+        self,
+        value: &str,
+        kind: Kind,
+        min: usize,
+        max: usize,
+    ) -> Result<Segment, EdiError> {
+        if value.is_empty() {
+            let position = self.elements.len() + 1;
+            return Err(EdiError::Element(
+                self.id,
+                position,
+                String::from("missing required element"),
+            ));
+        }
+        self.element(value, kind, min, max)
+    }
+
+    /// Segment ended by its terminator, the trailing elements left out being dropped
+    fn build(self) -> String {
+        let used = self
+            .elements
+            .iter()
+            .rposition(|e| !e.is_empty())
+            .map_or(0, |i| i + 1);
+        let mut out = String::from(self.id);
+        for element in &self.elements[..used] {
+            out.push(ELEMENT_SEPARATOR);
+            out.push_str(element);
+        }
+        out.push(SEGMENT_TERMINATOR);
+        out.push('\n');
+        out
+    }
+}
+
+/// Identifiers of the interchange
+#[derive(Clone, PartialEq, Debug)]
+pub struct Envelope {
+    /// Qualifier of the sender ID, `ZZ` mutually defined by default
+    pub sender_qualifier: String,
+    pub sender_id: String,
+    /// Qualifier of the receiver ID, `ZZ` mutually defined by default
+    pub receiver_qualifier: String,
+    pub receiver_id: String,
+    /// Control number of the interchange and of its functional group
+    pub control_number: u32,
+    /// The interchange is a test, not to be processed
+    pub test: bool,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl Envelope {
+    pub fn new(
+        sender_id: &str,
+        receiver_id: &str,
+        control_number: u32,
+        timestamp: DateTime<Utc>,
+    ) -> Envelope {
+        Envelope {
+            sender_qualifier: String::from("ZZ"),
+            sender_id: String::from(sender_id),
+            receiver_qualifier: String::from("ZZ"),
+            receiver_id: String::from(receiver_id),
+            control_number,
+            test: false,
+            timestamp,
+        }
+    }
+
+    /// ISA segment, whose elements have a fixed width
+    fn isa(&self) -> Result<String, EdiError> {
+        let control = self.control_number.to_string();
+        let segment = Segment::new("ISA")
+            .required("00", Kind::ID, 2, 2)?
+            .required(&" ".repeat(10), Kind::AN, 10, 10)?
+            .required("00", Kind::ID, 2, 2)?
+            .required(&" ".repeat(10), Kind::AN, 10, 10)?
+            .required(&self.sender_qualifier, Kind::ID, 2, 2)?
+            .required(&format!("{:<15}", self.sender_id), Kind::AN, 15, 15)?
+            .required(&self.receiver_qualifier, Kind::ID, 2, 2)?
+            .required(&format!("{:<15}", self.receiver_id), Kind::AN, 15, 15)?
+            .required(&self.timestamp.format("%y%m%d").to_string(), Kind::N0, 6, 6)?
+            .required(&self.timestamp.format("%H%M").to_string(), Kind::N0, 4, 4)?
+            .required("U", Kind::ID, 1, 1)?
+            .required("00401", Kind::ID, 5, 5)?
+            .required(&format!("{:09}", self.control_number), Kind::N0, 9, 9)
+            .map_err(|_| {
+                EdiError::Element(
+                    "ISA",
+                    13,
+                    format!("control number {} is too large", control),
+                )
+            })?
+            .required("0", Kind::ID, 1, 1)?
+            .required(if self.test { "T" } else { "P" }, Kind::ID, 1, 1)?;
+        // The component separator isn't a regular element
+        let mut isa = segment.build();
+        isa.insert_str(
+            isa.len() - 2,
+            &format!("{}{}", ELEMENT_SEPARATOR, COMPONENT_SEPARATOR),
+        );
+        Ok(isa)
+    }
+}
+
+/// 418 transaction set of a consist, from its ST header to its SE trailer
+fn transaction_set(
+    consist: &Consist,
+    control_number: usize,
+    load_status: Option<LoadStatus>,
+) -> Result<String, EdiError> {
+    let control = format!("{:04}", control_number);
+    let mut segments = vec![Segment::new("ST")
+        .required("418", Kind::ID, 3, 3)?
+        .required(&control, Kind::AN, 4, 9)?
+        .build()];
+
+    for vehicle in &consist.vehicles {
+        let description = if vehicle.id.group == LOCOMOTIVE_GROUP {
+            "LO"
+        } else {
+            "RR"
+        };
+        segments.push(
+            Segment::new("LX")
+                .required(&vehicle.position.to_string(), Kind::N0, 1, 6)?
+                .build(),
+        );
+        segments.push(
+            Segment::new("W2")
+                .required(&vehicle.id.mark, Kind::ID, 2, 4)?
+                .required(&vehicle.id.number.to_string(), Kind::AN, 1, 10)?
+                .element("", Kind::N0, 1, 7)?
+                .required(description, Kind::ID, 2, 2)?
+                .element(load_status.map_or("", LoadStatus::code), Kind::ID, 1, 2)?
+                .build(),
+        );
+    }
+
+    let count = (segments.len() + 1).to_string();
+    segments.push(
+        Segment::new("SE")
+            .required(&count, Kind::N0, 1, 10)?
+            .required(&control, Kind::AN, 4, 9)?
+            .build(),
+    );
+    Ok(segments.concat())
+}
+
+/// Interchange holding the 418 transaction set of each consist, in a single functional group
+pub fn interchange(
+    consists: &[Consist],
+    envelope: &Envelope,
+    load_status: Option<LoadStatus>,
+) -> Result<String, EdiError> {
+    let control = envelope.control_number.to_string();
+    let mut out = envelope.isa()?;
+    out.push_str(
+        &Segment::new("GS")
+            .required(FUNCTIONAL_ID, Kind::ID, 2, 2)?
+            .required(&envelope.sender_id, Kind::AN, 2, 15)?
+            .required(&envelope.receiver_id, Kind::AN, 2, 15)?
+            .required(
+                &envelope.timestamp.format("%Y%m%d").to_string(),
+                Kind::N0,
+                8,
+                8,
+            )?
+            .required(
+                &envelope.timestamp.format("%H%M").to_string(),
+                Kind::N0,
+                4,
+                8,
+            )?
+            .required(&control, Kind::N0, 1, 9)?
+            .required("X", Kind::ID, 1, 2)?
+            .required(VERSION, Kind::AN, 1, 12)?
+            .build(),
+    );
+
+    for (i, consist) in consists.iter().enumerate() {
+        if consist.vehicles.is_empty() {
+            return Err(EdiError::EmptyConsist(i));
+        }
+        out.push_str(&transaction_set(consist, i + 1, load_status)?);
+    }
+
+    let sets = consists.len().to_string();
+    out.push_str(
+        &Segment::new("GE")
+            .required(&sets, Kind::N0, 1, 6)?
+            .required(&control, Kind::N0, 1, 9)?
+            .build(),
+    );
+    out.push_str(
+        &Segment::new("IEA")
+            .required("1", Kind::N0, 1, 5)?
+            .required(&format!("{:09}", envelope.control_number), Kind::N0, 9, 9)?
+            .build(),
+    );
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{consist::ConsistBuilder, encode::TagBuilder, read::TagRead, AEITagData};
+    use chrono::{Duration, TimeZone};
+
+    fn timestamp() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, 1, 12, 30, 0).unwrap()
+    }
+
+    fn consist(tags: &[&str]) -> Consist {
+        let mut builder = ConsistBuilder::new();
+        for (i, tag) in tags.iter().enumerate() {
+            builder.push(TagRead::new(
+                AEITagData::new(tag).unwrap(),
+                timestamp() + Duration::seconds(i as i64),
+                "R1",
+            ));
+        }
+        builder.build()
+    }
+
+    #[test]
+    fn interchange_envelope() {
+        let consists = [
+            consist(&[
+                "2F3E06C007DB1E139000000000000331",
+                "9EA488C030426A179000000000000331",
+            ]),
+            consist(&["9EA488C5320CC01B9000000000000331"]),
+        ];
+        let mut envelope = Envelope::new("QNSL", "CN", 42, timestamp());
+        envelope.test = true;
+        let edi = interchange(&consists, &envelope, Some(LoadStatus::Empty)).unwrap();
+
+        assert_eq!(
+            edi,
+            "ISA*00*          *00*          *ZZ*QNSL           *ZZ*CN             *240501*1230*U*00401*000000042*0*T*>~\n\
+             GS*AI*QNSL*CN*20240501*1230*42*X*004010~\n\
+             ST*418*0001~\n\
+             LX*1~\n\
+             W2*QNSL*502**LO*E~\n\
+             LX*2~\n\
+             W2*IOCC*3088**RR*E~\n\
+             SE*6*0001~\n\
+             ST*418*0002~\n\
+             LX*1~\n\
+             W2*IOCC*85123**RR*E~\n\
+             SE*4*0002~\n\
+             GE*2*42~\n\
+             IEA*1*000000042~\n"
+        );
+        // The ISA segment has a fixed length
+        assert_eq!(edi.lines().next().unwrap().len(), 106);
+    }
+
+    #[test]
+    fn element_validation() {
+        let consists = [consist(&["9EA488C030426A179000000000000331"])];
+
+        let envelope = Envelope::new("A SENDER TOO LONG", "CN", 1, timestamp());
+        assert_eq!(
+            interchange(&consists, &envelope, None),
+            Err(EdiError::Element(
+                "ISA",
+                6,
+                String::from("A SENDER TOO LONG is 17 characters long, expected 15 to 15")
+            ))
+        );
+
+        let envelope = Envelope::new("QNSL*", "CN", 1, timestamp());
+        assert!(matches!(
+            interchange(&consists, &envelope, None),
+            Err(EdiError::Element("ISA", 6, _))
+        ));
+
+        let envelope = Envelope::new("QNSL", "CN", 1_000_000_000, timestamp());
+        assert_eq!(
+            interchange(&consists, &envelope, None)
+                .unwrap_err()
+                .to_string(),
+            "invalid element ISA13 : control number 1000000000 is too large"
+        );
+
+        let envelope = Envelope::new("QNSL", "CN", 1, timestamp());
+        assert_eq!(
+            interchange(&[Consist::default()], &envelope, None),
+            Err(EdiError::EmptyConsist(0))
+        );
+    }
+
+    #[test]
+    fn short_marks() {
+        let mut builder = ConsistBuilder::new();
+        builder.push(TagRead::new(
+            TagBuilder::new("UP", 1234).build().unwrap(),
+            timestamp(),
+            "R1",
+        ));
+        let envelope = Envelope::new("QNSL", "UP", 1, timestamp());
+        let edi = interchange(&[builder.build()], &envelope, None).unwrap();
+
+        assert!(edi.contains("\nW2*UP*1234**RR~\n"));
+    }
+}
@@ -0,0 +1,295 @@
+//! Encoding of new tags
+//!
+//! A [`TagBuilder`] produces the raw value of a railcar tag, with its checksums, frame markers
+//! and data format code. [`plan_roster`] builds the left and right tags of every car of a roster,
+//! each tag being verified by decoding it back.
+//!
+//! ```rust
+//! use aei_tag_parser::{encode::TagBuilder, Side};
+//!
+//! let tag = TagBuilder::new("IOCC", 3088)
+//!     .side(Side::RIGHT)
+//!     .length_dm(106)
+//!     .bearing(1)
+//!     .build()
+//!     .unwrap();
+//! assert_eq!(hex::encode_upper(tag.raw()), "9EA488C030426A179000000000000331");
+//! ```
+
+use crate::{
+    roster::{Roster, RosterCar},
+    AEITagData, EncodeTagError, Side,
+};
+use serde::{Deserialize, Serialize};
+use std::{error, fmt::Display};
+
+/// Data format code found on railcar tags (bits 118-123)
+pub const RAILCAR_DATA_FORMAT: u8 = 0b11_0011;
+/// Equipment group code of railcars
+pub const RAILCAR_GROUP: u8 = 19;
+
+/// Builder of the raw value of a tag
+#[derive(Clone, Debug)]
+pub struct TagBuilder {
+    equipment_initial: String,
+    car_number: u32,
+    group: u8,
+    tag_type: u8,
+    side: Side,
+    length_dm: u16,
+    axles: u8,
+    bearing: u8,
+}
+
+impl TagBuilder {
+    /// Railcar tag for the given car, on the left side, 4 axles, length of 0 and plain bearings
+    pub fn new(equipment_initial: &str, car_number: u32) -> TagBuilder {
+        TagBuilder {
+            equipment_initial: String::from(equipment_initial),
+            car_number,
+            group: RAILCAR_GROUP,
+            tag_type: 3,
+            side: Side::LEFT,
+            length_dm: 0,
+            axles: 4,
+            bearing: 0,
+        }
+    }
+
+    pub fn group(mut self, group: u8) -> TagBuilder {
+        self.group = group;
+        self
+    }
+
+    pub fn side(mut self, side: Side) -> TagBuilder {
+        self.side = side;
+        self
+    }
+
+    pub fn length_dm(mut self, length_dm: u16) -> TagBuilder {
+        self.length_dm = length_dm;
+        self
+    }
+
+    pub fn axles(mut self, axles: u8) -> TagBuilder {
+        self.axles = axles;
+        self
+    }
+
+    pub fn bearing(mut self, bearing: u8) -> TagBuilder {
+        self.bearing = bearing;
+        self
+    }
+
+    /// Encodes the tag
+    pub fn build(&self) -> Result<AEITagData, EncodeTagError> {
+        if self.group > 31 {
+            return Err(EncodeTagError::GroupOutOfRange(self.group));
+        }
+        if !(1..=32).contains(&self.axles) {
+            return Err(EncodeTagError::AxlesOutOfRange(self.axles));
+        }
+        if self.bearing > 7 {
+            return Err(EncodeTagError::BearingOutOfRange(self.bearing));
+        }
+
+        let mut tag = AEITagData::from_raw([0u8; 16]);
+        tag.set_bits_multi(&[0..=4], u128::from(self.group));
+        tag.set_bits_multi(&[5..=6], u128::from(self.tag_type));
+        tag.set_bits_multi(&[56..=59, 64..=64], u128::from(self.axles - 1));
+        tag.set_bits_multi(&[62..=63], 0b11);
+        tag.set_bits_multi(&[65..=67], u128::from(self.bearing));
+        tag.set_bits_multi(&[118..=123], u128::from(RAILCAR_DATA_FORMAT));
+        tag.set_bits_multi(&[126..=127], 0b01);
+        tag.with_equipment_initial(&self.equipment_initial)?
+            .with_car_number(self.car_number)?
+            .with_length_dm(self.length_dm)?
+            .with_side_indicator(self.side);
+        Ok(tag)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PlanError {
+    /// A value required to program the tags is missing from the roster
+    MissingField(String, &'static str),
+    /// A value of the roster can't be encoded
+    Encode(String, EncodeTagError),
+    /// The tag decoded back doesn't match the roster
+    Verification(String, Side),
+}
+
+impl Display for PlanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlanError::MissingField(car, field) => {
+                write!(f, "{} : the {} is missing from the roster", car, field)
+            }
+            PlanError::Encode(car, e) => write!(f, "{} : {}", car, e),
+            PlanError::Verification(car, side) => write!(
+                f,
+                "{} : the {} tag doesn't decode back to the roster values",
+                car, side
+            ),
+        }
+    }
+}
+
+impl error::Error for PlanError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            PlanError::Encode(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Tags to program on a car
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlannedCar {
+    pub mark: String,
+    pub number: u32,
+    /// Raw value of the left side tag in hexadecimal
+    pub left: String,
+    /// Raw value of the right side tag in hexadecimal
+    pub right: String,
+}
+
+impl PlannedCar {
+    /// Raw values of the left then the right tag
+    pub fn raw(&self) -> [[u8; 16]; 2] {
+        let mut raw = [[0u8; 16]; 2];
+        hex::decode_to_slice(&self.left, &mut raw[0]).expect("planned tags are valid hexadecimal");
+        hex::decode_to_slice(&self.right, &mut raw[1]).expect("planned tags are valid hexadecimal");
+        raw
+    }
+}
+
+/// Builds and verifies the tag of one side of a roster car
+fn plan_side(car: &RosterCar, side: Side) -> Result<String, PlanError> {
+    let name = car.to_string();
+    let length_dm = car
+        .length_dm
+        .ok_or_else(|| PlanError::MissingField(name.clone(), "length"))?;
+    let axles = car
+        .axles
+        .ok_or_else(|| PlanError::MissingField(name.clone(), "number of axles"))?;
+    let tag = TagBuilder::new(&car.mark, car.number)
+        .group(car.group.unwrap_or(RAILCAR_GROUP))
+        .side(side)
+        .length_dm(length_dm)
+        .axles(axles)
+        .bearing(car.bearing.unwrap_or(0))
+        .build()
+        .map_err(|e| PlanError::Encode(name.clone(), e))?;
+
+    // Decode the tag from its hexadecimal value as the reader will
+    let encoded = hex::encode_upper(tag.raw());
+    let decoded =
+        AEITagData::new(&encoded).map_err(|_| PlanError::Verification(name.clone(), side))?;
+    let verified = car.matches(&decoded)
+        && decoded.checksums_valid()
+        && decoded.side_indicator() == side
+        && decoded.length_dm() == length_dm
+        && decoded.number_axles() == axles
+        && Some(decoded.equipment_group_code()) == car.group.or(Some(RAILCAR_GROUP))
+        && decoded.bearing_type_code() == car.bearing.unwrap_or(0);
+    if verified {
+        Ok(encoded)
+    } else {
+        Err(PlanError::Verification(name, side))
+    }
+}
+
+/// Builds the left and right tags of every car of the roster. Every car needs a length and a
+/// number of axles, the equipment group defaults to railcar and the bearing type to 0
+pub fn plan_roster(roster: &Roster) -> Vec<Result<PlannedCar, PlanError>> {
+    roster
+        .cars()
+        .iter()
+        .map(|car| {
+            Ok(PlannedCar {
+                mark: car.mark.clone(),
+                number: car.number,
+                left: plan_side(car, Side::LEFT)?,
+                right: plan_side(car, Side::RIGHT)?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_tags() {
+        let tag = TagBuilder::new("QNSL", 502)
+            .group(5)
+            .side(Side::RIGHT)
+            .length_dm(286)
+            .bearing(1)
+            .build()
+            .unwrap();
+        assert_eq!(
+            hex::encode_upper(tag.raw()),
+            "2F3E06C007DB1E139000000000000331"
+        );
+
+        let tag = TagBuilder::new("IOCC", 85123)
+            .length_dm(192)
+            .bearing(1)
+            .build()
+            .unwrap();
+        assert_eq!(
+            hex::encode_upper(tag.raw()),
+            "9EA488C5320CC01B9000000000000331"
+        );
+    }
+
+    #[test]
+    fn invalid_values() {
+        assert_eq!(
+            TagBuilder::new("IOCC", 1).axles(0).build().unwrap_err(),
+            EncodeTagError::AxlesOutOfRange(0)
+        );
+        assert_eq!(
+            TagBuilder::new("IOCC", 1).group(32).build().unwrap_err(),
+            EncodeTagError::GroupOutOfRange(32)
+        );
+        assert_eq!(
+            TagBuilder::new("IOCC", 1).bearing(8).build().unwrap_err(),
+            EncodeTagError::BearingOutOfRange(8)
+        );
+        assert!(TagBuilder::new("IO1C", 1).build().is_err());
+    }
+
+    #[test]
+    fn roster_plan() {
+        let roster = Roster::from_csv(
+            "mark;number;length_dm;axles;group;bearing\nIOCC;3088;106;4;19;1\nGATX;12;150;6;;\nUP;1;;4;;\nUP;2;100;40;;\n",
+        )
+        .unwrap();
+        let plan = plan_roster(&roster);
+
+        let car = plan[0].as_ref().unwrap();
+        assert_eq!(car.right, "9EA488C030426A179000000000000331");
+        assert_eq!(car.left, "9EA488C030406A1F9000000000000331");
+        assert_eq!(car.raw()[1][0], 0x9E);
+
+        let car = plan[1].as_ref().unwrap();
+        let left = AEITagData::new(&car.left).unwrap();
+        assert_eq!(left.equipment_initial(), "GATX");
+        assert_eq!(left.number_axles(), 6);
+        assert!(left.is_same_wagon(AEITagData::new(&car.right).unwrap()));
+
+        assert_eq!(
+            plan[2].as_ref().unwrap_err().to_string(),
+            "UP 1 : the length is missing from the roster"
+        );
+        assert_eq!(
+            plan[3].as_ref().unwrap_err(),
+            &PlanError::Encode(String::from("UP 2"), EncodeTagError::AxlesOutOfRange(40))
+        );
+    }
+}
@@ -0,0 +1,277 @@
+//! Framing of the byte streams of the serial readers
+//!
+//! The legacy readers send each tag in a frame ended by a terminator, line feed or carriage
+//! return, and optionally started by a start byte such as STX. A [`FrameReader`] extracts the
+//! frames of a byte stream and gives them back one per line, so that they can be decoded with a
+//! [`TagStream`](crate::stream::TagStream). The bytes received outside of a frame are dropped, as
+//! is a frame interrupted by a new start byte or longer than the maximum length, the decoding
+//! resuming with the next frame.
+//!
+//! ```rust
+//! use aei_tag_parser::{framing::{FrameReader, Framing}, stream::TagStream};
+//!
+//! let framing = Framing { start: Some(0x02), end: 0x03, ..Framing::default() };
+//! let data: &[u8] = b"\x029EA4\xFF\x029EA488C030426A179000000000000331\x03noise";
+//! let tags: Vec<u32> = TagStream::new(FrameReader::new(data, framing))
+//!     .map(|read| read.unwrap().1.car_number())
+//!     .collect();
+//!
+//! assert_eq!(tags, vec![3088]);
+//! ```
+
+use std::{
+    io::{self, BufRead, Read},
+    str::FromStr,
+};
+
+/// Frames of the stream
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Framing {
+    /// Byte starting a frame. Without it, every byte is part of a frame
+    pub start: Option<u8>,
+    /// Byte ending a frame
+    pub end: u8,
+    /// Longest frame kept, in bytes
+    pub max_length: usize,
+}
+
+impl Default for Framing {
+    /// Lines ended by a line feed
+    fn default() -> Self {
+        Framing {
+            start: None,
+            end: b'\n',
+            max_length: 256,
+        }
+    }
+}
+
+/// Parses a framing byte given as `lf`, `cr`, `stx`, `etx`, a character or an hexadecimal value
+/// such as `0x02`
+pub fn parse_byte(s: &str) -> Result<u8, String> {
+    match s.to_lowercase().as_str() {
+        "lf" => Ok(b'\n'),
+        "cr" => Ok(b'\r'),
+        "stx" => Ok(0x02),
+        "etx" => Ok(0x03),
+        l if l.starts_with("0x") => {
+            u8::from_str_radix(&l[2..], 16).map_err(|e| format!("invalid byte {} : {}", s, e))
+        }
+        _ if s.len() == 1 => Ok(s.as_bytes()[0]),
+        _ => Err(format!(
+            "invalid byte {}, expected lf, cr, stx, etx, a character or 0xHH",
+            s
+        )),
+    }
+}
+
+impl FromStr for Framing {
+    type Err = String;
+
+    /// Parses `END` or `START,END`, each byte being given as [`parse_byte`] expects
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = match s.split_once(',') {
+            Some((start, end)) => (Some(parse_byte(start.trim())?), parse_byte(end.trim())?),
+            None => (None, parse_byte(s.trim())?),
+        };
+        Ok(Framing {
+            start,
+            end,
+            ..Framing::default()
+        })
+    }
+}
+
+/// Reader giving back the frames of the inner reader, each one followed by a line feed
+pub struct FrameReader<R: Read> {
+    inner: R,
+    framing: Framing,
+    /// Content of the frame being received
+    frame: Vec<u8>,
+    in_frame: bool,
+    /// The frame is too long and dropped until its end
+    discarding: bool,
+    /// Frames received, ready to be read
+    output: Vec<u8>,
+    position: usize,
+    dropped: usize,
+    eof: bool,
+}
+
+impl<R: Read> FrameReader<R> {
+    pub fn new(inner: R, framing: Framing) -> FrameReader<R> {
+        FrameReader {
+            inner,
+            framing,
+            frame: Vec::new(),
+            in_frame: framing.start.is_none(),
+            discarding: false,
+            output: Vec::new(),
+            position: 0,
+            dropped: 0,
+            eof: false,
+        }
+    }
+
+    /// Number of partial or too long frames dropped
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Gives back the inner reader
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn push(&mut self, byte: u8) {
+        if Some(byte) == self.framing.start {
+            if !self.frame.is_empty() || self.discarding {
+                // The previous frame was interrupted
+                self.dropped += 1;
+            }
+            self.frame.clear();
+            self.in_frame = true;
+            self.discarding = false;
+        } else if byte == self.framing.end {
+            if self.in_frame && !self.discarding {
+                self.output.append(&mut self.frame);
+                self.output.push(b'\n');
+            }
+            self.frame.clear();
+            self.in_frame = self.framing.start.is_none();
+            self.discarding = false;
+        } else if self.in_frame && !self.discarding {
+            self.frame.push(byte);
+            if self.frame.len() > self.framing.max_length {
+                self.frame.clear();
+                self.discarding = true;
+                self.dropped += 1;
+            }
+        }
+    }
+}
+
+impl<R: Read> Read for FrameReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: Read> BufRead for FrameReader<R> {
+    /// Reads the inner reader until a frame is complete. The read timeouts of the serial ports
+    /// are retried
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.position >= self.output.len() {
+            self.output.clear();
+            self.position = 0;
+        }
+
+        let mut chunk = [0u8; 256];
+        while self.output.is_empty() && !self.eof {
+            match self.inner.read(&mut chunk) {
+                Ok(0) => {
+                    self.eof = true;
+                    // The last line of a stream without start byte may miss its terminator
+                    if self.framing.start.is_none() && !self.frame.is_empty() && !self.discarding {
+                        self.output.append(&mut self.frame);
+                        self.output.push(b'\n');
+                    }
+                }
+                Ok(n) => chunk[..n].iter().for_each(|&b| self.push(b)),
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::Interrupted | io::ErrorKind::TimedOut
+                    ) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(&self.output[self.position..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.position = (self.position + amt).min(self.output.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::TagStream;
+
+    static TAG1: &str = "2F3E06C007DB1E139000000000000331";
+    static TAG2: &str = "9EA488C030426A179000000000000331";
+
+    fn frames(data: &[u8], framing: Framing) -> (Vec<String>, usize) {
+        let mut reader = FrameReader::new(data, framing);
+        let mut out = String::new();
+        reader.read_to_string(&mut out).unwrap();
+        (out.lines().map(String::from).collect(), reader.dropped())
+    }
+
+    #[test]
+    fn line_framing() {
+        let data = format!("{}\r\n{}", TAG1, TAG2);
+        let mut reader = FrameReader::new(data.as_bytes(), Framing::default());
+        let mut out = String::new();
+        reader.read_to_string(&mut out).unwrap();
+
+        // The carriage return is kept, the tag decoding trims it
+        assert_eq!(out, format!("{}\r\n{}\n", TAG1, TAG2));
+        // Without start byte every line is a frame, none being discarded
+        assert_eq!(reader.dropped(), 0);
+    }
+
+    #[test]
+    fn start_and_end_bytes() {
+        let framing: Framing = "stx,etx".parse().unwrap();
+        // Noise, a frame interrupted by a new one, a complete frame and a partial one
+        let data = [
+            &b"\xFF\xFE"[..],
+            b"\x029EA488",
+            b"\x02",
+            TAG1.as_bytes(),
+            b"\x03\r\n\x02",
+            TAG2.as_bytes(),
+            b"\x03\x029EA4",
+        ]
+        .concat();
+        let (lines, dropped) = frames(&data, framing);
+
+        assert_eq!(lines, vec![TAG1, TAG2]);
+        assert_eq!(dropped, 1);
+    }
+
+    #[test]
+    fn long_frames() {
+        let framing = Framing {
+            max_length: 40,
+            ..Framing::default()
+        };
+        let data = format!("{}\n{}\n", "A".repeat(100), TAG2);
+        let reads: Vec<_> = TagStream::new(FrameReader::new(data.as_bytes(), framing)).collect();
+
+        assert_eq!(reads.len(), 1);
+        assert_eq!(reads[0].as_ref().unwrap().1.car_number(), 3088);
+    }
+
+    #[test]
+    fn framing_parsing() {
+        assert_eq!(
+            "cr".parse::<Framing>(),
+            Ok(Framing {
+                end: b'\r',
+                ..Framing::default()
+            })
+        );
+        let framing: Framing = "0x02, 0x0D".parse().unwrap();
+        assert_eq!((framing.start, framing.end), (Some(2), 13));
+        assert_eq!(parse_byte("$"), Ok(b'$'));
+        assert!("0xZZ".parse::<Framing>().is_err());
+        assert!("crlf".parse::<Framing>().is_err());
+    }
+}
@@ -0,0 +1,322 @@
+//! Fusion of the axle counter with the tags read
+//!
+//! When both tags of a vehicle are missing, the consist simply loses the vehicle, but the wheel
+//! sensors of the site still count its axles. [`fuse`] splits the axles counted during a train
+//! passage between the tagged vehicles of its consist : the axles of a vehicle are counted from a
+//! short lead before its first tag read, and it takes as many of them as its tag gives. The axles
+//! left over before the next tagged vehicle make an untagged vehicle, which may actually be
+//! several vehicles coupled together, while too few axles or too few left over to be a vehicle
+//! are flagged as an axle count mismatch. Only the vehicles have axles : the end-of-train device
+//! and the other tags read on the train, such as those of the containers, are left out.
+//!
+//! ```rust
+//! use aei_tag_parser::{consist::ConsistBuilder, fusion::{fuse, FusionRules}, read::TagRead, AEITagData};
+//! use chrono::{Duration, TimeZone, Utc};
+//!
+//! let start = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+//! let mut builder = ConsistBuilder::new();
+//! builder.push(TagRead::new(AEITagData::new("9EA488C030426A179000000000000331").unwrap(), start, "R1"));
+//! // 4 axles for the tagged car, then 4 axles of a car without tags
+//! let axles: Vec<_> = [1, 2, 5, 6, 8, 9, 12, 13]
+//!     .iter()
+//!     .map(|s| start + Duration::seconds(*s))
+//!     .collect();
+//!
+//! let fusion = fuse(&builder.build(), &axles, &FusionRules::default());
+//! assert_eq!(fusion.vehicles.len(), 2);
+//! assert!(fusion.vehicles[1].is_untagged());
+//! ```
+
+use crate::{
+    consist::{Consist, Vehicle, VehicleId},
+    summary::is_vehicle_group,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+/// Rules splitting the axles between the vehicles
+#[derive(Clone, PartialEq, Debug)]
+pub struct FusionRules {
+    /// Time the first axle of a vehicle may pass the wheel sensor before its first tag read
+    pub lead: Duration,
+    /// Fewest axles left over after a vehicle making an untagged vehicle, fewer axles being
+    /// counted with the vehicle
+    pub min_untagged_axles: usize,
+}
+
+impl Default for FusionRules {
+    fn default() -> Self {
+        FusionRules {
+            lead: Duration::milliseconds(500),
+            min_untagged_axles: 2,
+        }
+    }
+}
+
+/// Vehicle of the train, tagged or not
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct FusedVehicle {
+    /// Position in the train, from 1, the untagged vehicles included
+    pub position: usize,
+    /// Identity of a tagged vehicle
+    pub vehicle: Option<VehicleId>,
+    /// Number of axles given by the tag
+    pub tag_axles: Option<u8>,
+    /// Timestamps of the axles counted
+    pub axles: Vec<DateTime<Utc>>,
+}
+
+impl FusedVehicle {
+    /// Vehicle of which no tag was read
+    pub fn is_untagged(&self) -> bool {
+        self.vehicle.is_none()
+    }
+
+    /// The number of axles counted differs from the tag
+    pub fn is_axle_mismatch(&self) -> bool {
+        self.tag_axles
+            .is_some_and(|axles| usize::from(axles) != self.axles.len())
+    }
+}
+
+impl Display for FusedVehicle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.vehicle {
+            Some(vehicle) => write!(f, "#{} {} : ", self.position, vehicle)?,
+            None => write!(f, "#{} untagged : ", self.position)?,
+        }
+        write!(f, "{} axle(s)", self.axles.len())?;
+        match self.tag_axles {
+            Some(axles) if self.is_axle_mismatch() => write!(f, ", tag {}", axles),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Vehicles of a train, from the tags read and the axles counted
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct Fusion {
+    pub vehicles: Vec<FusedVehicle>,
+}
+
+impl Fusion {
+    fn push(&mut self, vehicle: Option<&Vehicle>, axles: &[DateTime<Utc>]) {
+        self.vehicles.push(FusedVehicle {
+            position: self.vehicles.len() + 1,
+            vehicle: vehicle.map(|vehicle| vehicle.id.clone()),
+            tag_axles: vehicle.map(|vehicle| vehicle.tag().number_axles()),
+            axles: axles.to_vec(),
+        });
+    }
+
+    /// Vehicles of which no tag was read
+    pub fn untagged(&self) -> impl Iterator<Item = &FusedVehicle> {
+        self.vehicles.iter().filter(|vehicle| vehicle.is_untagged())
+    }
+
+    /// Tagged vehicles whose number of axles counted differs from their tag
+    pub fn axle_mismatches(&self) -> impl Iterator<Item = &FusedVehicle> {
+        self.vehicles
+            .iter()
+            .filter(|vehicle| vehicle.is_axle_mismatch())
+    }
+}
+
+impl Display for Fusion {
+    /// Formats a line per vehicle, followed by the totals
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for vehicle in &self.vehicles {
+            writeln!(f, "{}", vehicle)?;
+        }
+        write!(
+            f,
+            "{} vehicle(s), {} untagged, {} axle count mismatch(es)",
+            self.vehicles.len(),
+            self.untagged().count(),
+            self.axle_mismatches().count()
+        )
+    }
+}
+
+/// Splits the axles counted during the passage of a train between the vehicles of its consist
+pub fn fuse(consist: &Consist, axles: &[DateTime<Utc>], rules: &FusionRules) -> Fusion {
+    let mut axles = axles.to_vec();
+    axles.sort();
+    let vehicles: Vec<&Vehicle> = consist
+        .vehicles
+        .iter()
+        .filter(|vehicle| is_vehicle_group(vehicle.id.group))
+        .collect();
+    let starts: Vec<DateTime<Utc>> = vehicles
+        .iter()
+        .map(|vehicle| vehicle.first_seen() - rules.lead)
+        .collect();
+
+    let mut fusion = Fusion::default();
+    // Axles ahead of the first tagged vehicle
+    let mut cursor = match starts.first() {
+        Some(start) => axles.partition_point(|axle| axle < start),
+        None => axles.len(),
+    };
+    if cursor >= rules.min_untagged_axles || vehicles.is_empty() {
+        if cursor > 0 {
+            fusion.push(None, &axles[..cursor]);
+        }
+    } else {
+        cursor = 0;
+    }
+
+    for (i, vehicle) in vehicles.iter().enumerate() {
+        let end = match starts.get(i + 1) {
+            Some(next) => cursor + axles[cursor..].partition_point(|axle| axle < next),
+            None => axles.len(),
+        };
+        let counted = &axles[cursor..end];
+        let expected = usize::from(vehicle.tag().number_axles());
+        if counted.len() >= expected + rules.min_untagged_axles {
+            fusion.push(Some(vehicle), &counted[..expected]);
+            fusion.push(None, &counted[expected..]);
+        } else {
+            fusion.push(Some(vehicle), counted);
+        }
+        cursor = end;
+    }
+    fusion
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{consist::ConsistBuilder, read::TagRead, AEITagData};
+    use chrono::TimeZone;
+
+    /// QNSL 502 right, 4 axles
+    static TAG1: &str = "2F3E06C007DB1E139000000000000331";
+    /// IOCC 3088 right, 4 axles
+    static TAG2: &str = "9EA488C030426A179000000000000331";
+
+    fn start() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap()
+    }
+
+    /// Consist of the tags read at the given seconds
+    fn consist(reads: &[(&str, i64)]) -> Consist {
+        let mut builder = ConsistBuilder::new();
+        for (tag, second) in reads {
+            builder.push(TagRead::new(
+                AEITagData::new(tag).unwrap(),
+                start() + Duration::seconds(*second),
+                "R1",
+            ));
+        }
+        builder.build()
+    }
+
+    /// Axles counted at the given tenths of seconds
+    fn axles(tenths: &[i64]) -> Vec<DateTime<Utc>> {
+        tenths
+            .iter()
+            .map(|tenth| start() + Duration::milliseconds(tenth * 100))
+            .collect()
+    }
+
+    fn counts(fusion: &Fusion) -> Vec<(Option<u32>, usize)> {
+        fusion
+            .vehicles
+            .iter()
+            .map(|v| (v.vehicle.as_ref().map(|id| id.number), v.axles.len()))
+            .collect()
+    }
+
+    #[test]
+    fn tagged_vehicles() {
+        let fusion = fuse(
+            &consist(&[(TAG1, 0), (TAG2, 10)]),
+            &axles(&[2, 5, 40, 45, 102, 105, 140, 145]),
+            &FusionRules::default(),
+        );
+
+        assert_eq!(counts(&fusion), vec![(Some(502), 4), (Some(3088), 4)]);
+        assert_eq!(fusion.untagged().count(), 0);
+        assert_eq!(fusion.axle_mismatches().count(), 0);
+    }
+
+    #[test]
+    fn container_on_railcar() {
+        // Container tag of the railcar IOCC 3088, read just after it
+        let container = "56A488C030426A179000000000000331";
+        let fusion = fuse(
+            &consist(&[(TAG1, 0), (TAG2, 10), (container, 11)]),
+            &axles(&[2, 5, 40, 45, 102, 105, 140, 145]),
+            &FusionRules::default(),
+        );
+
+        assert_eq!(counts(&fusion), vec![(Some(502), 4), (Some(3088), 4)]);
+        assert_eq!(fusion.axle_mismatches().count(), 0);
+    }
+
+    #[test]
+    fn untagged_vehicles() {
+        let fusion = fuse(
+            &consist(&[(TAG1, 5), (TAG2, 20)]),
+            &axles(&[
+                0, 3, 20, 23, // untagged, ahead of the first tag read
+                52, 55, 90, 95, // QNSL 502
+                102, 105, 140, 145, 150, 153, 180, 185, // untagged
+                202, 205, 240, 245, // IOCC 3088
+            ]),
+            &FusionRules::default(),
+        );
+
+        assert_eq!(
+            counts(&fusion),
+            vec![(None, 4), (Some(502), 4), (None, 8), (Some(3088), 4)]
+        );
+        assert_eq!(fusion.vehicles[2].position, 3);
+        assert_eq!(fusion.untagged().count(), 2);
+        assert_eq!(fusion.axle_mismatches().count(), 0);
+    }
+
+    #[test]
+    fn axle_count_mismatches() {
+        let fusion = fuse(
+            &consist(&[(TAG1, 0), (TAG2, 10)]),
+            &axles(&[-1, 2, 5, 40, 102, 105, 140, 145, 150]),
+            &FusionRules::default(),
+        );
+
+        assert_eq!(counts(&fusion), vec![(Some(502), 4), (Some(3088), 5)]);
+        assert_eq!(
+            fusion.to_string(),
+            "#1 QNSL 502 : 4 axle(s)\n#2 IOCC 3088 : 5 axle(s), tag 4\n2 vehicle(s), 0 untagged, 1 axle count mismatch(es)"
+        );
+
+        let fusion = fuse(
+            &consist(&[(TAG1, 0), (TAG2, 10)]),
+            &axles(&[2, 5, 40, 102, 105, 140, 145]),
+            &FusionRules::default(),
+        );
+        assert_eq!(
+            fusion
+                .axle_mismatches()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>(),
+            vec!["#1 QNSL 502 : 3 axle(s), tag 4"]
+        );
+    }
+
+    #[test]
+    fn no_tag_read() {
+        let fusion = fuse(
+            &Consist::default(),
+            &axles(&[0, 3, 20, 23]),
+            &FusionRules::default(),
+        );
+
+        assert_eq!(counts(&fusion), vec![(None, 4)]);
+        assert!(fuse(&Consist::default(), &[], &FusionRules::default())
+            .vehicles
+            .is_empty());
+    }
+}
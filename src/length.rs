@@ -0,0 +1,142 @@
+//! Length of the equipment
+//!
+//! The tag stores the length in whole decimeters. [`Length`] converts it to the other units used
+//! in the railway industry, feet and inches being rounded to the nearest inch.
+//!
+//! ```rust
+//! use aei_tag_parser::length::{Length, Units};
+//!
+//! let length = Length::from_dm(286);
+//! assert_eq!(length.feet_inches(), (93, 10));
+//! assert_eq!(length.format(Units::Metric), "28.6 m");
+//! assert_eq!(length.format(Units::Imperial), "93' 10\"");
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, str::FromStr};
+
+/// Number of decimeters in a foot
+pub(crate) const DM_PER_FOOT: f64 = 3.048;
+
+/// Unit system used to display lengths
+#[derive(Copy, Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum Units {
+    #[default]
+    Metric,
+    Imperial,
+}
+
+impl FromStr for Units {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "metric" => Ok(Units::Metric),
+            "imperial" => Ok(Units::Imperial),
+            _ => Err(format!(
+                "unknown unit system {}, expected metric or imperial",
+                s
+            )),
+        }
+    }
+}
+
+/// Length stored as a whole number of decimeters, as on the tag
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Length {
+    decimeters: u16,
+}
+
+impl Length {
+    pub fn from_dm(decimeters: u16) -> Length {
+        Length { decimeters }
+    }
+
+    /// Length in decimeters
+    pub fn decimeters(&self) -> u16 {
+        self.decimeters
+    }
+
+    /// Length in meters
+    pub fn meters(&self) -> f64 {
+        f64::from(self.decimeters) / 10.0
+    }
+
+    /// Length in decimal feet
+    pub fn feet(&self) -> f64 {
+        f64::from(self.decimeters) / DM_PER_FOOT
+    }
+
+    /// Length rounded to the nearest inch, halves being rounded up.
+    /// One inch being exactly 0.254 dm, the rounding is done on integers
+    pub fn inches(&self) -> u32 {
+        (u32::from(self.decimeters) * 1000 + 127) / 254
+    }
+
+    /// Length in feet and inches, rounded to the nearest inch
+    pub fn feet_inches(&self) -> (u32, u8) {
+        let inches = self.inches();
+        (inches / 12, (inches % 12) as u8)
+    }
+
+    /// Formats the length in the given unit system : meters with one decimal for the metric
+    /// system, feet and inches for the imperial one
+    pub fn format(&self, units: Units) -> String {
+        match units {
+            Units::Metric => format!("{:.1} m", self.meters()),
+            Units::Imperial => {
+                let (feet, inches) = self.feet_inches();
+                format!("{}' {}\"", feet, inches)
+            }
+        }
+    }
+}
+
+impl Display for Length {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.format(Units::Metric))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversions() {
+        let length = Length::from_dm(192);
+
+        assert_eq!(length.decimeters(), 192);
+        assert_eq!(length.meters(), 19.2);
+        assert!((length.feet() - 62.992).abs() < 0.001);
+        assert_eq!(length.inches(), 756);
+        assert_eq!(length.feet_inches(), (63, 0));
+    }
+
+    #[test]
+    fn inch_rounding() {
+        // 1 dm = 3.937 in
+        assert_eq!(Length::from_dm(1).inches(), 4);
+        // 127 dm = 500 in exactly
+        assert_eq!(Length::from_dm(127).inches(), 500);
+        // 0 dm
+        assert_eq!(Length::from_dm(0).feet_inches(), (0, 0));
+        // 4095 dm = 16122.047 in
+        assert_eq!(Length::from_dm(4095).feet_inches(), (1343, 6));
+    }
+
+    #[test]
+    fn formatting() {
+        assert_eq!(Length::from_dm(106).format(Units::Metric), "10.6 m");
+        assert_eq!(Length::from_dm(106).format(Units::Imperial), "34' 9\"");
+        assert_eq!(Length::from_dm(106).to_string(), "10.6 m");
+    }
+
+    #[test]
+    fn units_parsing() {
+        assert_eq!("metric".parse::<Units>(), Ok(Units::Metric));
+        assert_eq!("Imperial".parse::<Units>(), Ok(Units::Imperial));
+        assert!("cubits".parse::<Units>().is_err());
+    }
+}
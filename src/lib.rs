@@ -50,6 +50,137 @@
 //! # 9EA488C5320CC01B9000000000000331 : Initials : IOCC      Car number : 85123
 //! ```
 //!
+//! Files and pipes are decoded line by line as they are read, so logs of any size can be processed. Blank lines and lines starting with `#` are skipped, and the invalid lines are reported with their line number :
+//! ```bash
+//! $ cat tags.txt | aei-tag-parser -s
+//! # Output :
+//! # Raw : 2F3E06C007DB1E139000000000000331    Initials : QNSL    ...
+//! # line 2 : 9EA4 : the provided string couldn't be parsed as an hexadecimal number
+//! ```
+//!
+//! The messages of a TransCore style reader (sign-on, presence, live and buffered reads) can be given instead of bare tags with `--input-format transcore`. Only the tags read are decoded, the other messages being skipped :
+//! ```bash
+//! $ cat reader.log
+//! #Model E4 Series Ver 4.14
+//! #PRESENCE ON
+//! #9EA488C030426A179000000000000331&1/05/01/24 12:00:00
+//! $ aei-tag-parser -f reader.log --input-format transcore --csv
+//! # Output :
+//! # 9EA488C030426A179000000000000331;IOCC;3088;Railcar;19;Right;10.6 m;Railroad
+//! ```
+//!
+//! The tags can also be received from the readers over the network and are printed as soon as they are decoded. `--listen tcp://ADDRESS:PORT` accepts the connections of the readers, `--listen udp://ADDRESS:PORT` receives their datagrams and `--connect tcp://HOST:PORT` connects to a reader, reconnecting when the connection is lost :
+//! ```bash
+//! $ aei-tag-parser --connect tcp://192.168.1.20:5000 --input-format transcore --csv
+//! ```
+//!
+//! The legacy readers connected over RS-232 are read with `--serial`, available when the CLI is installed with the `serial` feature. `--baud` sets the speed of the port (9600 by default) and `--framing` the bytes delimiting the frames : a terminator such as `lf` or `cr`, or a start and an end byte such as `stx,etx`. The bytes received outside of a frame and the garbled frames are dropped, the decoding resuming with the next frame :
+//! ```bash
+//! $ aei-tag-parser --serial /dev/ttyUSB0 --baud 19200 --framing stx,etx
+//! ```
+//!
+//! Output formats :
+//! ```bash
+//! # CSV, one tag per line with ";" as separator
+//! $ aei-tag-parser --csv 9EA488C030426A179000000000000331
+//! # JSON, one object per line
+//! $ aei-tag-parser --json 9EA488C030426A179000000000000331
+//! ```
+//!
+//! Owner defined fields stored in the spare bits can be decoded for a given equipment initial, each field being made of one or more bit ranges :
+//! ```bash
+//! $ aei-tag-parser --spare-field IOCC:fleet=81-88 --spare-field IOCC:security=106-109 9EA488C030426A179000528000140339
+//! # Output :
+//! # Raw : 9EA488C030426A179000528000140339    Initials : IOCC    Car number : 3088    Equipment type : Railcar(19)    Side : Right    Length : 10.6 m    Owner data : fleet=165,security=5
+//! ```
+//!
+//! Lengths are printed in meters by default, `--units imperial` prints them in feet and inches in every output format :
+//! ```bash
+//! $ aei-tag-parser --units imperial 9EA488C5320CC01B9000000000000331
+//! # Output :
+//! # Raw : 9EA488C5320CC01B9000000000000331    Initials : IOCC    Car number : 85123    Equipment type : Railcar(19)    Side : Left    Length : 63' 0"
+//! ```
+//!
+//! The owners of the reporting marks can be read from a local CSV or TOML file, marks missing from it being flagged as unknown :
+//! ```bash
+//! $ cat marks.csv
+//! mark;owner;country;kind;active_from;active_to
+//! IOCC;Iron Ore Company of Canada;CA;railroad;1954-01-01;
+//! $ aei-tag-parser --registry marks.csv 9EA488C030426A179000000000000331 2F3E06C007DB1E139000000000000331
+//! # Output :
+//! # Raw : 9EA488C030426A179000000000000331    Initials : IOCC    ...    Owner : Iron Ore Company of Canada (CA)
+//! # Raw : 2F3E06C007DB1E139000000000000331    Initials : QNSL    ...    Owner : Unknown mark
+//! ```
+//!
+//! The `lint` command checks the tags for programming mistakes (invalid checksums, length of 0, implausible number of axles, reserved bits set, ...). It exits with 1 when a violation reaches the `--fail-on` severity (`error` by default) and with 2 when a tag can't be parsed, so it can gate a batch of tags to program :
+//! ```bash
+//! $ aei-tag-parser lint -f tags.txt --fail-on warning
+//! # Output :
+//! # 9EA488C030426A179000000000000331 : OK
+//! # 9EA488C030426A139000000000000331 : error[checksum] : the checksums don't match the tag data
+//! ```
+//! The thresholds of the rules can be set per equipment group, and the severities overridden, with `--config lint.toml`.
+//!
+//! The `reconcile` command compares the tags with a roster of the fleet (CSV with the `mark`, `number` and optionally `length_dm`, `axles`, `group` and `bearing` columns). It reports the tags disagreeing with the roster field by field, the cars of the roster never seen and the tags which aren't on the roster :
+//! ```bash
+//! $ aei-tag-parser reconcile --roster roster.csv -f tags.txt
+//! # Output :
+//! # MISMATCH : IOCC 85123 (Left) : length roster 190, tag 192; axles roster 6, tag 4
+//! # UNSEEN   : IOCC 99999
+//! # UNKNOWN  : QNSL 502 (Right)
+//! ```
+//!
+//! The `encode-roster` command generates the left and right tags to program on every car of a roster. The `length_dm` and `axles` columns are required, the group defaults to railcar (19) and the bearing type to 0. Every tag is decoded back and checked against the roster before being written, in CSV (default), JSON or as raw 16 bytes records with `--format binary` :
+//! ```bash
+//! $ aei-tag-parser encode-roster --roster roster.csv
+//! # Output :
+//! # mark;number;side;tag
+//! # IOCC;3088;Left;9EA488C030406A1F9000000000000331
+//! # IOCC;3088;Right;9EA488C030426A179000000000000331
+//! ```
+//!
+//! The `export` command groups the reads of each reader, in the order of time, into train passages, a passage ending after 30 seconds without reads (`--gap`), and writes the consist of each one as an AAR T-94 style fixed-format report with `--t94`. The timestamped reads are taken from a CSV file given with `--reads`, the tags of the other inputs being stamped as they are received. The reads of a TransCore reader (`--input-format transcore`) keep the timestamps it sends, and a passage also lasts while its presence detector is on. With a site configuration giving the side of the track of each antenna (`--site-config`), the direction of the train and the orientation of the vehicles are written too :
+//! ```bash
+//! $ aei-tag-parser export --t94 --site SEPT1 --reads reads.csv --site-config site.toml
+//! # Output :
+//! # THSEPT1  2024050112000020240501120005R002000080000478
+//! # EQ00105QNSL000502AB0402862F3E06C007D91E1B900000000000033120240501120000
+//! # EQ00219IOCC085123BL0401929EA488C5320CC01B900000000000033120240501120005
+//! # TT00200004
+//! ```
+//!
+//! `--edi418` writes instead an X12 EDI 418 advance interchange consist for the railroad receiving the trains, a transaction set per train. The interchange IDs are given with `--sender` and `--receiver`, the load status of the vehicles with `--load-status loaded|empty` :
+//! ```bash
+//! $ aei-tag-parser export --edi418 --sender QNSL --receiver CN --reads reads.csv
+//! # Output :
+//! # ISA*00*          *00*          *ZZ*QNSL           *ZZ*CN             *240501*1230*U*00401*000000001*0*P*>~
+//! # GS*AI*QNSL*CN*20240501*1230*1*X*004010~
+//! # ST*418*0001~
+//! # LX*1~
+//! # W2*QNSL*502**LO~
+//! # ...
+//! ```
+//!
+//! The `summary` command prints the totals of each train passage, grouped as for `export` : vehicles by equipment group, locomotives, axles, length, presence of the end-of-train device and the other tags read, such as those of the containers, which are not vehicles :
+//! ```bash
+//! $ aei-tag-parser summary --reads reads.csv
+//! # Output :
+//! # 2024-05-01T12:00:00Z - 2024-05-01T12:00:05Z : 2 vehicles, 1 locomotive, 8 axles, 47.8 m (156.8 ft), EOT missing
+//! #     Locomotive(5) : 1
+//! #     Railcar(19) : 1
+//! ```
+//!
+//! The `verify-consist` command compares the consist of each train passage with the planned train list (a CSV listing the cars in order, with the columns of a roster). It reports the planned cars not read, the vehicles read which aren't planned, the fewest cars out of the planned order and the cars whose tag disagrees with the list. The command exits with 1 when a consist differs from the list, and `--json` prints the result in JSON format :
+//! ```bash
+//! $ aei-tag-parser verify-consist --reads reads.csv --plan plan.csv
+//! # Output :
+//! # 2024-05-01T12:00:00Z - 2024-05-01T12:00:05Z :
+//! # MISSING  : IOCC 1 planned #3
+//! # MOVED    : IOCC 85123 planned #1, read #2
+//! # MISMATCH : IOCC 85123 read #2 : length roster 190, tag 192
+//! # 1 car(s) in order, 1 missing, 0 extra, 1 out of order, 1 mismatch(es)
+//! ```
+//!
 //! ## Librairie
 //!
 //! This project can also be used as an external library. Documentation is available here : [https://docs.rs/aei_tag_parser/1.0.0/aei_tag_parser/index.html](https://docs.rs/aei_tag_parser/1.0.0/aei_tag_parser/index.html)
@@ -57,12 +188,66 @@
 //! ### Usage
 //!
 //! ```rust
+//! use aei_tag_parser::AEITagData;
+//!
 //! let tag_str : String = String::from("9EA488C030426A179000000000000331");
-//! let tag : AEITagData = AEITagData::new(&tag_str);
+//! let tag : AEITagData = AEITagData::new(&tag_str).unwrap();
 //!
 //! println!("Tag {} content is : \r\n\tInitials: {}\r\n\tCar number: {}", &tag_str, tag.equipment_initial(), tag.car_number());
 //! ```
 //!
+//! A tag can also be corrected, the checksums being updated and the other bits kept as is :
+//!
+//! ```rust
+//! use aei_tag_parser::{AEITagData, Side};
+//!
+//! let mut tag = AEITagData::new("9EA488C030426A179000000000000331").unwrap();
+//! tag.with_equipment_initial("QNSL").unwrap().with_car_number(502).unwrap().with_side_indicator(Side::LEFT);
+//!
+//! println!("New tag value : {}", hex::encode_upper(tag.raw()));
+//! ```
+//!
+//! The context of a reading (timestamp, reader, antenna, signal strength and read count) is kept with the tag in a `TagRead`, which can be read from and written to CSV or JSON :
+//!
+//! ```rust
+//! use aei_tag_parser::{read::TagRead, AEITagData};
+//!
+//! let read = TagRead::new(AEITagData::new("9EA488C030426A179000000000000331").unwrap(), chrono::Utc::now(), "SEPT-ILES");
+//!
+//! println!("{}", read.to_csv());
+//! ```
+//!
+//! The `async` feature adds the `codec` module, decoding the tags received on any tokio `AsyncRead` (a socket for instance) and writing the decoded records to an `AsyncWrite` :
+//!
+//! ```toml
+//! [dependencies]
+//! aei_tag_parser = { version = "1.2", features = ["async"] }
+//! ```
+//!
+//! The `reader::llrp` module connects to the LLRP readers (EPC Gen2 UHF readers reading tags written in the AEI format), starts an inventory and decodes the tags reported with their antenna, signal strength and timestamp.
+//!
+//! The `framing` module extracts the frames of a serial reader byte stream, and the `serial` module, with the `serial` feature, opens the serial port they are read from.
+//!
+//! The `train` module groups the reads of a reader into train passages, a passage ending after a gap without reads or when the presence detector of the reader turns off. Each passage keeps its start and end time and its reads in order.
+//!
+//! The `consist` module pairs the left and right tags of the vehicles of a passage, merging the repeated reads, and lists the vehicles in order with the sides read. The consist can be exported in CSV or JSON.
+//!
+//! The `orientation` module infers the direction of the train and the leading end of each vehicle, short or long hood forward for the locomotives, from the side of the track of the antennas which read the tags, given in a site configuration.
+//!
+//! The `t94` module writes a consist as an AAR T-94 style report and parses the existing reports, decoding their tags again to validate the records. The reports of several passages, written one after the other by `export`, are parsed together with `T94Report::parse_all`.
+//!
+//! The `edi418` module builds the X12 EDI 418 interchange of a list of consists, checking every element against its definition.
+//!
+//! The `summary` module adds up the vehicles, axles and length of a passage or a consist.
+//!
+//! The `align` module computes the minimum edit script between two sequences, the elements out of order being reported as moved, and the `verify` module uses it to compare a consist with its train list.
+//!
+//! The `diff` module compares the consists of a train read at two sites : the vehicles set out, picked up or moved in between, and the tags read at one of the sites only.
+//!
+//! The `fusion` module splits the axles counted by the wheel sensors of a site between the vehicles of a consist, from the number of axles of their tags, adding the vehicles of which no tag was read and flagging the axle count mismatches.
+//!
+//! The `detector` module maps the alarms of the hot bearing and wheel detectors, given by axle from the head of the train, to the vehicle, its axle and side in the AAR designation and its bearing type. A vehicle without tags shifts the alarms behind it onto the wrong vehicles : with the axles counted by the site, `map_fused_alarms` locates the vehicles from the `fusion` instead and flags the alarms behind an untagged vehicle or an axle count mismatch as unreliable.
+//!
 //! # Install
 //!
 //! To install the CLI util, you must have Rust installed (cf [RustUp](https://rustup.rs/)).
@@ -71,6 +256,11 @@
 //! ```bash
 //! $ cargo install aei_tag_parser
 //! ```
+//!
+//! The serial port input needs the `serial` feature :
+//! ```bash
+//! $ cargo install aei_tag_parser --features serial
+//! ```
 
 /* Data field descriptions for the Railcar tag
 // +==========================+===============+====================+===============+===============+===============================================+
@@ -119,8 +309,44 @@ use serde::{Deserialize, Serialize};
 use std::{
     error,
     fmt::{Debug, Display},
+    ops::RangeInclusive,
 };
 
+pub mod align;
+#[cfg(feature = "async")]
+pub mod codec;
+pub mod consist;
+mod csv_input;
+pub mod detector;
+pub mod diff;
+pub mod edi418;
+pub mod encode;
+pub mod framing;
+pub mod fusion;
+pub mod length;
+pub mod lint;
+pub mod mark;
+pub mod net;
+pub mod orientation;
+pub mod read;
+pub mod reader;
+pub mod reconcile;
+pub mod registry;
+pub mod roster;
+#[cfg(feature = "serial")]
+pub mod serial;
+pub mod spare;
+pub mod stream;
+pub mod summary;
+pub mod t94;
+pub mod train;
+pub mod verify;
+
+use length::{Length, Units};
+use mark::{EquipmentInitial, OwnerClass};
+use registry::MarkLookup;
+use spare::SpareField;
+
 #[derive(Debug)]
 pub enum NewTagError {
     HexParsing(FromHexError),
@@ -153,6 +379,102 @@ impl error::Error for NewTagError {
     }
 }
 
+#[derive(Debug, PartialEq)]
+pub enum BitRangeError {
+    /// The range goes past the last bit of the tag (127)
+    OutOfBounds(RangeInclusive<usize>),
+    /// The start of the range is after its end
+    Empty(RangeInclusive<usize>),
+    /// The ranges cover more bits than the returned value can hold
+    TooWide(usize),
+}
+
+impl Display for BitRangeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BitRangeError::OutOfBounds(range) => write!(
+                f,
+                "the bit range {}-{} is out of the tag bounds (0-127)",
+                range.start(),
+                range.end()
+            ),
+            BitRangeError::Empty(range) => write!(
+                f,
+                "the bit range {}-{} is empty",
+                range.start(),
+                range.end()
+            ),
+            BitRangeError::TooWide(width) => {
+                write!(
+                    f,
+                    "the bit ranges cover {} bits, at most 128 are allowed",
+                    width
+                )
+            }
+        }
+    }
+}
+
+impl error::Error for BitRangeError {}
+
+#[derive(Debug, PartialEq)]
+pub enum EncodeTagError {
+    /// The equipment initial must be made of 1 to 4 letters, only followed by blanks
+    InvalidEquipmentInitial(String),
+    /// The car number must be contained in \[0;999999]
+    CarNumberOutOfRange(u32),
+    /// The length must be contained in \[0;4095] decimeters
+    LengthOutOfRange(u16),
+    /// The equipment group code must be contained in \[0;31]
+    GroupOutOfRange(u8),
+    /// The number of axles must be contained in \[1;32]
+    AxlesOutOfRange(u8),
+    /// The bearing type code must be contained in \[0;7]
+    BearingOutOfRange(u8),
+}
+
+impl Display for EncodeTagError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EncodeTagError::InvalidEquipmentInitial(initial) => write!(
+                f,
+                "the equipment initial {:?} must be made of 1 to 4 letters",
+                initial
+            ),
+            EncodeTagError::CarNumberOutOfRange(number) => write!(
+                f,
+                "the car number {} is out of range (0-{})",
+                number, MAX_CAR_NUMBER
+            ),
+            EncodeTagError::LengthOutOfRange(length) => write!(
+                f,
+                "the length {} dm is out of range (0-{})",
+                length, MAX_LENGTH_DM
+            ),
+            EncodeTagError::GroupOutOfRange(group) => {
+                write!(
+                    f,
+                    "the equipment group code {} is out of range (0-31)",
+                    group
+                )
+            }
+            EncodeTagError::AxlesOutOfRange(axles) => {
+                write!(f, "the number of axles {} is out of range (1-32)", axles)
+            }
+            EncodeTagError::BearingOutOfRange(bearing) => {
+                write!(f, "the bearing type code {} is out of range (0-7)", bearing)
+            }
+        }
+    }
+}
+
+impl error::Error for EncodeTagError {}
+
+/// Highest car number which can be encoded on a tag
+pub const MAX_CAR_NUMBER: u32 = 999_999;
+/// Highest length in decimeters which can be encoded on a tag
+pub const MAX_LENGTH_DM: u16 = 4095;
+
 #[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum Side {
     LEFT,
@@ -162,13 +484,14 @@ pub enum Side {
 impl Display for Side {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match *self {
-            Side::LEFT => write!(f,"Left"),
-            Side::RIGHT => write!(f,"Right"),
+            Side::LEFT => write!(f, "Left"),
+            Side::RIGHT => write!(f, "Right"),
         }
     }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(from = "SerializedTag")]
 pub struct AEITagData {
     raw: [u8; 16],
     equipment_group_code: u8,
@@ -179,6 +502,53 @@ pub struct AEITagData {
     side_indicator: Side,
     length_dm: u16,
     number_axles: u8,
+    bearing_type_code: u8,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    spare_fields: Vec<SpareField>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mark_owner: Option<MarkLookup>,
+}
+
+/// Serialized representation of a tag. The fields added since the first release are optional, so
+/// that the tags serialized by the previous releases can still be read
+#[derive(Deserialize)]
+struct SerializedTag {
+    raw: [u8; 16],
+    equipment_group_code: u8,
+    tag_type_code: u8,
+    equipment_initial_code: u32,
+    equipment_initial: String,
+    car_number: u32,
+    side_indicator: Side,
+    length_dm: u16,
+    number_axles: u8,
+    #[serde(default)]
+    bearing_type_code: Option<u8>,
+    #[serde(default)]
+    spare_fields: Vec<SpareField>,
+    #[serde(default)]
+    mark_owner: Option<MarkLookup>,
+}
+
+impl From<SerializedTag> for AEITagData {
+    fn from(tag: SerializedTag) -> Self {
+        AEITagData {
+            bearing_type_code: tag
+                .bearing_type_code
+                .unwrap_or_else(|| AEITagData::parse_bearing_type(&tag.raw)),
+            raw: tag.raw,
+            equipment_group_code: tag.equipment_group_code,
+            tag_type_code: tag.tag_type_code,
+            equipment_initial_code: tag.equipment_initial_code,
+            equipment_initial: tag.equipment_initial,
+            car_number: tag.car_number,
+            side_indicator: tag.side_indicator,
+            length_dm: tag.length_dm,
+            number_axles: tag.number_axles,
+            spare_fields: tag.spare_fields,
+            mark_owner: tag.mark_owner,
+        }
+    }
 }
 
 impl AEITagData {
@@ -186,6 +556,11 @@ impl AEITagData {
         let mut raw = [0u8; 16];
         hex::decode_to_slice(tag, &mut raw)?;
 
+        Ok(AEITagData::from_raw(raw))
+    }
+
+    /// Decode a tag from its raw binary value
+    pub fn from_raw(raw: [u8; 16]) -> AEITagData {
         let equipment_group_code = AEITagData::parse_eqp_group(&raw);
         let tag_type_code = AEITagData::parse_tag_type(&raw);
         let equipment_initial_code = AEITagData::parse_eqp_initial(&raw);
@@ -193,8 +568,9 @@ impl AEITagData {
         let side_indicator = AEITagData::parse_side(&raw);
         let length_dm = AEITagData::parse_length(&raw);
         let number_axles = AEITagData::parse_axles(&raw);
+        let bearing_type_code = AEITagData::parse_bearing_type(&raw);
 
-        Ok(AEITagData {
+        AEITagData {
             raw,
             equipment_group_code,
             tag_type_code,
@@ -204,7 +580,10 @@ impl AEITagData {
             side_indicator,
             length_dm,
             number_axles,
-        })
+            bearing_type_code,
+            spare_fields: Vec::new(),
+            mark_owner: None,
+        }
     }
 
     /// Parse the equipement group code from the raw tag data.
@@ -291,7 +670,30 @@ impl AEITagData {
         let c3 = char::try_from(if n3 == 0 { 32 } else { n3 + 64 }).unwrap();
         let c4 = char::try_from(if n4 == 0 { 32 } else { n4 + 64 }).unwrap();
 
-        vec![c1, c2, c3, c4].iter().collect()
+        [c1, c2, c3, c4].iter().collect()
+    }
+
+    /// Encode an equipment initial such as "IOCC" or "UP" into its 19 bits code
+    fn serialize_equipment_initial(equipment_initial: &str) -> Result<u32, EncodeTagError> {
+        let invalid = || EncodeTagError::InvalidEquipmentInitial(String::from(equipment_initial));
+        let letters = equipment_initial.trim_end();
+        if letters.is_empty()
+            || letters.len() > 4
+            || equipment_initial.len() > 4
+            || !letters.chars().all(|c| c.is_ascii_uppercase())
+        {
+            return Err(invalid());
+        }
+
+        let mut chars = letters.bytes();
+        // For C1, A = 0, ..., Z = 25
+        let mut code = u32::from(chars.next().ok_or_else(invalid)? - b'A');
+        // For C2, C3, C4, Blank / Space = 0, A = 1, ..., Z = 26
+        for _ in 1..4 {
+            code = code * 27 + chars.next().map_or(0, |c| u32::from(c - b'A') + 1);
+        }
+
+        Ok(code)
     }
 
     /// Equipment initial value
@@ -299,6 +701,22 @@ impl AEITagData {
         AEITagData::deserialize_equipement_initial(self.equipment_initial_code)
     }
 
+    /// Equipment initial (reporting mark)
+    pub fn mark(&self) -> EquipmentInitial {
+        EquipmentInitial::from_code(self.equipment_initial_code)
+    }
+
+    /// Owner category given by the last letter of the equipment initial
+    pub fn owner_class(&self) -> OwnerClass {
+        self.mark().owner_class()
+    }
+
+    /// Check that the owner category of the equipment initial is expected for the equipment
+    /// group, e.g. a container mark on a railcar tag is suspicious
+    pub fn owner_class_consistent(&self) -> bool {
+        self.owner_class().matches_group(self.equipment_group_code)
+    }
+
     /// Parse the car number from the raw tag data
     fn parse_car_number(raw: &[u8; 16]) -> u32 {
         let mut value = [0u8; 4];
@@ -345,7 +763,12 @@ impl AEITagData {
 
     /// Length of the railcar in feets
     pub fn length_ft(&self) -> u16 {
-        (f64::try_from(self.length_dm).unwrap() * 0.328084).round() as u16
+        self.length().feet().round() as u16
+    }
+
+    /// Length of the railcar
+    pub fn length(&self) -> Length {
+        Length::from_dm(self.length_dm)
     }
 
     /// Parse the number of axles from the raw tag data
@@ -358,35 +781,286 @@ impl AEITagData {
         self.number_axles
     }
 
+    /// Parse the bearing type code from the raw tag data
+    fn parse_bearing_type(raw: &[u8; 16]) -> u8 {
+        (raw[8] & 0b0111_0000) >> 4
+    }
+
+    /// Bearing type code value
+    pub fn bearing_type_code(&self) -> u8 {
+        self.bearing_type_code
+    }
+
+    fn parse_bearing_type_code(code: u8) -> String {
+        match code {
+            0 => String::from("Plain bearing"),
+            1 => String::from("Roller bearing"),
+            2..=7 => String::from("Reserved"),
+            _ => panic!("You should never reach this point. The value of bearing type code is meant to be contained in [0; 7]")
+        }
+    }
+
+    /// Bearing type value
+    pub fn bearing_type(&self) -> String {
+        AEITagData::parse_bearing_type_code(self.bearing_type_code)
+    }
+
     /// Raw value of the tag
     pub fn raw(&self) -> &[u8; 16] {
         &self.raw
     }
 
+    /// Value of the bits contained in `range`, bit 0 being the most significant bit of the tag
+    /// as in the AAR data field table. Both ends of the range are included.
+    ///
+    /// ```rust
+    /// use aei_tag_parser::AEITagData;
+    ///
+    /// let tag = AEITagData::new("2F3E06C007DB1E139000000000000331").unwrap();
+    /// // Equipment group code
+    /// assert_eq!(tag.bits(0..=4).unwrap(), 5);
+    /// ```
+    pub fn bits(&self, range: RangeInclusive<usize>) -> Result<u128, BitRangeError> {
+        if range.start() > range.end() {
+            return Err(BitRangeError::Empty(range));
+        }
+        if *range.end() > 127 {
+            return Err(BitRangeError::OutOfBounds(range));
+        }
+
+        let width = range.end() - range.start() + 1;
+        let value = u128::from_be_bytes(self.raw) >> (127 - range.end());
+        if width == 128 {
+            Ok(value)
+        } else {
+            Ok(value & ((1u128 << width) - 1))
+        }
+    }
+
+    /// Value made of the concatenation of several bit ranges, the first range holding the most
+    /// significant bits. This is how the fields split across the tag are laid out, e.g. the
+    /// length is stored in bits 94-96 then 47-55.
+    pub fn bits_multi(&self, ranges: &[RangeInclusive<usize>]) -> Result<u128, BitRangeError> {
+        let mut width = 0;
+        let mut value = 0u128;
+        for range in ranges {
+            let bits = self.bits(range.clone())?;
+            let range_width = range.end() - range.start() + 1;
+            width += range_width;
+            if width > 128 {
+                return Err(BitRangeError::TooWide(width));
+            }
+            value = value.checked_shl(range_width as u32).unwrap_or(0) | bits;
+        }
+
+        Ok(value)
+    }
+
+    /// Sets the value of the given bit ranges, the first range receiving the most significant bits.
+    /// The checksums are not updated
+    pub(crate) fn set_bits_multi(&mut self, ranges: &[RangeInclusive<usize>], value: u128) {
+        let mut raw = u128::from_be_bytes(self.raw);
+        let mut value = value;
+        for range in ranges.iter().rev() {
+            let width = range.end() - range.start() + 1;
+            let mask = if width == 128 {
+                u128::MAX
+            } else {
+                (1u128 << width) - 1
+            };
+            let shift = 127 - range.end();
+            raw = (raw & !(mask << shift)) | ((value & mask) << shift);
+            value = value.checked_shr(width as u32).unwrap_or(0);
+        }
+        self.raw = raw.to_be_bytes();
+    }
+
+    /// Checksum of one half of the tag : the ones' complement of the sum of the 60 data bits taken
+    /// as bytes, truncated to 2 bits
+    fn checksum(half: &[u8]) -> u8 {
+        let sum = half[..7]
+            .iter()
+            .fold(u32::from(half[7] & 0xF0), |sum, byte| {
+                sum + u32::from(*byte)
+            });
+        !(sum as u8) & 0b11
+    }
+
+    /// Check that both checksums (bits 60-61 and 124-125) match the data of the tag
+    pub fn checksums_valid(&self) -> bool {
+        self.bits(60..=61) == Ok(u128::from(AEITagData::checksum(&self.raw[..8])))
+            && self.bits(124..=125) == Ok(u128::from(AEITagData::checksum(&self.raw[8..])))
+    }
+
+    /// Recompute both checksums then every field from the raw value
+    fn reencode(&mut self) {
+        let first = AEITagData::checksum(&self.raw[..8]);
+        let second = AEITagData::checksum(&self.raw[8..]);
+        self.set_bits_multi(&[60..=61], u128::from(first));
+        self.set_bits_multi(&[124..=125], u128::from(second));
+
+        let spare_fields = std::mem::take(&mut self.spare_fields);
+        let mark_owner = self.mark_owner.take();
+        *self = AEITagData {
+            spare_fields,
+            mark_owner,
+            ..AEITagData::from_raw(self.raw)
+        };
+    }
+
+    /// Change the equipment initial. The owner fields decoded from the spare bits and the owner of
+    /// the mark are cleared as they depend on the equipment initial, the spare bits themselves are
+    /// kept
+    pub fn with_equipment_initial(
+        &mut self,
+        equipment_initial: &str,
+    ) -> Result<&mut AEITagData, EncodeTagError> {
+        let code = AEITagData::serialize_equipment_initial(equipment_initial)?;
+        self.set_bits_multi(&[7..=25], u128::from(code));
+        self.spare_fields.clear();
+        self.mark_owner = None;
+        self.reencode();
+        Ok(self)
+    }
+
+    /// Change the car number
+    pub fn with_car_number(&mut self, car_number: u32) -> Result<&mut AEITagData, EncodeTagError> {
+        if car_number > MAX_CAR_NUMBER {
+            return Err(EncodeTagError::CarNumberOutOfRange(car_number));
+        }
+        self.set_bits_multi(&[26..=45], u128::from(car_number));
+        self.reencode();
+        Ok(self)
+    }
+
+    /// Change the length in decimeters
+    pub fn with_length_dm(&mut self, length_dm: u16) -> Result<&mut AEITagData, EncodeTagError> {
+        if length_dm > MAX_LENGTH_DM {
+            return Err(EncodeTagError::LengthOutOfRange(length_dm));
+        }
+        self.set_bits_multi(&[94..=96, 47..=55], u128::from(length_dm));
+        self.reencode();
+        Ok(self)
+    }
+
+    /// Change the side indicator
+    pub fn with_side_indicator(&mut self, side: Side) -> &mut AEITagData {
+        self.set_bits_multi(&[46..=46], u128::from(side == Side::RIGHT));
+        self.reencode();
+        self
+    }
+
+    /// Owner defined fields decoded from the spare bits by a [`spare::SpareFieldDecoder`].
+    /// Empty unless the tag went through a [`spare::SpareFieldRegistry`]
+    pub fn spare_fields(&self) -> &[SpareField] {
+        &self.spare_fields
+    }
+
+    /// Owner of the equipment initial found in a [`registry::MarkRegistry`].
+    /// None unless the tag went through a registry
+    pub fn mark_owner(&self) -> Option<&MarkLookup> {
+        self.mark_owner.as_ref()
+    }
+
     /// Returns a short string which describes the datas
     pub fn to_short_string(&self) -> String {
-        format!(
-            "Raw : {}\tInitials : {}\tCar number : {}\tEquipment type : {}({})\tSide : {}",
-            hex::encode_upper(&self.raw),
+        self.to_short_string_in(Units::Metric)
+    }
+
+    /// Returns a short string which describes the datas, the length being written in the given units
+    pub fn to_short_string_in(&self, units: Units) -> String {
+        let mut out = format!(
+            "Raw : {}\tInitials : {}\tCar number : {}\tEquipment type : {}({})\tSide : {}\tLength : {}",
+            hex::encode_upper(self.raw),
             self.equipment_initial(),
             self.car_number,
             self.equipment_group(),
             self.equipment_group_code,
-            self.side_indicator
-        )
+            self.side_indicator,
+            self.length().format(units)
+        );
+        if let Some(owner) = &self.mark_owner {
+            out.push_str(&format!("\tOwner : {}", owner));
+        }
+        if !self.spare_fields.is_empty() {
+            out.push_str(&format!("\tOwner data : {}", self.spare_fields_string()));
+        }
+        out
     }
 
     // Returns a string in CSV format which describes the tag
     pub fn to_csv(&self) -> String {
-        format!(
-            "{};{};{};{};{};{}",
-            hex::encode_upper(&self.raw),
+        self.to_csv_in(Units::Metric)
+    }
+
+    /// Returns a string in CSV format which describes the tag, the length being written in the given units
+    pub fn to_csv_in(&self, units: Units) -> String {
+        let mut out = format!(
+            "{};{};{};{};{};{};{};{}",
+            hex::encode_upper(self.raw),
             self.equipment_initial(),
             self.car_number,
             self.equipment_group(),
             self.equipment_group_code,
-            self.side_indicator
-        )
+            self.side_indicator,
+            self.length().format(units),
+            self.owner_class()
+        );
+        match &self.mark_owner {
+            Some(MarkLookup::Known(owner)) => {
+                out.push_str(&format!(";{}", csv_input::quote(&owner.owner)))
+            }
+            Some(MarkLookup::Unknown) => out.push_str(";UNKNOWN"),
+            None => (),
+        }
+        if !self.spare_fields.is_empty() {
+            out.push_str(&format!(";{}", self.spare_fields_string()));
+        }
+        out
+    }
+
+    /// Returns the tag serialized in JSON
+    pub fn to_json(&self) -> String {
+        self.to_json_in(Units::Metric)
+    }
+
+    /// Returns the tag serialized in JSON with an additional `length` object holding the
+    /// length in the given units
+    pub fn to_json_in(&self, units: Units) -> String {
+        let mut value = serde_json::to_value(self).expect("a decoded tag can always be serialized");
+        value["length"] = self.length_json(units);
+        value["owner_class"] = serde_json::json!(self.owner_class());
+        value["owner_class_consistent"] = serde_json::json!(self.owner_class_consistent());
+        value.to_string()
+    }
+
+    fn length_json(&self, units: Units) -> serde_json::Value {
+        let length = self.length();
+        match units {
+            Units::Metric => serde_json::json!({
+                "unit": "m",
+                "value": length.meters(),
+                "text": length.format(units),
+            }),
+            Units::Imperial => {
+                let (feet, inches) = length.feet_inches();
+                serde_json::json!({
+                    "unit": "ft",
+                    "value": length.feet(),
+                    "feet": feet,
+                    "inches": inches,
+                    "text": length.format(units),
+                })
+            }
+        }
+    }
+
+    fn spare_fields_string(&self) -> String {
+        self.spare_fields
+            .iter()
+            .map(|field| field.to_string())
+            .collect::<Vec<String>>()
+            .join(",")
     }
 
     /// Check if 2 tags belong to the same wagon. It compare the equipment initials code,
@@ -547,6 +1221,28 @@ mod tests {
         assert_eq!(AEITagData::new(TAG1).unwrap().number_axles(), 4);
     }
 
+    #[test]
+    fn valid_bearing_type() {
+        let tag = AEITagData::new(TAG1).unwrap();
+
+        assert_eq!(tag.bearing_type_code(), 1);
+        assert_eq!(tag.bearing_type(), "Roller bearing");
+        assert_eq!(
+            u128::from(tag.bearing_type_code()),
+            tag.bits(65..=67).unwrap()
+        );
+    }
+
+    #[test]
+    fn deserialize_previous_release() {
+        let mut json = serde_json::to_value(AEITagData::new(TAG1).unwrap()).unwrap();
+        json.as_object_mut().unwrap().remove("bearing_type_code");
+        let tag: AEITagData = serde_json::from_value(json).unwrap();
+
+        assert_eq!(tag.bearing_type_code(), 1);
+        assert_eq!(tag.car_number(), 502);
+    }
+
     #[test]
     fn invalid_hex_odd_length() {
         let result = AEITagData::new("00F");
@@ -608,6 +1304,145 @@ mod tests {
     fn test_short_string() {
         let tag = AEITagData::new(TAG1).unwrap();
 
-        assert_eq!(tag.to_short_string(), "Initials : QNSL\tCar number : 502");
+        assert_eq!(
+            tag.to_short_string(),
+            "Raw : 2F3E06C007DB1E139000000000000331\tInitials : QNSL\tCar number : 502\tEquipment type : Locomotive(5)\tSide : Right\tLength : 28.6 m"
+        );
+    }
+
+    #[test]
+    fn formats_in_units() {
+        let tag = AEITagData::new(TAG3).unwrap();
+
+        assert_eq!(tag.length().feet_inches(), (63, 0));
+        assert!(tag
+            .to_short_string_in(Units::Imperial)
+            .ends_with("\tLength : 63' 0\""));
+        assert_eq!(
+            tag.to_csv_in(Units::Imperial),
+            "9EA488C5320CC01B9000000000000331;IOCC;85123;Railcar;19;Left;63' 0\";Railroad"
+        );
+        assert_eq!(
+            tag.to_csv(),
+            "9EA488C5320CC01B9000000000000331;IOCC;85123;Railcar;19;Left;19.2 m;Railroad"
+        );
+
+        let json: serde_json::Value =
+            serde_json::from_str(&tag.to_json_in(Units::Imperial)).unwrap();
+        assert_eq!(json["length"]["feet"], 63);
+        assert_eq!(json["length"]["inches"], 0);
+        assert_eq!(json["length_dm"], 192);
+        assert_eq!(json["owner_class"], "railroad");
+        assert_eq!(json["owner_class_consistent"], true);
+    }
+
+    #[test]
+    fn valid_checksums() {
+        assert!(AEITagData::new(TAG1).unwrap().checksums_valid());
+        assert!(AEITagData::new(TAG2).unwrap().checksums_valid());
+        assert!(AEITagData::new(TAG3).unwrap().checksums_valid());
+        assert!(!AEITagData::new("9EA488C030426A139000000000000331")
+            .unwrap()
+            .checksums_valid());
+    }
+
+    #[test]
+    fn reencode_fields() {
+        let mut tag = AEITagData::new(TAG2).unwrap();
+        tag.with_equipment_initial("UP")
+            .unwrap()
+            .with_car_number(999999)
+            .unwrap()
+            .with_length_dm(4095)
+            .unwrap()
+            .with_side_indicator(Side::LEFT);
+
+        let decoded = AEITagData::new(&hex::encode(tag.raw())).unwrap();
+        assert!(decoded.checksums_valid());
+        assert_eq!(decoded.equipment_initial(), "UP  ");
+        assert_eq!(tag.equipment_initial(), "UP  ");
+        assert_eq!(decoded.car_number(), 999999);
+        assert_eq!(decoded.length_dm(), 4095);
+        assert_eq!(decoded.side_indicator(), Side::LEFT);
+        assert_eq!(decoded.equipment_group_code(), 19);
+        assert_eq!(decoded.number_axles(), 4);
+    }
+
+    #[test]
+    fn reencode_keeps_other_bits() {
+        let mut tag = AEITagData::new("9EA488C030426A179000528000140339").unwrap();
+        tag.with_equipment_initial("QNSL").unwrap();
+
+        assert_eq!(tag.equipment_initial_code(), 325659);
+        assert_eq!(tag.bits(81..=88).unwrap(), 0xA5);
+        assert_eq!(tag.bits(106..=109).unwrap(), 0x5);
+        assert_eq!(tag.bits(62..=63).unwrap(), 0b11);
+        assert_eq!(tag.bits(126..=127).unwrap(), 0b01);
+        assert!(tag.checksums_valid());
+
+        tag.with_equipment_initial("IOCC").unwrap();
+        assert_eq!(
+            hex::encode_upper(tag.raw()),
+            "9EA488C030426A179000528000140339"
+        );
+    }
+
+    #[test]
+    fn invalid_reencoding() {
+        let mut tag = AEITagData::new(TAG1).unwrap();
+
+        assert_eq!(
+            tag.with_equipment_initial("A BC").unwrap_err(),
+            EncodeTagError::InvalidEquipmentInitial(String::from("A BC"))
+        );
+        assert!(tag.with_equipment_initial("iocc").is_err());
+        assert!(tag.with_equipment_initial("").is_err());
+        assert!(tag.with_equipment_initial("ABCDE").is_err());
+        assert_eq!(
+            tag.with_car_number(1_000_000).unwrap_err(),
+            EncodeTagError::CarNumberOutOfRange(1_000_000)
+        );
+        assert_eq!(
+            tag.with_length_dm(4096).unwrap_err(),
+            EncodeTagError::LengthOutOfRange(4096)
+        );
+        assert_eq!(hex::encode_upper(tag.raw()), TAG1);
+    }
+
+    #[test]
+    fn bits_match_parsed_fields() {
+        let tag = AEITagData::new(TAG3).unwrap();
+
+        assert_eq!(tag.bits(0..=4).unwrap(), 19);
+        assert_eq!(tag.bits(26..=45).unwrap(), 85123);
+        assert_eq!(
+            tag.bits_multi(&[94..=96, 47..=55]).unwrap(),
+            u128::from(tag.length_dm())
+        );
+        assert_eq!(
+            tag.bits_multi(&[56..=59, 64..=64]).unwrap() + 1,
+            u128::from(tag.number_axles())
+        );
+        assert_eq!(tag.bits(0..=127).unwrap(), u128::from_be_bytes(*tag.raw()));
+    }
+
+    #[test]
+    fn invalid_bit_ranges() {
+        let tag = AEITagData::new(TAG1).unwrap();
+
+        assert_eq!(
+            tag.bits(120..=128).unwrap_err(),
+            BitRangeError::OutOfBounds(120..=128)
+        );
+        #[allow(clippy::reversed_empty_ranges)]
+        let empty = 10..=5;
+        assert_eq!(
+            tag.bits(empty.clone()).unwrap_err(),
+            BitRangeError::Empty(empty)
+        );
+        assert_eq!(
+            tag.bits_multi(&[0..=127, 0..=0]).unwrap_err(),
+            BitRangeError::TooWide(129)
+        );
     }
 }
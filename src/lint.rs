@@ -0,0 +1,584 @@
+//! Plausibility checks for mis-programmed tags
+//!
+//! A [`Linter`] runs a list of named [`Rule`]s on decoded tags and reports every [`Violation`]
+//! with its [`Severity`]. The thresholds used by the rules can be configured per equipment group
+//! with a [`LintConfig`], which can be loaded from TOML :
+//!
+//! ```toml
+//! [severity]
+//! reserved-bits = "error"
+//!
+//! [[groups]]
+//! group = 19
+//! min_axles = 4
+//! max_axles = 12
+//! min_length_dm = 100
+//! max_length_dm = 400
+//! ```
+//!
+//! ```rust
+//! use aei_tag_parser::{lint::{Linter, Severity}, AEITagData};
+//!
+//! let linter = Linter::default();
+//! let tag = AEITagData::new("9EA488C030426A179000000000000331").unwrap();
+//! assert!(linter.lint(&tag).iter().all(|v| v.severity < Severity::Error));
+//! ```
+
+use crate::AEITagData;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt::Display};
+
+/// Severity of a violation, ordered from the least to the most severe
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Severity::Info => write!(f, "info"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Rule broken by a tag
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Violation {
+    pub rule: String,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}[{}] : {}", self.severity, self.rule, self.message)
+    }
+}
+
+/// Thresholds applied to the tags of one equipment group. A missing bound is not checked
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GroupThresholds {
+    pub group: u8,
+    pub min_axles: Option<u8>,
+    pub max_axles: Option<u8>,
+    pub min_length_dm: Option<u16>,
+    pub max_length_dm: Option<u16>,
+    /// Shortest plausible length per axle, in decimeters
+    pub min_dm_per_axle: Option<u16>,
+    /// Longest plausible length per axle, in decimeters
+    pub max_dm_per_axle: Option<u16>,
+}
+
+/// Configuration of the linter
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LintConfig {
+    /// Thresholds per equipment group
+    pub groups: Vec<GroupThresholds>,
+    /// Severity overriding the default severity of a rule, keyed by rule name
+    pub severity: HashMap<String, Severity>,
+    /// Names of the rules which are not run
+    pub disabled: Vec<String>,
+}
+
+impl Default for LintConfig {
+    /// Thresholds for railcars and locomotives
+    fn default() -> Self {
+        let rolling_stock = GroupThresholds {
+            group: 0,
+            min_axles: Some(4),
+            max_axles: Some(12),
+            min_length_dm: Some(100),
+            max_length_dm: Some(400),
+            min_dm_per_axle: Some(15),
+            max_dm_per_axle: Some(100),
+        };
+
+        LintConfig {
+            groups: vec![
+                GroupThresholds {
+                    group: 19,
+                    ..rolling_stock.clone()
+                },
+                GroupThresholds {
+                    group: 5,
+                    ..rolling_stock
+                },
+            ],
+            severity: HashMap::new(),
+            disabled: Vec::new(),
+        }
+    }
+}
+
+impl LintConfig {
+    /// Parses a configuration written in TOML. Missing entries take their default value
+    pub fn from_toml(config: &str) -> Result<LintConfig, toml::de::Error> {
+        toml::from_str(config)
+    }
+
+    /// Thresholds of the given equipment group
+    pub fn thresholds(&self, group: u8) -> Option<&GroupThresholds> {
+        self.groups
+            .iter()
+            .find(|thresholds| thresholds.group == group)
+    }
+}
+
+/// Plausibility check run on every tag
+pub trait Rule: Send + Sync {
+    /// Name used to report, configure or disable the rule
+    fn name(&self) -> &'static str;
+    /// Severity of the violations when it isn't overridden by the configuration
+    fn default_severity(&self) -> Severity;
+    /// Returns a message for every problem found on the tag
+    fn check(&self, tag: &AEITagData, config: &LintConfig) -> Vec<String>;
+}
+
+/// Both checksums must match the tag data
+pub struct ChecksumRule;
+
+impl Rule for ChecksumRule {
+    fn name(&self) -> &'static str {
+        "checksum"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn check(&self, tag: &AEITagData, _config: &LintConfig) -> Vec<String> {
+        if tag.checksums_valid() {
+            Vec::new()
+        } else {
+            vec![String::from("the checksums don't match the tag data")]
+        }
+    }
+}
+
+/// A length of 0 means the length was never programmed
+pub struct ZeroLengthRule;
+
+impl Rule for ZeroLengthRule {
+    fn name(&self) -> &'static str {
+        "zero-length"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn check(&self, tag: &AEITagData, _config: &LintConfig) -> Vec<String> {
+        if tag.length_dm() == 0 {
+            vec![String::from("the length is 0")]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// The length must be within the thresholds of the equipment group
+pub struct LengthRangeRule;
+
+impl Rule for LengthRangeRule {
+    fn name(&self) -> &'static str {
+        "length-range"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn check(&self, tag: &AEITagData, config: &LintConfig) -> Vec<String> {
+        let thresholds = match config.thresholds(tag.equipment_group_code()) {
+            Some(thresholds) => thresholds,
+            None => return Vec::new(),
+        };
+        let length = tag.length_dm();
+        if length == 0 {
+            // Already reported by the zero-length rule
+            return Vec::new();
+        }
+
+        let mut messages = Vec::new();
+        if let Some(min) = thresholds.min_length_dm.filter(|min| length < *min) {
+            messages.push(format!(
+                "the length {} dm is shorter than {} dm for a {}",
+                length,
+                min,
+                tag.equipment_group()
+            ));
+        }
+        if let Some(max) = thresholds.max_length_dm.filter(|max| length > *max) {
+            messages.push(format!(
+                "the length {} dm is longer than {} dm for a {}",
+                length,
+                max,
+                tag.equipment_group()
+            ));
+        }
+        messages
+    }
+}
+
+/// The number of axles must be within the thresholds of the equipment group
+pub struct AxleRangeRule;
+
+impl Rule for AxleRangeRule {
+    fn name(&self) -> &'static str {
+        "axle-range"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn check(&self, tag: &AEITagData, config: &LintConfig) -> Vec<String> {
+        let thresholds = match config.thresholds(tag.equipment_group_code()) {
+            Some(thresholds) => thresholds,
+            None => return Vec::new(),
+        };
+        let axles = tag.number_axles();
+
+        let mut messages = Vec::new();
+        if let Some(min) = thresholds.min_axles.filter(|min| axles < *min) {
+            messages.push(format!(
+                "{} axle(s) is less than {} for a {}",
+                axles,
+                min,
+                tag.equipment_group()
+            ));
+        }
+        if let Some(max) = thresholds.max_axles.filter(|max| axles > *max) {
+            messages.push(format!(
+                "{} axles is more than {} for a {}",
+                axles,
+                max,
+                tag.equipment_group()
+            ));
+        }
+        messages
+    }
+}
+
+/// The length per axle must be plausible for the equipment group
+pub struct AxlesForLengthRule;
+
+impl Rule for AxlesForLengthRule {
+    fn name(&self) -> &'static str {
+        "axles-for-length"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn check(&self, tag: &AEITagData, config: &LintConfig) -> Vec<String> {
+        let thresholds = match config.thresholds(tag.equipment_group_code()) {
+            Some(thresholds) => thresholds,
+            None => return Vec::new(),
+        };
+        if tag.length_dm() == 0 {
+            return Vec::new();
+        }
+        let per_axle = tag.length_dm() / u16::from(tag.number_axles());
+
+        let implausible = thresholds.min_dm_per_axle.is_some_and(|min| per_axle < min)
+            || thresholds.max_dm_per_axle.is_some_and(|max| per_axle > max);
+        if implausible {
+            vec![format!(
+                "{} axles is implausible for a length of {} dm",
+                tag.number_axles(),
+                tag.length_dm()
+            )]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// The equipment group code must not be a reserved one
+pub struct ReservedGroupRule;
+
+impl Rule for ReservedGroupRule {
+    fn name(&self) -> &'static str {
+        "reserved-group"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn check(&self, tag: &AEITagData, _config: &LintConfig) -> Vec<String> {
+        if tag.equipment_group().starts_with("Reserved") {
+            vec![format!(
+                "the equipment group code {} is reserved",
+                tag.equipment_group_code()
+            )]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// The bits reserved for future use by the AAR (97-105) must be 0
+pub struct ReservedBitsRule;
+
+impl Rule for ReservedBitsRule {
+    fn name(&self) -> &'static str {
+        "reserved-bits"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn check(&self, tag: &AEITagData, _config: &LintConfig) -> Vec<String> {
+        match tag.bits(97..=105) {
+            Ok(0) => Vec::new(),
+            Ok(value) => vec![format!("the reserved bits 97-105 are set ({:#05X})", value)],
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+/// The equipment initial must not contain a blank followed by a letter
+pub struct MarkBlankRule;
+
+impl Rule for MarkBlankRule {
+    fn name(&self) -> &'static str {
+        "mark-blank"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn check(&self, tag: &AEITagData, _config: &LintConfig) -> Vec<String> {
+        let initial = tag.equipment_initial();
+        if initial.trim_end().contains(' ') {
+            vec![format!(
+                "the equipment initial {:?} has a blank in the middle",
+                initial
+            )]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// The owner category of the equipment initial must be expected for the equipment group,
+/// see [`crate::mark::OwnerClass::matches_group`]
+pub struct MarkClassRule;
+
+impl Rule for MarkClassRule {
+    fn name(&self) -> &'static str {
+        "mark-class"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn check(&self, tag: &AEITagData, _config: &LintConfig) -> Vec<String> {
+        if tag.owner_class_consistent() {
+            Vec::new()
+        } else {
+            vec![format!(
+                "the {} mark {} is unexpected on a {} tag",
+                tag.owner_class(),
+                tag.mark(),
+                tag.equipment_group()
+            )]
+        }
+    }
+}
+
+/// Runs the rules on the tags
+pub struct Linter {
+    rules: Vec<Box<dyn Rule>>,
+    config: LintConfig,
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Linter::new(LintConfig::default())
+    }
+}
+
+impl Linter {
+    /// Linter running every built-in rule
+    pub fn new(config: LintConfig) -> Linter {
+        Linter {
+            rules: vec![
+                Box::new(ChecksumRule),
+                Box::new(ZeroLengthRule),
+                Box::new(LengthRangeRule),
+                Box::new(AxleRangeRule),
+                Box::new(AxlesForLengthRule),
+                Box::new(ReservedGroupRule),
+                Box::new(ReservedBitsRule),
+                Box::new(MarkBlankRule),
+                Box::new(MarkClassRule),
+            ],
+            config,
+        }
+    }
+
+    /// Adds a custom rule
+    pub fn with_rule<R: Rule + 'static>(mut self, rule: R) -> Linter {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    /// Names of the rules run by the linter
+    pub fn rules(&self) -> Vec<&'static str> {
+        self.rules.iter().map(|rule| rule.name()).collect()
+    }
+
+    /// Every violation found on the tag
+    pub fn lint(&self, tag: &AEITagData) -> Vec<Violation> {
+        self.rules
+            .iter()
+            .filter(|rule| !self.config.disabled.iter().any(|name| name == rule.name()))
+            .flat_map(|rule| {
+                let severity = *self
+                    .config
+                    .severity
+                    .get(rule.name())
+                    .unwrap_or(&rule.default_severity());
+                rule.check(tag, &self.config)
+                    .into_iter()
+                    .map(move |message| Violation {
+                        rule: String::from(rule.name()),
+                        severity,
+                        message,
+                    })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// IOCC 3088 RIGHT Railcar(19) 35' 0" 4 axles
+    static TAG: &str = "9EA488C030426A179000000000000331";
+
+    fn rules_broken(tag: &AEITagData, linter: &Linter) -> Vec<String> {
+        linter.lint(tag).into_iter().map(|v| v.rule).collect()
+    }
+
+    #[test]
+    fn valid_tag() {
+        let tag = AEITagData::new(TAG).unwrap();
+
+        assert!(Linter::default().lint(&tag).is_empty());
+    }
+
+    #[test]
+    fn mis_programmed_tag() {
+        let mut tag = AEITagData::new(TAG).unwrap();
+        tag.with_length_dm(0).unwrap();
+
+        assert_eq!(rules_broken(&tag, &Linter::default()), vec!["zero-length"]);
+
+        tag.with_length_dm(600).unwrap();
+        assert_eq!(
+            rules_broken(&tag, &Linter::default()),
+            vec!["length-range", "axles-for-length"]
+        );
+    }
+
+    #[test]
+    fn one_axle_railcar() {
+        let mut tag = AEITagData::new(TAG).unwrap();
+        tag.set_bits_multi(&[56..=59, 64..=64], 0);
+        tag.reencode();
+
+        assert_eq!(tag.number_axles(), 1);
+        assert_eq!(
+            rules_broken(&tag, &Linter::default()),
+            vec!["axle-range", "axles-for-length"]
+        );
+    }
+
+    #[test]
+    fn raw_defects() {
+        let mut tag = AEITagData::new(TAG).unwrap();
+        tag.set_bits_multi(&[0..=4], 2);
+        tag.set_bits_multi(&[97..=105], 4);
+        let tag = AEITagData::from_raw(*tag.raw());
+
+        let violations = Linter::default().lint(&tag);
+        assert_eq!(
+            violations
+                .iter()
+                .map(|v| v.rule.as_str())
+                .collect::<Vec<_>>(),
+            vec!["checksum", "reserved-group", "reserved-bits"]
+        );
+        assert_eq!(violations[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn blank_in_mark() {
+        // Equipment initial "A BC"
+        let mut tag = AEITagData::new(TAG).unwrap();
+        tag.set_bits_multi(&[7..=25], 2 * 27 + 3);
+        tag.reencode();
+
+        assert_eq!(tag.equipment_initial(), "A BC");
+        assert_eq!(rules_broken(&tag, &Linter::default()), vec!["mark-blank"]);
+    }
+
+    #[test]
+    fn mark_class_rule() {
+        // The classification of the marks is tested in the mark module
+        let mut tag = AEITagData::new(TAG).unwrap();
+        tag.with_equipment_initial("TTNU").unwrap();
+
+        let violations = Linter::default().lint(&tag);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(
+            (violations[0].rule.as_str(), violations[0].severity),
+            ("mark-class", Severity::Warning)
+        );
+
+        let config = LintConfig::from_toml(r#"disabled = ["mark-class"]"#).unwrap();
+        assert!(Linter::new(config).lint(&tag).is_empty());
+    }
+
+    #[test]
+    fn configuration() {
+        let config = LintConfig::from_toml(
+            r#"
+            disabled = ["axles-for-length"]
+
+            [severity]
+            length-range = "error"
+
+            [[groups]]
+            group = 19
+            max_length_dm = 50
+            "#,
+        )
+        .unwrap();
+        let tag = AEITagData::new(TAG).unwrap();
+
+        let violations = Linter::new(config).lint(&tag);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(
+            violations[0].to_string(),
+            "error[length-range] : the length 106 dm is longer than 50 dm for a Railcar"
+        );
+    }
+}
@@ -1,97 +1,299 @@
-use aei_tag_parser::AEITagData;
+#[cfg(feature = "serial")]
+use aei_tag_parser::{framing::Framing, serial};
+use aei_tag_parser::{
+    length::Units,
+    net::{Endpoint, NetReader},
+    reader::transcore::MessageStream,
+    registry::MarkRegistry,
+    spare::{BitFieldDecoder, SpareFieldRegistry},
+    stream::{LineError, TagStream},
+    AEITagData,
+};
 use atty::Stream;
-use clap::{arg, command, value_parser, ArgMatches};
+use clap::{arg, command, value_parser, Arg, ArgMatches};
 use std::{
     fs::File,
     io::{self, BufRead},
+    iter,
+    ops::RangeInclusive,
     path::PathBuf,
     process,
+    time::Duration,
 };
 
+mod commands;
+
+/// Output format of the decoded tags
+#[derive(Clone, Copy)]
+enum OutputFormat {
+    Short,
+    Csv,
+    Json,
+}
+
+/// Decoding options given on the command line
+struct Decoder {
+    spare_fields: SpareFieldRegistry,
+    marks: Option<MarkRegistry>,
+}
+
+impl Decoder {
+    #[cfg(not(tarpaulin_include))]
+    fn from_matches(matches: &ArgMatches) -> Decoder {
+        let marks = matches.get_one::<PathBuf>("registry").map(|path| {
+            MarkRegistry::from_path(path).unwrap_or_else(|e| {
+                eprintln!(
+                    "Couldn't load registry : {} because {}",
+                    path.to_string_lossy(),
+                    e
+                );
+                process::exit(1);
+            })
+        });
+
+        Decoder {
+            spare_fields: spare_field_registry(matches),
+            marks,
+        }
+    }
+
+    /// Annotates the tag with the owner fields and the owner of its mark
+    fn annotate(&self, mut tag: AEITagData) -> AEITagData {
+        self.spare_fields.apply(&mut tag);
+        if let Some(marks) = &self.marks {
+            marks.apply(&mut tag);
+        }
+        tag
+    }
+}
+
 #[cfg(not(tarpaulin_include))]
 fn main() {
     let matches = cli();
-    // tags to parse
-    let mut tags: Vec<String> = Vec::new();
-
-    // If there is a file specified in the argument, add the contained tags in the list of tags to parse
-    if let Some(path) = matches.get_one::<PathBuf>("file") {
-        read_tags_from_file(path, &mut tags);
-    } else if matches.contains_id("stdin") {
-        // Read tags from stdin
-        read_tags_from_stdin(&mut tags);
+    match matches.subcommand() {
+        Some(("encode-roster", matches)) => process::exit(commands::encode_roster::run(matches)),
+        Some(("export", matches)) => process::exit(commands::export::run(matches)),
+        Some(("lint", matches)) => process::exit(commands::lint::run(matches)),
+        Some(("reconcile", matches)) => process::exit(commands::reconcile::run(matches)),
+        Some(("summary", matches)) => process::exit(commands::summary::run(matches)),
+        Some(("verify-consist", matches)) => process::exit(commands::verify_consist::run(matches)),
+        _ => (),
     }
 
-    // Extract the tags passed as argument when calling the program
-    read_tags_from_cli(&matches, &mut tags);
-    if matches.contains_id("csv") {
-        print_tags(&tags, true);
+    let tags = read_tags(&matches);
+    let decoder = Decoder::from_matches(&matches);
+    let format = if matches.contains_id("csv") {
+        OutputFormat::Csv
+    } else if matches.contains_id("json") {
+        OutputFormat::Json
     } else {
-        print_tags(&tags, false);
-    }
+        OutputFormat::Short
+    };
+    let units = matches
+        .get_one::<String>("units")
+        .and_then(|units| units.parse::<Units>().ok())
+        .unwrap_or_default();
+    print_tags(tags, &decoder, format, units);
 }
 
+/// Builds the spare field decoders from the `--spare-field MARK:NAME=RANGES` options
 #[cfg(not(tarpaulin_include))]
-fn read_tags_from_file(path: &PathBuf, out: &mut Vec<String>) {
-    let f = match File::open(path) {
-        Ok(f) => f,
-        Err(e) => {
-            eprintln!(
-                "Couldn't open file : {} because {}",
-                path.to_string_lossy(),
-                e.to_string()
-            );
-            process::exit(1);
+fn spare_field_registry(matches: &ArgMatches) -> SpareFieldRegistry {
+    let mut decoders: Vec<(String, BitFieldDecoder)> = Vec::new();
+    for spec in matches
+        .get_many::<String>("spare-field")
+        .unwrap_or_default()
+    {
+        let (initial, name, ranges) = match parse_spare_field(spec) {
+            Some(field) => field,
+            None => {
+                eprintln!(
+                    "Invalid spare field : {} (expected MARK:NAME=START-END[+START-END...])",
+                    spec
+                );
+                process::exit(1);
+            }
+        };
+
+        match decoders.iter_mut().find(|(i, _)| *i == initial) {
+            Some((_, decoder)) => *decoder = decoder.clone().field(&name, &ranges),
+            None => decoders.push((initial, BitFieldDecoder::new().field(&name, &ranges))),
         }
+    }
+
+    let mut registry = SpareFieldRegistry::new();
+    for (initial, decoder) in decoders {
+        registry.register(&initial, decoder);
+    }
+    registry
+}
+
+/// Parses a spare field specification such as `IOCC:fleet=81-88+90-93`. The ranges must be
+/// ordered bits of the tag, 0 to 127, making an unsigned value of at most 64 bits
+#[cfg(not(tarpaulin_include))]
+fn parse_spare_field(spec: &str) -> Option<(String, String, Vec<RangeInclusive<usize>>)> {
+    let (initial, field) = spec.split_once(':')?;
+    let (name, ranges) = field.split_once('=')?;
+    let ranges = ranges
+        .split('+')
+        .map(|range| match range.split_once('-') {
+            Some((start, end)) => Some(start.trim().parse().ok()?..=end.trim().parse().ok()?),
+            None => {
+                let bit = range.trim().parse().ok()?;
+                Some(bit..=bit)
+            }
+        })
+        .collect::<Option<Vec<RangeInclusive<usize>>>>()?;
+    if ranges
+        .iter()
+        .any(|range| range.start() > range.end() || *range.end() > 127)
+    {
+        return None;
+    }
+    let width: usize = ranges
+        .iter()
+        .map(|range| range.end() - range.start() + 1)
+        .sum();
+    if width > 64 {
+        return None;
+    }
+
+    Some((initial.to_uppercase(), String::from(name), ranges))
+}
+
+/// Decoded tags, streamed from the file or stdin then followed by the tags given as arguments
+/// in `matches`, numbered from 1
+#[cfg(not(tarpaulin_include))]
+fn read_tags(matches: &ArgMatches) -> Box<dyn Iterator<Item = Result<AEITagData, LineError>>> {
+    let input: Box<dyn Iterator<Item = Result<AEITagData, LineError>>> = match open_input(matches) {
+        Some((reader, name)) => stream_tags(reader, &name, is_transcore(matches)),
+        None => Box::new(iter::empty()),
     };
+    Box::new(input.chain(tag_args(matches)))
+}
 
-    let mut lines: Vec<String> = io::BufReader::new(f)
-        .lines()
-        .filter(|line| line.is_ok())
-        .map(|line| line.unwrap())
-        .collect();
+/// The input is made of the messages of a TransCore reader
+#[cfg(not(tarpaulin_include))]
+fn is_transcore(matches: &ArgMatches) -> bool {
+    matches
+        .get_one::<String>("input-format")
+        .is_some_and(|format| format == "transcore")
+}
 
-    out.append(&mut lines);
+/// Input selected in `matches`, the file, the network, the serial port or stdin, with its name
+#[cfg(not(tarpaulin_include))]
+fn open_input(matches: &ArgMatches) -> Option<(Box<dyn BufRead>, String)> {
+    if let Some(path) = matches.get_one::<PathBuf>("file") {
+        // If there is a file specified in the argument, stream the tags it contains
+        match File::open(path) {
+            Ok(f) => Some((
+                Box::new(io::BufReader::new(f)),
+                path.to_string_lossy().into_owned(),
+            )),
+            Err(e) => {
+                eprintln!(
+                    "Couldn't open file : {} because {}",
+                    path.to_string_lossy(),
+                    e
+                );
+                process::exit(1);
+            }
+        }
+    } else if let Some(endpoint) = matches
+        .get_one::<Endpoint>("listen")
+        .or_else(|| matches.get_one::<Endpoint>("connect"))
+    {
+        // Decode the lines sent by the readers as they arrive
+        let reader = NetReader::open_with(endpoint, Duration::from_secs(1), |address, e| {
+            eprintln!("{} : {}", address, e)
+        })
+        .unwrap_or_else(|e| {
+            eprintln!("Couldn't listen on {} because {}", endpoint, e);
+            process::exit(1);
+        });
+        Some((Box::new(reader), endpoint.to_string()))
+    } else if let Some(input) = serial_input(matches) {
+        Some(input)
+    } else if matches.contains_id("stdin") && atty::isnt(Stream::Stdin) {
+        Some((Box::new(io::stdin().lock()), String::from("stdin")))
+    } else {
+        None
+    }
 }
 
+/// Tags passed as argument when calling the program, numbered from 1
 #[cfg(not(tarpaulin_include))]
-fn read_tags_from_cli(matches: &ArgMatches, out: &mut Vec<String>) {
-    let mut vals: Vec<String> = matches
+fn tag_args(matches: &ArgMatches) -> impl Iterator<Item = Result<AEITagData, LineError>> {
+    let args: Vec<String> = matches
         .get_many::<String>("tags")
-        .map(|vals| vals.collect::<Vec<_>>())
         .unwrap_or_default()
-        .iter()
-        .map(|&s| String::from(s))
+        .cloned()
         .collect();
+    args.into_iter()
+        .enumerate()
+        .map(|(i, val)| AEITagData::new(&val).map_err(|e| LineError::Argument(i + 1, val, e)))
+}
 
-    out.append(&mut vals);
+/// Serial port given with `--serial`, its frames being read as lines
+#[cfg(all(feature = "serial", not(tarpaulin_include)))]
+fn serial_input(matches: &ArgMatches) -> Option<(Box<dyn BufRead>, String)> {
+    let path = matches.get_one::<String>("serial")?;
+    let baud_rate = *matches.get_one::<u32>("baud").unwrap_or(&9600);
+    let framing = matches
+        .get_one::<Framing>("framing")
+        .copied()
+        .unwrap_or_default();
+    let port = serial::open(path, baud_rate, framing).unwrap_or_else(|e| {
+        eprintln!("Couldn't open serial port : {} because {}", path, e);
+        process::exit(1);
+    });
+    Some((Box::new(port), path.clone()))
 }
 
+#[cfg(not(feature = "serial"))]
+fn serial_input(_matches: &ArgMatches) -> Option<(Box<dyn BufRead>, String)> {
+    None
+}
+
+/// Tags of the input, given one per line or as the messages of a TransCore reader
 #[cfg(not(tarpaulin_include))]
-fn read_tags_from_stdin(out: &mut Vec<String>) {
-    let stdin = io::stdin();
-    if atty::isnt(Stream::Stdin) {
-        for line in stdin.lock().lines() {
-            if let Ok(l) = line {
-                out.push(l);
-            } else if let Err(e) = line {
-                eprintln!("{}", e);
-            }
-        }
+fn stream_tags<R: BufRead + 'static>(
+    reader: R,
+    name: &str,
+    transcore: bool,
+) -> Box<dyn Iterator<Item = Result<AEITagData, LineError>>> {
+    if transcore {
+        Box::new(
+            MessageStream::new(reader, name)
+                .tag_reads()
+                .map(|read| read.map(|(_, read)| read.tag)),
+        )
+    } else {
+        Box::new(TagStream::new(reader).map(|read| read.map(|(_, tag)| tag)))
     }
 }
 
 #[cfg(not(tarpaulin_include))]
-fn print_tags(tags: &Vec<String>, csv: bool) {
-    for val in tags {
-        let tag = match AEITagData::new(&val) {
-            Ok(val) => {
-                if csv {
-                    val.to_csv()
-                } else {
-                    val.to_short_string()
+fn print_tags(
+    tags: impl Iterator<Item = Result<AEITagData, LineError>>,
+    decoder: &Decoder,
+    format: OutputFormat,
+    units: Units,
+) {
+    for read in tags {
+        let tag = match read {
+            Ok(tag) => {
+                let tag = decoder.annotate(tag);
+                match format {
+                    OutputFormat::Short => tag.to_short_string_in(units),
+                    OutputFormat::Csv => tag.to_csv_in(units),
+                    OutputFormat::Json => tag.to_json_in(units),
                 }
             }
+            Err(e @ LineError::Io(_, _)) => {
+                eprintln!("{}", e);
+                continue;
+            }
             Err(e) => e.to_string(),
         };
 
@@ -99,21 +301,78 @@ fn print_tags(tags: &Vec<String>, csv: bool) {
     }
 }
 
+/// Arguments selecting the tags to parse, shared by every command
+#[cfg(not(tarpaulin_include))]
+fn input_args() -> Vec<Arg<'static>> {
+    #[allow(unused_mut)]
+    let mut args = vec![
+        arg!(-f --file "Path to a file to read the tags from")
+            .takes_value(true)
+            .value_parser(value_parser!(PathBuf)),
+        arg!([tags] "One or multiple tags to parse")
+            .value_parser(value_parser!(String))
+            .multiple_values(true),
+        arg!(-s --stdin "Get the data from stdin"),
+        arg!(--listen <URL> "Receive the tags sent by the readers on tcp://ADDRESS:PORT or udp://ADDRESS:PORT")
+            .required(false)
+            .value_parser(Endpoint::listen)
+            .conflicts_with_all(&["file", "stdin"]),
+        arg!(--connect <URL> "Connect to the reader at tcp://HOST:PORT and receive its tags")
+            .required(false)
+            .value_parser(Endpoint::connect)
+            .conflicts_with_all(&["file", "stdin", "listen"]),
+        arg!(--"input-format" <FORMAT> "Format of the input : one tag per line or the messages of a TransCore reader")
+            .required(false)
+            .value_parser(["hex", "transcore"])
+            .default_value("hex"),
+    ];
+    #[cfg(feature = "serial")]
+    args.extend([
+        arg!(--serial <PATH> "Read the tags sent by a reader on the serial port PATH")
+            .required(false)
+            .value_parser(value_parser!(String))
+            .conflicts_with_all(&["file", "stdin", "listen", "connect"]),
+        arg!(--baud <N> "Speed of the serial port")
+            .required(false)
+            .value_parser(value_parser!(u32))
+            .default_value("9600"),
+        arg!(--framing <FRAMING> "Frames of the serial port : END or START,END bytes, e.g. lf, cr or stx,etx")
+            .required(false)
+            .value_parser(|s: &str| s.parse::<Framing>())
+            .default_value("lf"),
+    ]);
+    args
+}
+
 #[cfg(not(tarpaulin_include))]
 fn cli() -> clap::ArgMatches {
     command!()
+        .args(input_args())
+        .subcommand(commands::encode_roster::command())
+        .subcommand(commands::export::command())
+        .subcommand(commands::lint::command())
+        .subcommand(commands::reconcile::command())
+        .subcommand(commands::summary::command())
+        .subcommand(commands::verify_consist::command())
+        .arg(arg!(--csv "Print the data in CSV format"))
+        .arg(arg!(--json "Print the data in JSON format").conflicts_with("csv"))
+        .arg(
+            arg!(--units <UNITS> "Unit system used to print the lengths")
+                .required(false)
+                .value_parser(["metric", "imperial"])
+                .default_value("metric"),
+        )
         .arg(
-            arg!(-f --file "Path to a file to read the tags from")
-                .takes_value(true)
+            arg!(--registry <FILE> "CSV or TOML file listing the owners of the reporting marks")
+                .required(false)
                 .value_parser(value_parser!(PathBuf)),
         )
         .arg(
-            arg!([tags] "One or multiple tags to parse")
-                .value_parser(value_parser!(String))
-                .multiple_values(true),
+            arg!(--"spare-field" <FIELD> "Decode an owner field from the spare bits, e.g. IOCC:fleet=81-88+90-93")
+                .required(false)
+                .multiple_occurrences(true)
+                .value_parser(value_parser!(String)),
         )
-        .arg(arg!(-s --stdin "Get the data from stdin"))
-        .arg(arg!(--csv "Print the data in CSV format"))
         .arg_required_else_help(true)
         .get_matches()
 }
@@ -0,0 +1,163 @@
+//! Equipment initial (reporting mark) classification
+//!
+//! The AAR convention gives a meaning to the last letter of a reporting mark : marks ending with
+//! X belong to private owners, marks ending with U to containers and marks ending with Z to
+//! trailers. Every other mark belongs to a railroad.
+//!
+//! ```rust
+//! use aei_tag_parser::{mark::OwnerClass, AEITagData};
+//!
+//! let tag = AEITagData::new("9EA488C030426A179000000000000331").unwrap();
+//! assert_eq!(tag.mark().to_string(), "IOCC");
+//! assert_eq!(tag.mark().owner_class(), OwnerClass::Railroad);
+//! assert!(tag.owner_class_consistent());
+//! ```
+
+use crate::{AEITagData, EncodeTagError};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, str::FromStr};
+
+/// Owner category given by the last letter of the reporting mark
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OwnerClass {
+    /// Railroad owned equipment
+    Railroad,
+    /// Mark ending with X : private car owner
+    PrivateOwner,
+    /// Mark ending with U : container
+    Container,
+    /// Mark ending with Z : trailer
+    Trailer,
+}
+
+impl OwnerClass {
+    /// Checks that equipment of the given group is expected to carry a mark of this class.
+    /// Containers are expected on container tags, trailers on highway equipment tags (tractor,
+    /// straight truck, dolly, trailer and chassis) and the other classes on rail equipment tags.
+    /// Groups meaning other, experimental or reserved are never suspicious
+    pub fn matches_group(&self, group: u8) -> bool {
+        let container = group == 10;
+        let highway = matches!(group, 17 | 18 | 20 | 21 | 27);
+        let rail = matches!(group, 1 | 4 | 5 | 6 | 8 | 12 | 19 | 24 | 28);
+
+        match self {
+            OwnerClass::Container => !(highway || rail),
+            OwnerClass::Trailer => !(container || rail),
+            OwnerClass::Railroad | OwnerClass::PrivateOwner => !(container || highway),
+        }
+    }
+}
+
+impl Display for OwnerClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            OwnerClass::Railroad => write!(f, "Railroad"),
+            OwnerClass::PrivateOwner => write!(f, "Private owner"),
+            OwnerClass::Container => write!(f, "Container"),
+            OwnerClass::Trailer => write!(f, "Trailer"),
+        }
+    }
+}
+
+/// Equipment initial (reporting mark) of a tag
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct EquipmentInitial {
+    code: u32,
+    mark: String,
+}
+
+impl EquipmentInitial {
+    /// Equipment initial from its 19 bits code
+    pub fn from_code(code: u32) -> EquipmentInitial {
+        EquipmentInitial {
+            code,
+            mark: String::from(AEITagData::deserialize_equipement_initial(code).trim_end()),
+        }
+    }
+
+    /// Code stored on the tag
+    pub fn code(&self) -> u32 {
+        self.code
+    }
+
+    /// Mark without the trailing blanks
+    pub fn as_str(&self) -> &str {
+        &self.mark
+    }
+
+    /// Owner category of the mark
+    pub fn owner_class(&self) -> OwnerClass {
+        match self.mark.chars().last() {
+            Some('X') => OwnerClass::PrivateOwner,
+            Some('U') => OwnerClass::Container,
+            Some('Z') => OwnerClass::Trailer,
+            _ => OwnerClass::Railroad,
+        }
+    }
+}
+
+impl FromStr for EquipmentInitial {
+    type Err = EncodeTagError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        AEITagData::serialize_equipment_initial(s).map(EquipmentInitial::from_code)
+    }
+}
+
+impl Display for EquipmentInitial {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.mark)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn owner_classes() {
+        let class = |mark: &str| mark.parse::<EquipmentInitial>().unwrap().owner_class();
+
+        assert_eq!(class("IOCC"), OwnerClass::Railroad);
+        assert_eq!(class("UP"), OwnerClass::Railroad);
+        assert_eq!(class("GATX"), OwnerClass::PrivateOwner);
+        assert_eq!(class("TTNU"), OwnerClass::Container);
+        assert_eq!(class("JBHZ"), OwnerClass::Trailer);
+    }
+
+    #[test]
+    fn mark_parsing() {
+        let mark = "QNSL".parse::<EquipmentInitial>().unwrap();
+        assert_eq!(mark.code(), 325659);
+        assert_eq!(mark, EquipmentInitial::from_code(325659));
+
+        let mark = "UP".parse::<EquipmentInitial>().unwrap();
+        assert_eq!(mark.as_str(), "UP");
+        assert_eq!(mark.to_string(), "UP");
+
+        assert!("U P".parse::<EquipmentInitial>().is_err());
+    }
+
+    #[test]
+    fn group_cross_check() {
+        assert!(OwnerClass::Railroad.matches_group(19));
+        assert!(OwnerClass::PrivateOwner.matches_group(19));
+        assert!(!OwnerClass::Container.matches_group(19));
+        assert!(!OwnerClass::Trailer.matches_group(5));
+        assert!(OwnerClass::Container.matches_group(10));
+        assert!(!OwnerClass::Railroad.matches_group(10));
+        assert!(OwnerClass::Trailer.matches_group(21));
+        assert!(!OwnerClass::PrivateOwner.matches_group(27));
+        assert!(OwnerClass::Container.matches_group(31));
+    }
+
+    #[test]
+    fn container_mark_on_railcar() {
+        let mut tag = AEITagData::new("9EA488C030426A179000000000000331").unwrap();
+        tag.with_equipment_initial("TTNU").unwrap();
+
+        assert_eq!(tag.mark().owner_class(), OwnerClass::Container);
+        assert!(!tag.owner_class_consistent());
+    }
+}
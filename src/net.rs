@@ -0,0 +1,428 @@
+//! Network inputs
+//!
+//! The readers push their data over TCP, either as clients of the host or as servers it connects
+//! to, or broadcast it over UDP. A [`NetReader`] receives the lines sent by the readers in
+//! background threads and merges them into a single [`BufRead`], so that it can be decoded as a
+//! file with a [`TagStream`](crate::stream::TagStream). Only whole lines are merged, the lines of
+//! concurrent connections never being mixed. At most [`MAX_PENDING_LINES`] lines wait to be read,
+//! the readers being held back beyond, and the lines longer than
+//! [`MAX_LINE_LENGTH`](crate::stream::MAX_LINE_LENGTH) are dropped as connection errors.
+//!
+//! ```rust,no_run
+//! use aei_tag_parser::{net::{Endpoint, NetReader}, stream::TagStream};
+//!
+//! let endpoint: Endpoint = "tcp://0.0.0.0:5000".parse().unwrap();
+//! let reader = NetReader::open(&endpoint).unwrap();
+//! for read in TagStream::new(reader) {
+//!     match read {
+//!         Ok((_, tag)) => println!("{}", tag.to_short_string()),
+//!         Err(e) => eprintln!("{}", e),
+//!     }
+//! }
+//! ```
+
+use crate::stream::{LineError, LineReader};
+use std::{
+    fmt::Display,
+    io::{self, BufRead, BufReader, Read},
+    net::{SocketAddr, TcpListener, TcpStream, UdpSocket},
+    str::FromStr,
+    sync::{
+        mpsc::{self, Receiver, SyncSender},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+/// Delay before the first reconnection attempt, doubled after every failure
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+/// Longest delay between two reconnection attempts
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+/// Most lines received and not read yet
+pub const MAX_PENDING_LINES: usize = 4096;
+
+/// Where the data of the readers is received from
+#[derive(Clone, PartialEq, Debug)]
+pub enum Endpoint {
+    /// Accept the connections of the readers on the address, `tcp://ADDRESS:PORT`
+    TcpListen(String),
+    /// Connect to the reader at the address, `tcp://HOST:PORT`, reconnecting when the
+    /// connection is lost
+    TcpConnect(String),
+    /// Receive the datagrams sent to the address, `udp://ADDRESS:PORT`
+    UdpListen(String),
+}
+
+impl Endpoint {
+    /// Endpoint to listen on, `tcp://ADDRESS:PORT` or `udp://ADDRESS:PORT`
+    pub fn listen(url: &str) -> Result<Endpoint, String> {
+        match url.split_once("://") {
+            Some(("tcp", address)) => Ok(Endpoint::TcpListen(String::from(address))),
+            Some(("udp", address)) => Ok(Endpoint::UdpListen(String::from(address))),
+            _ => Err(format!(
+                "invalid address {}, expected tcp://ADDRESS:PORT or udp://ADDRESS:PORT",
+                url
+            )),
+        }
+    }
+
+    /// Endpoint to connect to, `tcp://HOST:PORT`
+    pub fn connect(url: &str) -> Result<Endpoint, String> {
+        match url.split_once("://") {
+            Some(("tcp", address)) => Ok(Endpoint::TcpConnect(String::from(address))),
+            _ => Err(format!("invalid address {}, expected tcp://HOST:PORT", url)),
+        }
+    }
+}
+
+impl FromStr for Endpoint {
+    type Err = String;
+
+    /// Parses a listening endpoint
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Endpoint::listen(s)
+    }
+}
+
+impl Display for Endpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Endpoint::TcpListen(address) | Endpoint::TcpConnect(address) => {
+                write!(f, "tcp://{}", address)
+            }
+            Endpoint::UdpListen(address) => write!(f, "udp://{}", address),
+        }
+    }
+}
+
+/// Callback told of the connection errors, with the address of the connection
+type ErrorHandler = Arc<dyn Fn(&str, &io::Error) + Send + Sync>;
+
+/// Lines received from the readers
+pub struct NetReader {
+    lines: Receiver<Vec<u8>>,
+    current: Vec<u8>,
+    position: usize,
+    local_addr: Option<SocketAddr>,
+}
+
+impl NetReader {
+    /// Starts receiving the data of the endpoint, ignoring the connection errors
+    pub fn open(endpoint: &Endpoint) -> io::Result<NetReader> {
+        NetReader::open_with(endpoint, RECONNECT_DELAY, |_, _| ())
+    }
+
+    /// Starts receiving the data of the endpoint. The connection errors are given to `on_error`
+    /// and a lost connection to a reader is retried after `reconnect_delay`, the delay doubling
+    /// after every failed attempt. The failed accepts of a listener are retried the same way.
+    /// Fails when the address to listen on can't be bound
+    pub fn open_with<F>(
+        endpoint: &Endpoint,
+        reconnect_delay: Duration,
+        on_error: F,
+    ) -> io::Result<NetReader>
+    where
+        F: Fn(&str, &io::Error) + Send + Sync + 'static,
+    {
+        let (sender, lines) = mpsc::sync_channel(MAX_PENDING_LINES);
+        let on_error: ErrorHandler = Arc::new(on_error);
+        let local_addr = match endpoint {
+            Endpoint::TcpListen(address) => {
+                let listener = TcpListener::bind(address)?;
+                let local_addr = listener.local_addr()?;
+                thread::spawn(move || accept(listener, reconnect_delay, sender, on_error));
+                Some(local_addr)
+            }
+            Endpoint::TcpConnect(address) => {
+                let address = address.clone();
+                thread::spawn(move || connect(&address, reconnect_delay, sender, on_error));
+                None
+            }
+            Endpoint::UdpListen(address) => {
+                let socket = UdpSocket::bind(address)?;
+                let local_addr = socket.local_addr()?;
+                thread::spawn(move || receive_datagrams(socket, sender, on_error));
+                Some(local_addr)
+            }
+        };
+
+        Ok(NetReader {
+            lines,
+            current: Vec::new(),
+            position: 0,
+            local_addr,
+        })
+    }
+
+    /// Address listened on
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+}
+
+impl Read for NetReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl BufRead for NetReader {
+    /// Waits for the next line when the current one is consumed. The end of the data is only
+    /// reached when every background thread stopped
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.position >= self.current.len() {
+            match self.lines.recv() {
+                Ok(line) => {
+                    self.current = line;
+                    self.position = 0;
+                }
+                Err(_) => return Ok(&[]),
+            }
+        }
+        Ok(&self.current[self.position..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.position = (self.position + amt).min(self.current.len());
+    }
+}
+
+/// Sends the lines of the stream until it is closed, the lines too long being given to
+/// `on_error`. Returns false when the reader is dropped
+fn forward_lines<R: Read>(
+    stream: R,
+    sender: &SyncSender<Vec<u8>>,
+    address: &str,
+    on_error: &ErrorHandler,
+) -> io::Result<bool> {
+    let mut lines = LineReader::new(BufReader::new(stream));
+    loop {
+        let mut line = match lines.next_line() {
+            None => return Ok(true),
+            Some(Ok((_, line))) => line.to_vec(),
+            Some(Err(LineError::Io(_, e))) if e.kind() == io::ErrorKind::InvalidData => {
+                on_error(address, &e);
+                continue;
+            }
+            Some(Err(LineError::Io(_, e))) => return Err(e),
+            Some(Err(e)) => return Err(io::Error::new(io::ErrorKind::InvalidData, e.to_string())),
+        };
+        if !line.ends_with(b"\n") {
+            line.push(b'\n');
+        }
+        if sender.send(line).is_err() {
+            return Ok(false);
+        }
+    }
+}
+
+fn accept(
+    listener: TcpListener,
+    retry_delay: Duration,
+    sender: SyncSender<Vec<u8>>,
+    on_error: ErrorHandler,
+) {
+    let mut delay = retry_delay;
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                delay = retry_delay;
+                let sender = sender.clone();
+                let on_error = on_error.clone();
+                thread::spawn(move || {
+                    let peer = stream
+                        .peer_addr()
+                        .map(|addr| addr.to_string())
+                        .unwrap_or_default();
+                    if let Err(e) = forward_lines(stream, &sender, &peer, &on_error) {
+                        on_error(&peer, &e);
+                    }
+                });
+            }
+            Err(e) => {
+                on_error(
+                    &listener
+                        .local_addr()
+                        .map(|a| a.to_string())
+                        .unwrap_or_default(),
+                    &e,
+                );
+                // Persistent failures, such as running out of file descriptors, aren't retried
+                // in a tight loop
+                thread::sleep(delay);
+                delay = (delay * 2).min(MAX_RECONNECT_DELAY.max(retry_delay));
+            }
+        }
+    }
+}
+
+fn connect(
+    address: &str,
+    reconnect_delay: Duration,
+    sender: SyncSender<Vec<u8>>,
+    on_error: ErrorHandler,
+) {
+    let mut delay = reconnect_delay;
+    loop {
+        match TcpStream::connect(address) {
+            Ok(stream) => {
+                delay = reconnect_delay;
+                match forward_lines(stream, &sender, address, &on_error) {
+                    Ok(true) => on_error(
+                        address,
+                        &io::Error::new(io::ErrorKind::ConnectionAborted, "connection closed"),
+                    ),
+                    Ok(false) => return,
+                    Err(e) => on_error(address, &e),
+                }
+            }
+            Err(e) => {
+                on_error(address, &e);
+                thread::sleep(delay);
+                delay = (delay * 2).min(MAX_RECONNECT_DELAY.max(reconnect_delay));
+                continue;
+            }
+        }
+        thread::sleep(delay);
+    }
+}
+
+fn receive_datagrams(socket: UdpSocket, sender: SyncSender<Vec<u8>>, on_error: ErrorHandler) {
+    let mut buf = [0u8; 65536];
+    loop {
+        match socket.recv_from(&mut buf) {
+            // A datagram holds one or several lines
+            Ok((n, peer)) => {
+                match forward_lines(&buf[..n], &sender, &peer.to_string(), &on_error) {
+                    Ok(true) => (),
+                    _ => return,
+                }
+            }
+            Err(e) => on_error(
+                &socket
+                    .local_addr()
+                    .map(|a| a.to_string())
+                    .unwrap_or_default(),
+                &e,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::{TagStream, MAX_LINE_LENGTH};
+    use std::io::Write;
+
+    static TAG1: &str = "2F3E06C007DB1E139000000000000331";
+    static TAG2: &str = "9EA488C030426A179000000000000331";
+
+    fn car_numbers(reader: NetReader, count: usize) -> Vec<u32> {
+        let mut numbers: Vec<u32> = TagStream::new(reader)
+            .take(count)
+            .map(|read| read.unwrap().1.car_number())
+            .collect();
+        numbers.sort();
+        numbers
+    }
+
+    #[test]
+    fn endpoints() {
+        assert_eq!(
+            "tcp://0.0.0.0:5000".parse::<Endpoint>(),
+            Ok(Endpoint::TcpListen(String::from("0.0.0.0:5000")))
+        );
+        assert_eq!(
+            Endpoint::listen("udp://0.0.0.0:5000"),
+            Ok(Endpoint::UdpListen(String::from("0.0.0.0:5000")))
+        );
+        assert_eq!(
+            Endpoint::connect("tcp://reader:5000").unwrap().to_string(),
+            "tcp://reader:5000"
+        );
+        assert!(Endpoint::connect("udp://reader:5000").is_err());
+        assert!(Endpoint::listen("0.0.0.0:5000").is_err());
+    }
+
+    #[test]
+    fn tcp_listen() {
+        let endpoint = Endpoint::listen("tcp://127.0.0.1:0").unwrap();
+        let reader = NetReader::open(&endpoint).unwrap();
+        let addr = reader.local_addr().unwrap();
+
+        // Two readers, the first one sending its line in two parts
+        let mut first = TcpStream::connect(addr).unwrap();
+        let mut second = TcpStream::connect(addr).unwrap();
+        first.write_all(&TAG1.as_bytes()[..10]).unwrap();
+        second.write_all(format!("{}\n", TAG2).as_bytes()).unwrap();
+        first.write_all(&TAG1.as_bytes()[10..]).unwrap();
+        drop(first);
+
+        assert_eq!(car_numbers(reader, 2), vec![502, 3088]);
+    }
+
+    #[test]
+    fn long_lines() {
+        let endpoint = Endpoint::listen("tcp://127.0.0.1:0").unwrap();
+        let (errors, errors_received) = mpsc::channel();
+        let reader = NetReader::open_with(&endpoint, RECONNECT_DELAY, move |_, e| {
+            let _ = errors.send(e.kind());
+        })
+        .unwrap();
+
+        // The line without a break is dropped, not the connection
+        let mut stream = TcpStream::connect(reader.local_addr().unwrap()).unwrap();
+        stream
+            .write_all(format!("{}\n{}\n", "F".repeat(MAX_LINE_LENGTH * 4), TAG1).as_bytes())
+            .unwrap();
+
+        assert_eq!(car_numbers(reader, 1), vec![502]);
+        assert_eq!(errors_received.recv().unwrap(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn tcp_reconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = Endpoint::TcpConnect(listener.local_addr().unwrap().to_string());
+        let server = thread::spawn(move || {
+            // The connection is closed after each tag
+            for tag in [TAG1, TAG2] {
+                let (mut stream, _) = listener.accept().unwrap();
+                stream.write_all(format!("{}\n", tag).as_bytes()).unwrap();
+            }
+        });
+
+        let (errors, errors_received) = mpsc::channel();
+        let reader = NetReader::open_with(&endpoint, Duration::from_millis(10), move |_, e| {
+            let _ = errors.send(e.kind());
+        })
+        .unwrap();
+
+        assert_eq!(car_numbers(reader, 2), vec![502, 3088]);
+        server.join().unwrap();
+        assert_eq!(
+            errors_received.recv().unwrap(),
+            io::ErrorKind::ConnectionAborted
+        );
+    }
+
+    #[test]
+    fn udp_listen() {
+        let endpoint = Endpoint::listen("udp://127.0.0.1:0").unwrap();
+        let reader = NetReader::open(&endpoint).unwrap();
+        let addr = reader.local_addr().unwrap();
+
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .send_to(format!("{}\r\n{}", TAG1, TAG2).as_bytes(), addr)
+            .unwrap();
+
+        assert_eq!(car_numbers(reader, 2), vec![502, 3088]);
+    }
+}
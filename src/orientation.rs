@@ -0,0 +1,446 @@
+//! Direction of travel of the trains and orientation of their vehicles
+//!
+//! The tags of a vehicle are mounted diagonally, the right tag near the A end and the left tag
+//! near the B end, the left side being the left of an observer standing at the B end and facing
+//! the A end. A tag read by an antenna on the side of the track matching its side indicator, as
+//! seen in the direction of travel, is thus on a vehicle moving A end first.
+//!
+//! The [`SiteConfig`] gives the side of the track of each antenna, as seen from a train moving in
+//! the reference direction of the site, and optionally its position along the track. The
+//! direction of the train is a vote of :
+//! - the order in which the antennas at different positions first read each tag
+//! - the order in which the two tags of each vehicle are read, the tag near the leading end
+//!   passing first, combined with the sides of the antennas reading them
+//!
+//! Once the direction is known, the orientation of each vehicle follows from the sides of the
+//! antennas which read its tags. Locomotives are taken as having their short hood at the A end.
+//!
+//! The site configuration is a TOML file listing the antennas :
+//! ```toml
+//! [[antennas]]
+//! antenna = 1
+//! side = "left"
+//! position_m = 0.0
+//!
+//! [[antennas]]
+//! antenna = 2
+//! side = "right"
+//! ```
+
+use crate::{
+    consist::{Consist, Vehicle},
+    read::TagRead,
+    Side,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, error, fmt::Display, fs, io, path::Path};
+
+/// Equipment group code of the locomotives
+pub const LOCOMOTIVE_GROUP: u8 = 5;
+
+#[derive(Debug)]
+pub enum SiteConfigError {
+    Io(io::Error),
+    Toml(toml::de::Error),
+}
+
+impl From<io::Error> for SiteConfigError {
+    fn from(err: io::Error) -> Self {
+        SiteConfigError::Io(err)
+    }
+}
+
+impl From<toml::de::Error> for SiteConfigError {
+    fn from(err: toml::de::Error) -> Self {
+        SiteConfigError::Toml(err)
+    }
+}
+
+impl Display for SiteConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SiteConfigError::Io(e) => write!(f, "the site configuration couldn't be read : {}", e),
+            SiteConfigError::Toml(e) => {
+                write!(f, "the site configuration isn't valid TOML : {}", e)
+            }
+        }
+    }
+}
+
+impl error::Error for SiteConfigError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            SiteConfigError::Io(e) => Some(e),
+            SiteConfigError::Toml(e) => Some(e),
+        }
+    }
+}
+
+/// Side of the track, as seen from a train moving in the reference direction of the site
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrackSide {
+    Left,
+    Right,
+}
+
+impl TrackSide {
+    /// Side of the track as seen in the direction of travel
+    pub(crate) fn seen_in(self, direction: Direction) -> Side {
+        match (self, direction) {
+            (TrackSide::Left, Direction::Reference) | (TrackSide::Right, Direction::Reverse) => {
+                Side::LEFT
+            }
+            _ => Side::RIGHT,
+        }
+    }
+}
+
+/// Antenna of a reader
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct AntennaConfig {
+    pub antenna: u8,
+    pub side: TrackSide,
+    /// Position along the track, increasing in the reference direction
+    #[serde(default)]
+    pub position_m: Option<f64>,
+}
+
+/// Antennas of a site
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct SiteConfig {
+    #[serde(default)]
+    pub antennas: Vec<AntennaConfig>,
+}
+
+impl SiteConfig {
+    /// Loads a TOML site configuration file
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<SiteConfig, SiteConfigError> {
+        SiteConfig::from_toml(&fs::read_to_string(path)?)
+    }
+
+    /// Loads the `antennas` array of a TOML document
+    pub fn from_toml(data: &str) -> Result<SiteConfig, SiteConfigError> {
+        Ok(toml::from_str(data)?)
+    }
+
+    /// Configuration of the antenna
+    pub fn antenna(&self, antenna: Option<u8>) -> Option<&AntennaConfig> {
+        let antenna = antenna?;
+        self.antennas.iter().find(|a| a.antenna == antenna)
+    }
+}
+
+/// Direction of travel of a train
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// Reference direction of the site, towards the increasing antenna positions
+    Reference,
+    Reverse,
+}
+
+impl Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Direction::Reference => write!(f, "Reference"),
+            Direction::Reverse => write!(f, "Reverse"),
+        }
+    }
+}
+
+/// End of a vehicle leading in the direction of travel
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Orientation {
+    AEndLeading,
+    BEndLeading,
+}
+
+impl Display for Orientation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Orientation::AEndLeading => write!(f, "A end leading"),
+            Orientation::BEndLeading => write!(f, "B end leading"),
+        }
+    }
+}
+
+/// Hood of a locomotive leading in the direction of travel
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Hood {
+    Short,
+    Long,
+}
+
+impl Display for Hood {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Hood::Short => write!(f, "Short"),
+            Hood::Long => write!(f, "Long"),
+        }
+    }
+}
+
+impl Hood {
+    /// Hood forward of a locomotive of the given orientation
+    pub fn forward(orientation: Orientation) -> Hood {
+        match orientation {
+            Orientation::AEndLeading => Hood::Short,
+            Orientation::BEndLeading => Hood::Long,
+        }
+    }
+}
+
+/// Sum of the votes, positive for the first choice and negative for the second one
+fn majority<T>(votes: i32, first: T, second: T) -> Option<T> {
+    match votes {
+        v if v > 0 => Some(first),
+        v if v < 0 => Some(second),
+        _ => None,
+    }
+}
+
+/// Orientation of a vehicle given by the order in which its two tags were read
+fn read_order(vehicle: &Vehicle) -> Option<Orientation> {
+    let (left, right) = (vehicle.left.as_ref()?, vehicle.right.as_ref()?);
+    match right.timestamp.cmp(&left.timestamp) {
+        std::cmp::Ordering::Less => Some(Orientation::AEndLeading),
+        std::cmp::Ordering::Greater => Some(Orientation::BEndLeading),
+        std::cmp::Ordering::Equal => None,
+    }
+}
+
+/// Votes for the reference direction given by the antenna positions
+fn position_votes(reads: &[TagRead], site: &SiteConfig) -> i32 {
+    // First read of each tag by each positioned antenna
+    let mut first_reads: HashMap<&[u8; 16], Vec<(DateTime<Utc>, f64)>> = HashMap::new();
+    for read in reads {
+        if let Some(position) = site.antenna(read.antenna).and_then(|a| a.position_m) {
+            let tag_reads = first_reads.entry(read.tag.raw()).or_default();
+            match tag_reads.iter_mut().find(|(_, p)| *p == position) {
+                Some((timestamp, _)) => *timestamp = (*timestamp).min(read.timestamp),
+                None => tag_reads.push((read.timestamp, position)),
+            }
+        }
+    }
+
+    first_reads
+        .values()
+        .filter_map(|tag_reads| {
+            let first = tag_reads.iter().min_by_key(|(t, _)| *t)?;
+            let last = tag_reads.iter().max_by_key(|(t, _)| *t)?;
+            if first.0 == last.0 || first.1 == last.1 {
+                None
+            } else if first.1 < last.1 {
+                Some(1)
+            } else {
+                Some(-1)
+            }
+        })
+        .sum()
+}
+
+/// Votes for the reference direction given by the vehicles whose two tags were read
+fn read_order_votes(consist: &Consist, site: &SiteConfig) -> i32 {
+    let mut votes = 0;
+    for vehicle in &consist.vehicles {
+        let orientation = match read_order(vehicle) {
+            Some(orientation) => orientation,
+            None => continue,
+        };
+        for read in vehicle.reads() {
+            if let Some(antenna) = site.antenna(read.antenna) {
+                let tag_side = read.tag.side_indicator();
+                // Side of the antenna as seen in the direction of travel
+                let seen = match orientation {
+                    Orientation::AEndLeading => tag_side,
+                    Orientation::BEndLeading => match tag_side {
+                        Side::LEFT => Side::RIGHT,
+                        Side::RIGHT => Side::LEFT,
+                    },
+                };
+                votes += if antenna.side.seen_in(Direction::Reference) == seen {
+                    1
+                } else {
+                    -1
+                };
+            }
+        }
+    }
+    votes
+}
+
+/// Orientation of a vehicle moving in the given direction
+fn vehicle_orientation(
+    vehicle: &Vehicle,
+    direction: Option<Direction>,
+    site: &SiteConfig,
+) -> Option<Orientation> {
+    let votes: i32 = match direction {
+        Some(direction) => vehicle
+            .reads()
+            .filter_map(|read| {
+                let antenna = site.antenna(read.antenna)?;
+                Some(
+                    if antenna.side.seen_in(direction) == read.tag.side_indicator() {
+                        1
+                    } else {
+                        -1
+                    },
+                )
+            })
+            .sum(),
+        None => 0,
+    };
+    majority(votes, Orientation::AEndLeading, Orientation::BEndLeading)
+        .or_else(|| read_order(vehicle))
+}
+
+/// Infers the direction of the train and the orientation of its vehicles from the reads of its
+/// passage, setting them on the consist. Returns the direction, if it could be inferred
+pub fn orient(consist: &mut Consist, reads: &[TagRead], site: &SiteConfig) -> Option<Direction> {
+    let votes = position_votes(reads, site) + read_order_votes(consist, site);
+    let direction = majority(votes, Direction::Reference, Direction::Reverse);
+
+    consist.direction = direction;
+    for i in 0..consist.vehicles.len() {
+        let orientation = vehicle_orientation(&consist.vehicles[i], direction, site);
+        consist.vehicles[i].orientation = orientation;
+    }
+    direction
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{consist::ConsistBuilder, AEITagData};
+    use chrono::{Duration, TimeZone};
+
+    /// QNSL 502 right and left
+    static TAG1: &str = "2F3E06C007DB1E139000000000000331";
+    static TAG1_LEFT: &str = "2F3E06C007D91E1B9000000000000331";
+    /// IOCC 3088 right and left
+    static TAG2: &str = "9EA488C030426A179000000000000331";
+    static TAG2_LEFT: &str = "9EA488C030406A1F9000000000000331";
+    /// IOCC 85123 left
+    static TAG3: &str = "9EA488C5320CC01B9000000000000331";
+
+    fn read(tag: &str, antenna: u8, seconds: i64) -> TagRead {
+        let start = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        let mut read = TagRead::new(
+            AEITagData::new(tag).unwrap(),
+            start + Duration::seconds(seconds),
+            "R1",
+        );
+        read.antenna = Some(antenna);
+        read
+    }
+
+    fn consist(reads: &[TagRead]) -> Consist {
+        let mut builder = ConsistBuilder::new();
+        for read in reads {
+            builder.push(read.clone());
+        }
+        builder.build()
+    }
+
+    #[test]
+    fn antennas_on_both_sides() {
+        let site = SiteConfig::from_toml(
+            "[[antennas]]\nantenna = 1\nside = \"left\"\n\n[[antennas]]\nantenna = 2\nside = \"right\"\n",
+        )
+        .unwrap();
+        let reads = vec![
+            // Locomotive running short hood forward, its right tag passing first
+            read(TAG1, 2, 0),
+            read(TAG1_LEFT, 1, 1),
+            read(TAG2, 2, 5),
+            read(TAG2_LEFT, 1, 6),
+            // A car turned, its left tag facing the right side antenna
+            read(TAG3, 2, 10),
+        ];
+        let mut consist = consist(&reads);
+
+        assert_eq!(
+            orient(&mut consist, &reads, &site),
+            Some(Direction::Reference)
+        );
+        assert_eq!(consist.direction, Some(Direction::Reference));
+        let orientations: Vec<_> = consist.vehicles.iter().map(|v| v.orientation).collect();
+        assert_eq!(
+            orientations,
+            vec![
+                Some(Orientation::AEndLeading),
+                Some(Orientation::AEndLeading),
+                Some(Orientation::BEndLeading)
+            ]
+        );
+        assert_eq!(consist.vehicles[0].hood_forward(), Some(Hood::Short));
+        assert_eq!(consist.vehicles[1].hood_forward(), None);
+    }
+
+    #[test]
+    fn antenna_positions() {
+        let site = SiteConfig {
+            antennas: vec![
+                AntennaConfig {
+                    antenna: 1,
+                    side: TrackSide::Left,
+                    position_m: Some(0.0),
+                },
+                AntennaConfig {
+                    antenna: 3,
+                    side: TrackSide::Left,
+                    position_m: Some(20.0),
+                },
+            ],
+        };
+        // The tags are read by the antenna at 20 m first
+        let reads = vec![
+            read(TAG1, 3, 0),
+            read(TAG1, 1, 1),
+            read(TAG2, 3, 5),
+            read(TAG2, 1, 6),
+        ];
+        let mut consist = consist(&reads);
+
+        assert_eq!(
+            orient(&mut consist, &reads, &site),
+            Some(Direction::Reverse)
+        );
+        // The right tags face the left side antennas, on the right of the train
+        assert_eq!(
+            consist.vehicles[0].orientation,
+            Some(Orientation::AEndLeading)
+        );
+        assert_eq!(consist.vehicles[0].hood_forward(), Some(Hood::Short));
+        assert_eq!(
+            consist.vehicles[1].orientation,
+            Some(Orientation::AEndLeading)
+        );
+    }
+
+    #[test]
+    fn unknown_direction() {
+        // Without antenna configuration, only the read order of the two tags is known
+        let reads = vec![read(TAG1_LEFT, 1, 0), read(TAG1, 2, 1), read(TAG3, 1, 5)];
+        let mut consist = consist(&reads);
+
+        assert_eq!(orient(&mut consist, &reads, &SiteConfig::default()), None);
+        assert_eq!(
+            consist.vehicles[0].orientation,
+            Some(Orientation::BEndLeading)
+        );
+        assert_eq!(consist.vehicles[0].hood_forward(), Some(Hood::Long));
+        assert_eq!(consist.vehicles[1].orientation, None);
+    }
+
+    #[test]
+    fn invalid_config() {
+        let err = SiteConfig::from_toml("[[antennas]]\nantenna = 1\nside = \"up\"\n").unwrap_err();
+        assert!(matches!(err, SiteConfigError::Toml(_)));
+    }
+}
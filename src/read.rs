@@ -0,0 +1,318 @@
+//! Tag reads
+//!
+//! A [`TagRead`] is a decoded tag together with the context of its reading : when and by which
+//! reader it was read, on which antenna, with which signal strength and how many times the reader
+//! saw it. Reads are ordered by timestamp.
+//!
+//! They are exchanged as CSV, with the tag written in hexadecimal, or as JSON :
+//! ```text
+//! timestamp;reader;antenna;rssi;read_count;tag
+//! 2024-05-01T12:00:00Z;SEPT-ILES;1;-52.5;3;9EA488C030426A179000000000000331
+//! ```
+//!
+//! ```rust
+//! use aei_tag_parser::read::TagRead;
+//!
+//! let reads = TagRead::from_csv(
+//!     "timestamp;reader;antenna;rssi;read_count;tag\n\
+//!      2024-05-01T12:00:01Z;SEPT-ILES;2;;1;2F3E06C007DB1E139000000000000331\n\
+//!      2024-05-01T12:00:00Z;SEPT-ILES;1;-52.5;3;9EA488C030426A179000000000000331\n",
+//! )
+//! .unwrap();
+//! let first = reads.iter().min().unwrap();
+//! assert_eq!(first.tag.car_number(), 3088);
+//! assert_eq!(first.antenna, Some(1));
+//! ```
+
+use crate::{csv_input, AEITagData, NewTagError};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, error, fmt::Display, fs, io, path::Path};
+
+/// Header of the CSV representation of the reads
+pub const CSV_HEADER: &str = "timestamp;reader;antenna;rssi;read_count;tag";
+
+#[derive(Debug)]
+pub enum TagReadError {
+    Io(io::Error),
+    Csv(csv::Error),
+    Json(serde_json::Error),
+    /// The tag of a read couldn't be decoded
+    Tag(String, NewTagError),
+}
+
+impl From<io::Error> for TagReadError {
+    fn from(err: io::Error) -> Self {
+        TagReadError::Io(err)
+    }
+}
+
+impl From<csv::Error> for TagReadError {
+    fn from(err: csv::Error) -> Self {
+        TagReadError::Csv(err)
+    }
+}
+
+impl From<serde_json::Error> for TagReadError {
+    fn from(err: serde_json::Error) -> Self {
+        TagReadError::Json(err)
+    }
+}
+
+impl Display for TagReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TagReadError::Io(e) => write!(f, "the reads couldn't be read : {}", e),
+            TagReadError::Csv(e) => write!(f, "the reads aren't valid CSV : {}", e),
+            TagReadError::Json(e) => write!(f, "the read isn't valid JSON : {}", e),
+            TagReadError::Tag(tag, e) => write!(f, "{} : {}", tag, e),
+        }
+    }
+}
+
+impl error::Error for TagReadError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            TagReadError::Io(e) => Some(e),
+            TagReadError::Csv(e) => Some(e),
+            TagReadError::Json(e) => Some(e),
+            TagReadError::Tag(_, e) => Some(e),
+        }
+    }
+}
+
+/// Tag read by a reader
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TagRead {
+    /// Time of the read
+    pub timestamp: DateTime<Utc>,
+    /// Identifier of the site or of the reader
+    pub reader: String,
+    /// Antenna which read the tag, when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub antenna: Option<u8>,
+    /// Received signal strength, in dBm
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rssi: Option<f64>,
+    /// Number of times the reader saw the tag during the read
+    #[serde(default = "default_read_count")]
+    pub read_count: u32,
+    pub tag: AEITagData,
+}
+
+fn default_read_count() -> u32 {
+    1
+}
+
+/// Line of the CSV representation, the tag being written in hexadecimal
+#[derive(Serialize, Deserialize)]
+struct TagReadRecord {
+    timestamp: DateTime<Utc>,
+    reader: String,
+    #[serde(default)]
+    antenna: Option<u8>,
+    #[serde(default)]
+    rssi: Option<f64>,
+    #[serde(default)]
+    read_count: Option<u32>,
+    tag: String,
+}
+
+impl TagRead {
+    /// Single read of the tag, without antenna nor signal strength
+    pub fn new(tag: AEITagData, timestamp: DateTime<Utc>, reader: &str) -> TagRead {
+        TagRead {
+            timestamp,
+            reader: String::from(reader),
+            antenna: None,
+            rssi: None,
+            read_count: 1,
+            tag,
+        }
+    }
+
+    /// Loads the reads of a CSV file
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Vec<TagRead>, TagReadError> {
+        TagRead::from_csv(&fs::read_to_string(path)?)
+    }
+
+    /// Parses CSV data with a header line. Only the `timestamp`, `reader` and `tag` columns are
+    /// required, the read count defaulting to 1
+    pub fn from_csv(data: &str) -> Result<Vec<TagRead>, TagReadError> {
+        crate::csv_input::records::<TagReadRecord>(data)?
+            .into_iter()
+            .map(|record| {
+                let tag = AEITagData::new(&record.tag)
+                    .map_err(|e| TagReadError::Tag(record.tag.clone(), e))?;
+                Ok(TagRead {
+                    timestamp: record.timestamp,
+                    reader: record.reader,
+                    antenna: record.antenna,
+                    rssi: record.rssi,
+                    read_count: record.read_count.unwrap_or(1),
+                    tag,
+                })
+            })
+            .collect()
+    }
+
+    /// Parses a read serialized in JSON
+    pub fn from_json(data: &str) -> Result<TagRead, TagReadError> {
+        Ok(serde_json::from_str(data)?)
+    }
+
+    /// Returns a line in CSV format, with the columns of [`CSV_HEADER`]
+    pub fn to_csv(&self) -> String {
+        format!(
+            "{};{};{};{};{};{}",
+            self.timestamp.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            csv_input::quote(&self.reader),
+            self.antenna.map(|a| a.to_string()).unwrap_or_default(),
+            self.rssi.map(|r| r.to_string()).unwrap_or_default(),
+            self.read_count,
+            hex::encode_upper(self.tag.raw())
+        )
+    }
+
+    /// Returns the read serialized in JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("a read can always be serialized")
+    }
+}
+
+impl Ord for TagRead {
+    /// Reads are ordered by timestamp, then by reader, antenna and tag so that the order is total
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.timestamp
+            .cmp(&other.timestamp)
+            .then_with(|| self.reader.cmp(&other.reader))
+            .then_with(|| self.antenna.cmp(&other.antenna))
+            .then_with(|| self.tag.raw().cmp(other.tag.raw()))
+            .then_with(|| self.read_count.cmp(&other.read_count))
+            .then_with(|| match (self.rssi, other.rssi) {
+                (Some(a), Some(b)) => a.total_cmp(&b),
+                (a, b) => a.is_some().cmp(&b.is_some()),
+            })
+    }
+}
+
+impl PartialOrd for TagRead {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for TagRead {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for TagRead {}
+
+impl Display for TagRead {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {}",
+            self.timestamp.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            self.reader
+        )?;
+        if let Some(antenna) = self.antenna {
+            write!(f, " antenna {}", antenna)?;
+        }
+        write!(
+            f,
+            " : {} {} ({})",
+            self.tag.mark(),
+            self.tag.car_number(),
+            self.tag.side_indicator()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn read(second: u32, tag: &str) -> TagRead {
+        TagRead::new(
+            AEITagData::new(tag).unwrap(),
+            Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, second).unwrap(),
+            "SEPT-ILES",
+        )
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let mut first = read(0, "9EA488C030426A179000000000000331");
+        first.antenna = Some(1);
+        first.rssi = Some(-52.5);
+        first.read_count = 3;
+        let second = read(1, "2F3E06C007DB1E139000000000000331");
+
+        let csv = format!("{}\n{}\n{}\n", CSV_HEADER, first.to_csv(), second.to_csv());
+        assert_eq!(
+            first.to_csv(),
+            "2024-05-01T12:00:00Z;SEPT-ILES;1;-52.5;3;9EA488C030426A179000000000000331"
+        );
+        assert_eq!(TagRead::from_csv(&csv).unwrap(), vec![first, second]);
+    }
+
+    #[test]
+    fn quoted_reader() {
+        let mut read = read(0, "9EA488C030426A179000000000000331");
+        read.reader = String::from("SEPT-ILES; \"EAST\"");
+
+        let csv = format!("{}\n{}\n", CSV_HEADER, read.to_csv());
+        assert!(read.to_csv().contains(";\"SEPT-ILES; \"\"EAST\"\"\";"));
+        assert_eq!(TagRead::from_csv(&csv).unwrap()[0].reader, read.reader);
+    }
+
+    #[test]
+    fn minimal_csv() {
+        let reads = TagRead::from_csv(
+            "timestamp,reader,tag\n2024-05-01T12:00:00+02:00,R1,9EA488C030426A179000000000000331\n",
+        )
+        .unwrap();
+        assert_eq!(reads[0].read_count, 1);
+        assert_eq!(reads[0].antenna, None);
+        assert_eq!(
+            reads[0].timestamp,
+            Utc.with_ymd_and_hms(2024, 5, 1, 10, 0, 0).unwrap()
+        );
+
+        assert!(matches!(
+            TagRead::from_csv("timestamp,reader,tag\n2024-05-01T12:00:00Z,R1,9EA4\n"),
+            Err(TagReadError::Tag(_, _))
+        ));
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let mut read = read(0, "9EA488C5320CC01B9000000000000331");
+        read.rssi = Some(-60.0);
+        let json = read.to_json();
+
+        assert!(json.starts_with("{\"timestamp\":\"2024-05-01T12:00:00Z\",\"reader\":\"SEPT-ILES\",\"rssi\":-60.0,\"read_count\":1,\"tag\":{"));
+        assert_eq!(TagRead::from_json(&json).unwrap(), read);
+    }
+
+    #[test]
+    fn ordering() {
+        let mut reads = [
+            read(2, "9EA488C030426A179000000000000331"),
+            read(0, "9EA488C5320CC01B9000000000000331"),
+            read(1, "2F3E06C007DB1E139000000000000331"),
+        ];
+        reads.sort();
+
+        let numbers: Vec<u32> = reads.iter().map(|r| r.tag.car_number()).collect();
+        assert_eq!(numbers, vec![85123, 502, 3088]);
+        assert_eq!(
+            reads[0].to_string(),
+            "2024-05-01T12:00:00Z SEPT-ILES : IOCC 85123 (Left)"
+        );
+    }
+}
@@ -0,0 +1,740 @@
+//! Client of the Low Level Reader Protocol (LLRP) of the EPC Gen2 UHF readers
+//!
+//! Only the part of LLRP 1.0.1 needed to inventory AEI tags is implemented : the client adds,
+//! enables and starts a ROSpec reporting every tag as soon as it is read, then parses the
+//! RO_ACCESS_REPORT messages. The EPC of the tags written in the AEI format holds the 128 bits of
+//! the tag, decoded with [`AEITagData::new`]. The keepalives of the reader are acknowledged and the
+//! other messages it sends, such as the event notifications, are ignored.
+//!
+//! ```rust,no_run
+//! use aei_tag_parser::reader::llrp::{Client, RoSpec};
+//!
+//! let mut client = Client::connect("192.168.1.20:5084", "SEPT-ILES").unwrap();
+//! client.start(&RoSpec::new(1, &[1, 2])).unwrap();
+//! loop {
+//!     for report in client.next_reports().unwrap() {
+//!         match report.decode(client.name()) {
+//!             Ok(read) => println!("{}", read),
+//!             Err(e) => eprintln!("{} : {}", report.epc_hex(), e),
+//!         }
+//!     }
+//! }
+//! ```
+
+use crate::{read::TagRead, AEITagData, NewTagError};
+use chrono::{DateTime, Utc};
+use std::{
+    collections::VecDeque,
+    error,
+    fmt::Display,
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+};
+
+/// Port of the LLRP readers
+pub const DEFAULT_PORT: u16 = 5084;
+/// Protocol version of LLRP 1.0.1
+const VERSION: u16 = 1;
+/// Length of a message header
+const HEADER_LENGTH: usize = 10;
+/// Largest message accepted from the reader
+const MAX_MESSAGE_LENGTH: usize = 1 << 20;
+
+// Message types
+pub const CLOSE_CONNECTION_RESPONSE: u16 = 4;
+pub const CLOSE_CONNECTION: u16 = 14;
+pub const ADD_ROSPEC: u16 = 20;
+pub const DELETE_ROSPEC: u16 = 21;
+pub const START_ROSPEC: u16 = 22;
+pub const STOP_ROSPEC: u16 = 23;
+pub const ENABLE_ROSPEC: u16 = 24;
+pub const ADD_ROSPEC_RESPONSE: u16 = 30;
+pub const DELETE_ROSPEC_RESPONSE: u16 = 31;
+pub const START_ROSPEC_RESPONSE: u16 = 32;
+pub const STOP_ROSPEC_RESPONSE: u16 = 33;
+pub const ENABLE_ROSPEC_RESPONSE: u16 = 34;
+pub const RO_ACCESS_REPORT: u16 = 61;
+pub const KEEPALIVE: u16 = 62;
+pub const READER_EVENT_NOTIFICATION: u16 = 63;
+pub const KEEPALIVE_ACK: u16 = 72;
+pub const ERROR_MESSAGE: u16 = 100;
+
+// TV parameter types
+const ANTENNA_ID: u16 = 1;
+const FIRST_SEEN_TIMESTAMP_UTC: u16 = 2;
+const LAST_SEEN_TIMESTAMP_UTC: u16 = 4;
+const PEAK_RSSI: u16 = 6;
+const TAG_SEEN_COUNT: u16 = 8;
+const EPC_96: u16 = 13;
+
+// TLV parameter types
+const ROSPEC: u16 = 177;
+const RO_BOUNDARY_SPEC: u16 = 178;
+const ROSPEC_START_TRIGGER: u16 = 179;
+const ROSPEC_STOP_TRIGGER: u16 = 182;
+const AI_SPEC: u16 = 183;
+const AI_SPEC_STOP_TRIGGER: u16 = 184;
+const INVENTORY_PARAMETER_SPEC: u16 = 186;
+const RO_REPORT_SPEC: u16 = 237;
+const TAG_REPORT_CONTENT_SELECTOR: u16 = 238;
+const TAG_REPORT_DATA: u16 = 240;
+const EPC_DATA: u16 = 241;
+const LLRP_STATUS: u16 = 287;
+
+/// Length of the TV parameters, type included, indexed by their type
+const TV_LENGTHS: [usize; 19] = [0, 3, 9, 9, 9, 9, 2, 3, 3, 5, 3, 3, 3, 13, 3, 3, 5, 3, 5];
+
+#[derive(Debug)]
+pub enum LlrpError {
+    Io(io::Error),
+    /// The reader sent a message which doesn't follow the protocol
+    Protocol(String),
+    /// The reader refused a request : message type of the response, status code and description
+    Status(u16, u16, String),
+}
+
+impl From<io::Error> for LlrpError {
+    fn from(err: io::Error) -> Self {
+        LlrpError::Io(err)
+    }
+}
+
+impl Display for LlrpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LlrpError::Io(e) => write!(f, "the connection to the reader failed : {}", e),
+            LlrpError::Protocol(e) => write!(f, "invalid LLRP message : {}", e),
+            LlrpError::Status(kind, code, description) => write!(
+                f,
+                "the reader answered {} to message type {} : {}",
+                code, kind, description
+            ),
+        }
+    }
+}
+
+impl error::Error for LlrpError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            LlrpError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// LLRP message
+#[derive(Clone, PartialEq, Debug)]
+pub struct Message {
+    pub kind: u16,
+    pub id: u32,
+    /// Parameters of the message, header excluded
+    pub body: Vec<u8>,
+}
+
+impl Message {
+    pub fn new(kind: u16, id: u32, body: Vec<u8>) -> Message {
+        Message { kind, id, body }
+    }
+
+    /// Binary representation of the message, header included
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(HEADER_LENGTH + self.body.len());
+        out.extend_from_slice(&((VERSION << 10) | (self.kind & 0x3FF)).to_be_bytes());
+        out.extend_from_slice(&((HEADER_LENGTH + self.body.len()) as u32).to_be_bytes());
+        out.extend_from_slice(&self.id.to_be_bytes());
+        out.extend_from_slice(&self.body);
+        out
+    }
+
+    /// Reads the next message of the stream
+    pub fn read_from<R: Read>(reader: &mut R) -> Result<Message, LlrpError> {
+        let mut header = [0u8; HEADER_LENGTH];
+        reader.read_exact(&mut header)?;
+
+        let kind = u16::from_be_bytes([header[0], header[1]]) & 0x3FF;
+        let length = u32::from_be_bytes([header[2], header[3], header[4], header[5]]) as usize;
+        let id = u32::from_be_bytes([header[6], header[7], header[8], header[9]]);
+        if !(HEADER_LENGTH..=MAX_MESSAGE_LENGTH).contains(&length) {
+            return Err(LlrpError::Protocol(format!(
+                "message length of {} bytes",
+                length
+            )));
+        }
+
+        let mut body = vec![0u8; length - HEADER_LENGTH];
+        reader.read_exact(&mut body)?;
+        Ok(Message { kind, id, body })
+    }
+}
+
+/// Parameter of a message, either TV (type and value) or TLV (type, length and value)
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Parameter<'a> {
+    pub kind: u16,
+    /// Value of the parameter, header excluded
+    pub value: &'a [u8],
+}
+
+/// Encodes a TLV parameter
+fn tlv(kind: u16, value: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(4 + value.len());
+    out.extend_from_slice(&(kind & 0x3FF).to_be_bytes());
+    out.extend_from_slice(&((4 + value.len()) as u16).to_be_bytes());
+    out.extend_from_slice(value);
+    out
+}
+
+/// Splits the parameters following each other in `data`
+pub fn parameters(mut data: &[u8]) -> Result<Vec<Parameter<'_>>, LlrpError> {
+    let mut out = Vec::new();
+    while !data.is_empty() {
+        let (kind, header, length) = if data[0] & 0x80 != 0 {
+            let kind = u16::from(data[0] & 0x7F);
+            let length = TV_LENGTHS.get(usize::from(kind)).copied().unwrap_or(0);
+            if length == 0 {
+                return Err(LlrpError::Protocol(format!(
+                    "unknown TV parameter {}",
+                    kind
+                )));
+            }
+            (kind, 1, length)
+        } else if data.len() >= 4 {
+            let kind = u16::from_be_bytes([data[0], data[1]]) & 0x3FF;
+            (kind, 4, usize::from(u16::from_be_bytes([data[2], data[3]])))
+        } else {
+            return Err(LlrpError::Protocol(String::from("truncated parameter")));
+        };
+
+        if length < header || length > data.len() {
+            return Err(LlrpError::Protocol(format!(
+                "parameter {} of {} bytes",
+                kind, length
+            )));
+        }
+        out.push(Parameter {
+            kind,
+            value: &data[header..length],
+        });
+        data = &data[length..];
+    }
+    Ok(out)
+}
+
+/// Checks the LLRPStatus parameter of a response
+fn check_status(response: &Message) -> Result<(), LlrpError> {
+    let params = parameters(&response.body)?;
+    let status = params
+        .iter()
+        .find(|p| p.kind == LLRP_STATUS)
+        .ok_or_else(|| LlrpError::Protocol(String::from("response without status")))?;
+    if status.value.len() < 4 {
+        return Err(LlrpError::Protocol(String::from("truncated status")));
+    }
+
+    let code = u16::from_be_bytes([status.value[0], status.value[1]]);
+    let count = usize::from(u16::from_be_bytes([status.value[2], status.value[3]]));
+    if code == 0 {
+        return Ok(());
+    }
+    let description = status.value.get(4..4 + count).unwrap_or_default();
+    Err(LlrpError::Status(
+        response.kind,
+        code,
+        String::from_utf8_lossy(description).into_owned(),
+    ))
+}
+
+/// Encodes a LLRPStatus parameter
+pub fn status(code: u16, description: &str) -> Vec<u8> {
+    let mut value = Vec::new();
+    value.extend_from_slice(&code.to_be_bytes());
+    value.extend_from_slice(&(description.len() as u16).to_be_bytes());
+    value.extend_from_slice(description.as_bytes());
+    tlv(LLRP_STATUS, &value)
+}
+
+/// Reader operation inventorying the Gen2 tags on the given antennas until it is stopped, or for
+/// the given duration, and reporting every tag as soon as it is read
+#[derive(Clone, PartialEq, Debug)]
+pub struct RoSpec {
+    pub id: u32,
+    /// Antennas to use, none meaning all the antennas of the reader
+    pub antennas: Vec<u16>,
+    /// Duration of the inventory in milliseconds
+    pub duration_ms: Option<u32>,
+}
+
+impl RoSpec {
+    pub fn new(id: u32, antennas: &[u16]) -> RoSpec {
+        RoSpec {
+            id,
+            antennas: Vec::from(antennas),
+            duration_ms: None,
+        }
+    }
+
+    /// ROSpec parameter
+    pub fn encode(&self) -> Vec<u8> {
+        // Started by START_ROSPEC, stopped after the duration or by STOP_ROSPEC
+        let start_trigger = tlv(ROSPEC_START_TRIGGER, &[0]);
+        let stop_trigger = match self.duration_ms {
+            Some(duration) => {
+                let mut value = vec![1];
+                value.extend_from_slice(&duration.to_be_bytes());
+                tlv(ROSPEC_STOP_TRIGGER, &value)
+            }
+            None => tlv(ROSPEC_STOP_TRIGGER, &[0, 0, 0, 0, 0]),
+        };
+        let boundary = tlv(RO_BOUNDARY_SPEC, &[start_trigger, stop_trigger].concat());
+
+        let antennas = if self.antennas.is_empty() {
+            vec![0]
+        } else {
+            self.antennas.clone()
+        };
+        let mut ai_spec = Vec::new();
+        ai_spec.extend_from_slice(&(antennas.len() as u16).to_be_bytes());
+        for antenna in antennas {
+            ai_spec.extend_from_slice(&antenna.to_be_bytes());
+        }
+        ai_spec.extend(tlv(AI_SPEC_STOP_TRIGGER, &[0, 0, 0, 0, 0]));
+        // Inventory parameter spec 1, EPCglobal Class 1 Gen 2
+        ai_spec.extend(tlv(INVENTORY_PARAMETER_SPEC, &[0, 1, 1]));
+        let ai_spec = tlv(AI_SPEC, &ai_spec);
+
+        // Antenna, peak RSSI, first and last seen timestamps and seen count of every tag
+        let selector: u16 = 1 << 12 | 1 << 10 | 1 << 9 | 1 << 8 | 1 << 7;
+        let mut report_spec = vec![2, 0, 1];
+        report_spec.extend(tlv(TAG_REPORT_CONTENT_SELECTOR, &selector.to_be_bytes()));
+        let report_spec = tlv(RO_REPORT_SPEC, &report_spec);
+
+        let mut value = Vec::new();
+        value.extend_from_slice(&self.id.to_be_bytes());
+        // Priority 0, state disabled
+        value.extend_from_slice(&[0, 0]);
+        value.extend(boundary);
+        value.extend(ai_spec);
+        value.extend(report_spec);
+        tlv(ROSPEC, &value)
+    }
+}
+
+/// Tag seen by the reader, as reported in a TagReportData parameter
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct TagReport {
+    pub epc: Vec<u8>,
+    pub antenna: Option<u16>,
+    /// Peak RSSI in dBm
+    pub peak_rssi: Option<i8>,
+    pub first_seen: Option<DateTime<Utc>>,
+    pub last_seen: Option<DateTime<Utc>>,
+    pub seen_count: Option<u16>,
+}
+
+impl TagReport {
+    /// Parses the content of a TagReportData parameter
+    fn parse(value: &[u8]) -> Result<TagReport, LlrpError> {
+        let mut report = TagReport::default();
+        for param in parameters(value)? {
+            let v = param.value;
+            match param.kind {
+                EPC_DATA => {
+                    let bits = usize::from(u16::from_be_bytes(field(param.kind, v)?));
+                    report.epc = v
+                        .get(2..2 + bits.div_ceil(8))
+                        .ok_or_else(|| LlrpError::Protocol(String::from("truncated EPC")))?
+                        .to_vec();
+                }
+                EPC_96 => report.epc = v.to_vec(),
+                ANTENNA_ID => report.antenna = Some(u16::from_be_bytes(field(param.kind, v)?)),
+                PEAK_RSSI => report.peak_rssi = Some(field::<1>(param.kind, v)?[0] as i8),
+                FIRST_SEEN_TIMESTAMP_UTC => report.first_seen = timestamp(v),
+                LAST_SEEN_TIMESTAMP_UTC => report.last_seen = timestamp(v),
+                TAG_SEEN_COUNT => {
+                    report.seen_count = Some(u16::from_be_bytes(field(param.kind, v)?))
+                }
+                _ => (),
+            }
+        }
+        Ok(report)
+    }
+
+    /// TagReportData parameter
+    pub fn encode(&self) -> Vec<u8> {
+        let mut value = Vec::new();
+        let mut epc = ((self.epc.len() * 8) as u16).to_be_bytes().to_vec();
+        epc.extend_from_slice(&self.epc);
+        value.extend(tlv(EPC_DATA, &epc));
+        if let Some(antenna) = self.antenna {
+            value.push(0x80 | ANTENNA_ID as u8);
+            value.extend_from_slice(&antenna.to_be_bytes());
+        }
+        if let Some(rssi) = self.peak_rssi {
+            value.extend_from_slice(&[0x80 | PEAK_RSSI as u8, rssi as u8]);
+        }
+        for (kind, time) in [
+            (FIRST_SEEN_TIMESTAMP_UTC, self.first_seen),
+            (LAST_SEEN_TIMESTAMP_UTC, self.last_seen),
+        ] {
+            if let Some(time) = time {
+                value.push(0x80 | kind as u8);
+                value.extend_from_slice(&(time.timestamp_micros() as u64).to_be_bytes());
+            }
+        }
+        if let Some(count) = self.seen_count {
+            value.push(0x80 | TAG_SEEN_COUNT as u8);
+            value.extend_from_slice(&count.to_be_bytes());
+        }
+        tlv(TAG_REPORT_DATA, &value)
+    }
+
+    /// EPC in hexadecimal
+    pub fn epc_hex(&self) -> String {
+        hex::encode_upper(&self.epc)
+    }
+
+    /// Decodes the AEI tag held by the EPC. The read is stamped with the first time the tag was
+    /// seen, or the current time when the reader didn't send it
+    pub fn decode(&self, reader: &str) -> Result<TagRead, NewTagError> {
+        let tag = AEITagData::new(&self.epc_hex())?;
+        let timestamp = self.first_seen.or(self.last_seen).unwrap_or_else(Utc::now);
+        let mut read = TagRead::new(tag, timestamp, reader);
+        read.antenna = self.antenna.and_then(|antenna| u8::try_from(antenna).ok());
+        read.rssi = self.peak_rssi.map(f64::from);
+        read.read_count = self.seen_count.map_or(1, u32::from);
+        Ok(read)
+    }
+}
+
+/// First `N` bytes of the value of a parameter, which mustn't be shorter
+fn field<const N: usize>(kind: u16, value: &[u8]) -> Result<[u8; N], LlrpError> {
+    value
+        .get(..N)
+        .and_then(|value| value.try_into().ok())
+        .ok_or_else(|| {
+            LlrpError::Protocol(format!(
+                "parameter {} of {} bytes, {} expected",
+                kind,
+                value.len(),
+                N
+            ))
+        })
+}
+
+/// Microseconds since the epoch
+fn timestamp(value: &[u8]) -> Option<DateTime<Utc>> {
+    let micros = u64::from_be_bytes(value.try_into().ok()?);
+    DateTime::from_timestamp_micros(i64::try_from(micros).ok()?)
+}
+
+/// Tags reported in a RO_ACCESS_REPORT message
+pub fn parse_report(message: &Message) -> Result<Vec<TagReport>, LlrpError> {
+    parameters(&message.body)?
+        .iter()
+        .filter(|p| p.kind == TAG_REPORT_DATA)
+        .map(|p| TagReport::parse(p.value))
+        .collect()
+}
+
+/// RO_ACCESS_REPORT message
+pub fn encode_report(id: u32, reports: &[TagReport]) -> Message {
+    Message::new(
+        RO_ACCESS_REPORT,
+        id,
+        reports.iter().flat_map(TagReport::encode).collect(),
+    )
+}
+
+/// Connection to a LLRP reader
+pub struct Client<S: Read + Write> {
+    stream: S,
+    name: String,
+    next_id: u32,
+    /// Reports received while waiting for a response
+    pending: VecDeque<TagReport>,
+}
+
+impl Client<TcpStream> {
+    /// Connects to the reader, `name` identifying it in the reads
+    pub fn connect<A: ToSocketAddrs>(addr: A, name: &str) -> Result<Client<TcpStream>, LlrpError> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        Ok(Client::new(stream, name))
+    }
+}
+
+impl<S: Read + Write> Client<S> {
+    pub fn new(stream: S, name: &str) -> Client<S> {
+        Client {
+            stream,
+            name: String::from(name),
+            next_id: 1,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Name of the reader
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn send(&mut self, kind: u16, body: Vec<u8>) -> Result<u32, LlrpError> {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.stream
+            .write_all(&Message::new(kind, id, body).encode())?;
+        self.stream.flush()?;
+        Ok(id)
+    }
+
+    /// Reads the next message. The keepalives are acknowledged and the reports kept, `None`
+    /// being returned for both
+    fn receive(&mut self) -> Result<Option<Message>, LlrpError> {
+        let message = Message::read_from(&mut self.stream)?;
+        match message.kind {
+            KEEPALIVE => {
+                self.stream
+                    .write_all(&Message::new(KEEPALIVE_ACK, message.id, Vec::new()).encode())?;
+                self.stream.flush()?;
+                Ok(None)
+            }
+            RO_ACCESS_REPORT => {
+                self.pending.extend(parse_report(&message)?);
+                Ok(None)
+            }
+            _ => Ok(Some(message)),
+        }
+    }
+
+    /// Sends a request and waits for its response
+    fn request(&mut self, kind: u16, body: Vec<u8>, response: u16) -> Result<(), LlrpError> {
+        let id = self.send(kind, body)?;
+        loop {
+            match self.receive()? {
+                Some(message) if message.kind == ERROR_MESSAGE => return check_status(&message),
+                Some(message) if message.kind == response && message.id == id => {
+                    return check_status(&message)
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Adds, enables and starts the ROSpec
+    pub fn start(&mut self, spec: &RoSpec) -> Result<(), LlrpError> {
+        let id = spec.id.to_be_bytes().to_vec();
+        self.request(ADD_ROSPEC, spec.encode(), ADD_ROSPEC_RESPONSE)?;
+        self.request(ENABLE_ROSPEC, id.clone(), ENABLE_ROSPEC_RESPONSE)?;
+        self.request(START_ROSPEC, id, START_ROSPEC_RESPONSE)
+    }
+
+    /// Stops and deletes the ROSpec
+    pub fn stop(&mut self, spec_id: u32) -> Result<(), LlrpError> {
+        let id = spec_id.to_be_bytes().to_vec();
+        self.request(STOP_ROSPEC, id.clone(), STOP_ROSPEC_RESPONSE)?;
+        self.request(DELETE_ROSPEC, id, DELETE_ROSPEC_RESPONSE)
+    }
+
+    /// Waits for the next tags reported by the reader
+    pub fn next_reports(&mut self) -> Result<Vec<TagReport>, LlrpError> {
+        while self.pending.is_empty() {
+            // Only the reports matter once the ROSpec is started
+            self.receive()?;
+        }
+        Ok(self.pending.drain(..).collect())
+    }
+
+    /// Closes the connection
+    pub fn close(mut self) -> Result<(), LlrpError> {
+        self.request(CLOSE_CONNECTION, Vec::new(), CLOSE_CONNECTION_RESPONSE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::{net::TcpListener, thread};
+
+    static TAG2: &str = "9EA488C030426A179000000000000331";
+
+    fn report(tag: &str, antenna: u16) -> TagReport {
+        TagReport {
+            epc: hex::decode(tag).unwrap(),
+            antenna: Some(antenna),
+            peak_rssi: Some(-52),
+            first_seen: Some(Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap()),
+            last_seen: None,
+            seen_count: Some(3),
+        }
+    }
+
+    /// Answers the requests as a reader and sends reports once the ROSpec is started
+    fn stand_in_reader(listener: TcpListener) -> Vec<Message> {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut received = Vec::new();
+        // Connection event sent by the readers, ignored by the client
+        stream
+            .write_all(&Message::new(READER_EVENT_NOTIFICATION, 0, Vec::new()).encode())
+            .unwrap();
+
+        loop {
+            let message = Message::read_from(&mut stream).unwrap();
+            received.push(message.clone());
+            let response = match message.kind {
+                ADD_ROSPEC => ADD_ROSPEC_RESPONSE,
+                ENABLE_ROSPEC => ENABLE_ROSPEC_RESPONSE,
+                START_ROSPEC => START_ROSPEC_RESPONSE,
+                STOP_ROSPEC => STOP_ROSPEC_RESPONSE,
+                DELETE_ROSPEC => {
+                    let body = status(101, "ROSpec not found");
+                    stream
+                        .write_all(&Message::new(DELETE_ROSPEC_RESPONSE, message.id, body).encode())
+                        .unwrap();
+                    continue;
+                }
+                CLOSE_CONNECTION => CLOSE_CONNECTION_RESPONSE,
+                KEEPALIVE_ACK => continue,
+                _ => panic!("unexpected message {}", message.kind),
+            };
+            stream
+                .write_all(&Message::new(response, message.id, status(0, "")).encode())
+                .unwrap();
+
+            match message.kind {
+                START_ROSPEC => {
+                    let reports = [report(TAG2, 1), report("E2801160600002", 2)];
+                    stream
+                        .write_all(&encode_report(100, &reports).encode())
+                        .unwrap();
+                    stream
+                        .write_all(&Message::new(KEEPALIVE, 101, Vec::new()).encode())
+                        .unwrap();
+                    stream
+                        .write_all(&encode_report(102, &[report(TAG2, 2)]).encode())
+                        .unwrap();
+                }
+                CLOSE_CONNECTION => return received,
+                _ => (),
+            }
+        }
+    }
+
+    #[test]
+    fn inventory() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || stand_in_reader(listener));
+
+        let mut client = Client::connect(addr, "R1").unwrap();
+        let spec = RoSpec::new(7, &[1, 2]);
+        client.start(&spec).unwrap();
+
+        let reports = client.next_reports().unwrap();
+        assert_eq!(reports.len(), 2);
+        let read = reports[0].decode(client.name()).unwrap();
+        assert_eq!(read.tag.car_number(), 3088);
+        assert_eq!(read.antenna, Some(1));
+        assert_eq!(read.rssi, Some(-52.0));
+        assert_eq!(read.read_count, 3);
+        assert_eq!(read.reader, "R1");
+        assert_eq!(
+            read.timestamp,
+            Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap()
+        );
+        // Not an AEI tag
+        assert!(reports[1].decode("R1").is_err());
+
+        let reports = client.next_reports().unwrap();
+        assert_eq!(reports[0].antenna, Some(2));
+
+        match client.stop(7) {
+            Err(LlrpError::Status(DELETE_ROSPEC_RESPONSE, 101, description)) => {
+                assert_eq!(description, "ROSpec not found")
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+        client.close().unwrap();
+
+        let received = server.join().unwrap();
+        let kinds: Vec<u16> = received.iter().map(|m| m.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ADD_ROSPEC,
+                ENABLE_ROSPEC,
+                START_ROSPEC,
+                KEEPALIVE_ACK,
+                STOP_ROSPEC,
+                DELETE_ROSPEC,
+                CLOSE_CONNECTION
+            ]
+        );
+        assert_eq!(received[0].body, spec.encode());
+        assert_eq!(received[1].body, vec![0, 0, 0, 7]);
+        assert_eq!(received[3].id, 101);
+    }
+
+    #[test]
+    fn message_encoding() {
+        let message = Message::new(ENABLE_ROSPEC, 0x0102_0304, vec![0, 0, 0, 1]);
+        let bytes = message.encode();
+
+        assert_eq!(bytes, vec![0x04, 0x18, 0, 0, 0, 14, 1, 2, 3, 4, 0, 0, 0, 1]);
+        assert_eq!(Message::read_from(&mut &bytes[..]).unwrap(), message);
+        assert!(matches!(
+            Message::read_from(&mut &[0x04, 0x18, 0, 0, 0, 4, 1, 2, 3, 4][..]),
+            Err(LlrpError::Protocol(_))
+        ));
+    }
+
+    #[test]
+    fn rospec_parameters() {
+        let mut spec = RoSpec::new(1, &[]);
+        spec.duration_ms = Some(500);
+        let encoded = spec.encode();
+        let params = parameters(&encoded).unwrap();
+
+        assert_eq!(params.len(), 1);
+        assert_eq!(params[0].kind, ROSPEC);
+        let inner = parameters(&params[0].value[6..]).unwrap();
+        let kinds: Vec<u16> = inner.iter().map(|p| p.kind).collect();
+        assert_eq!(kinds, vec![RO_BOUNDARY_SPEC, AI_SPEC, RO_REPORT_SPEC]);
+        // Every antenna
+        assert_eq!(&inner[1].value[..4], &[0, 1, 0, 0]);
+        let boundary = parameters(inner[0].value).unwrap();
+        assert_eq!(boundary[1].value, &[1, 0, 0, 1, 0xF4]);
+    }
+
+    #[test]
+    fn report_parsing() {
+        let message = encode_report(1, &[report(TAG2, 4)]);
+        let reports = parse_report(&message).unwrap();
+
+        assert_eq!(reports, vec![report(TAG2, 4)]);
+        assert_eq!(reports[0].epc_hex(), TAG2);
+
+        // EPC-96 as a TV parameter
+        let mut body = tlv(TAG_REPORT_DATA, &[&[0x8D][..], &[0xAB; 12]].concat());
+        body.extend(tlv(999, &[1, 2]));
+        let reports = parse_report(&Message::new(RO_ACCESS_REPORT, 2, body)).unwrap();
+        assert_eq!(reports[0].epc, vec![0xAB; 12]);
+        assert!(reports[0].decode("R1").is_err());
+
+        let truncated = Message::new(RO_ACCESS_REPORT, 3, vec![0, 240, 0, 40, 1]);
+        assert!(parse_report(&truncated).is_err());
+    }
+
+    #[test]
+    fn malformed_parameters() {
+        // Parameters sent in TLV form with an empty value
+        for kind in [ANTENNA_ID, PEAK_RSSI, TAG_SEEN_COUNT, EPC_DATA] {
+            let body = tlv(TAG_REPORT_DATA, &tlv(kind, &[]));
+            let e = parse_report(&Message::new(RO_ACCESS_REPORT, 4, body)).unwrap_err();
+            assert!(matches!(e, LlrpError::Protocol(_)), "{:?}", e);
+        }
+
+        let body = tlv(TAG_REPORT_DATA, &[0, 1, 0, 4]);
+        assert!(parse_report(&Message::new(RO_ACCESS_REPORT, 5, body)).is_err());
+    }
+}
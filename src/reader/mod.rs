@@ -0,0 +1,7 @@
+//! Protocols of the wayside readers
+//!
+//! The readers don't send bare tags but messages framing them with the context of the read. Each
+//! module parses the messages of a family of readers into [`TagRead`](crate::read::TagRead)s.
+
+pub mod llrp;
+pub mod transcore;
@@ -0,0 +1,338 @@
+//! ASCII protocol of the TransCore style readers
+//!
+//! Every message is a line starting with the `#` start of message marker and ended by CR LF.
+//! The marker is optional so that logs without it can be parsed too. The messages are :
+//!
+//! | Message        | Format                                          | Example                                             |
+//! |----------------|-------------------------------------------------|-----------------------------------------------------|
+//! | Sign-on        | `Model <model and version>`                     | `#Model E4 Series Ver 4.14`                         |
+//! | Tag read       | `<tag>[&<antenna>][/<MM/DD/YY HH:MM:SS[.fff]>]` | `#9EA488C030426A179000000000000331&2/05/01/24 12:00:00.250` |
+//! | Buffered read  | `BUF <tag read>`                                | `#BUF 9EA488C030426A179000000000000331/05/01/24 11:59:58` |
+//! | Presence       | `PRESENCE ON\|OFF[&<antenna>]`                  | `#PRESENCE ON&1`                                    |
+//! | Status         | `Done`, `Error <code>`                          | `#Error 5`                                          |
+//!
+//! The timestamps sent by the reader are taken as UTC. Reads without a timestamp are stamped with
+//! the time they are received, while a read with an invalid timestamp is an error. Any other
+//! message is kept as [`Message::Unknown`].
+//!
+//! ```rust
+//! use aei_tag_parser::reader::transcore::{Message, MessageStream};
+//!
+//! let data = "#Model E4 Series Ver 4.14\r\n#9EA488C030426A179000000000000331&2/05/01/24 12:00:00\r\n";
+//! let messages: Vec<Message> = MessageStream::new(data.as_bytes(), "SEPT-ILES")
+//!     .map(|message| message.unwrap().1)
+//!     .collect();
+//!
+//! assert_eq!(messages[0], Message::SignOn(String::from("Model E4 Series Ver 4.14")));
+//! match &messages[1] {
+//!     Message::Tag(read) => assert_eq!((read.tag.car_number(), read.antenna), (3088, Some(2))),
+//!     _ => unreachable!(),
+//! }
+//! ```
+
+use crate::{
+    read::TagRead,
+    stream::{LineError, LineReader},
+    AEITagData,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::io::{self, BufRead};
+
+/// Start of message marker
+pub const SOM: char = '#';
+
+/// Format of the timestamps sent by the reader
+const TIMESTAMP_FORMAT: &str = "%m/%d/%y %H:%M:%S%.f";
+
+/// Message sent by the reader
+#[derive(Clone, PartialEq, Debug)]
+pub enum Message {
+    /// Model and version sent by the reader when it starts
+    SignOn(String),
+    /// Tag read as it passes
+    Tag(TagRead),
+    /// Tag read while the host wasn't connected, sent from the reader buffer
+    Buffered(TagRead),
+    /// Presence detector turned on or off
+    Presence { present: bool, antenna: Option<u8> },
+    /// Acknowledgment (`Done`) or error of a command
+    Status(String),
+    /// Message of an unknown type, kept as received
+    Unknown(String),
+}
+
+/// Parses the message of the line `line_no`, received at `received` from the reader named
+/// `reader`. Returns `None` for blank lines
+pub fn parse_message(
+    line_no: usize,
+    line: &str,
+    reader: &str,
+    received: DateTime<Utc>,
+) -> Option<Result<Message, LineError>> {
+    let line = line.trim();
+    let body = line.strip_prefix(SOM).unwrap_or(line).trim();
+    if body.is_empty() {
+        return None;
+    }
+
+    let keyword = body
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_uppercase();
+    let message = match keyword.as_str() {
+        "MODEL" => Message::SignOn(String::from(body)),
+        "DONE" | "ERROR" => Message::Status(String::from(body)),
+        "BUF" => {
+            return Some(
+                parse_tag_read(line_no, body[3..].trim_start(), reader, received)
+                    .map(Message::Buffered),
+            )
+        }
+        _ if keyword.starts_with("PRESENCE") => {
+            parse_presence(body).unwrap_or_else(|| Message::Unknown(String::from(line)))
+        }
+        _ if is_tag_report(body) => {
+            return Some(parse_tag_read(line_no, body, reader, received).map(Message::Tag))
+        }
+        _ => Message::Unknown(String::from(line)),
+    };
+    Some(Ok(message))
+}
+
+/// Check if the message starts with the 32 hexadecimal digits of a tag
+fn is_tag_report(body: &str) -> bool {
+    let digits = body.chars().take_while(|c| c.is_ascii_hexdigit()).count();
+    digits == 32 && matches!(body.chars().nth(32), None | Some('&') | Some('/'))
+}
+
+/// Parses `<tag>[&<antenna>][/<timestamp>]`
+fn parse_tag_read(
+    line_no: usize,
+    report: &str,
+    reader: &str,
+    received: DateTime<Utc>,
+) -> Result<TagRead, LineError> {
+    let (report, timestamp) = match report.split_once('/') {
+        Some((report, timestamp)) => {
+            let timestamp = timestamp.trim();
+            match NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT) {
+                Ok(timestamp) => (report, Some(timestamp.and_utc())),
+                Err(e) => return Err(LineError::Timestamp(line_no, String::from(timestamp), e)),
+            }
+        }
+        None => (report, None),
+    };
+    let (tag, antenna) = match report.split_once('&') {
+        Some((tag, antenna)) => (tag, antenna.trim().parse().ok()),
+        None => (report, None),
+    };
+
+    let tag = tag.trim();
+    let data = AEITagData::new(tag).map_err(|e| LineError::Tag(line_no, String::from(tag), e))?;
+    let mut read = TagRead::new(data, timestamp.unwrap_or(received), reader);
+    read.antenna = antenna;
+    Ok(read)
+}
+
+/// Parses `PRESENCE ON|OFF[&<antenna>]`
+fn parse_presence(body: &str) -> Option<Message> {
+    let (state, antenna) = match body.split_once('&') {
+        Some((state, antenna)) => (state, antenna.trim().parse().ok()),
+        None => (body, None),
+    };
+    let present = match state.to_uppercase().split_whitespace().nth(1) {
+        Some("ON") => true,
+        Some("OFF") => false,
+        _ => return None,
+    };
+    Some(Message::Presence { present, antenna })
+}
+
+/// Iterator over the messages of a reader, yielding each message with its line number. A tag
+/// read whose tag can't be decoded is yielded as an error without ending the stream
+pub struct MessageStream<R: BufRead> {
+    lines: LineReader<R>,
+    name: String,
+}
+
+impl<R: BufRead> MessageStream<R> {
+    /// Messages read from `reader`, the reads being attributed to the reader named `name`
+    pub fn new(reader: R, name: &str) -> MessageStream<R> {
+        MessageStream {
+            lines: LineReader::new(reader),
+            name: String::from(name),
+        }
+    }
+
+    /// Only the tags read, live or from the reader buffer, the other messages being dropped
+    pub fn tag_reads(self) -> impl Iterator<Item = Result<(usize, TagRead), LineError>> {
+        self.filter_map(|message| match message {
+            Ok((line, Message::Tag(read) | Message::Buffered(read))) => Some(Ok((line, read))),
+            Ok(_) => None,
+            Err(e) => Some(Err(e)),
+        })
+    }
+}
+
+impl<R: BufRead> Iterator for MessageStream<R> {
+    type Item = Result<(usize, Message), LineError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (line_no, data) = match self.lines.next_line()? {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e)),
+            };
+            let line = match std::str::from_utf8(data) {
+                Ok(line) => line,
+                Err(e) => {
+                    return Some(Err(LineError::Io(
+                        line_no,
+                        io::Error::new(io::ErrorKind::InvalidData, e),
+                    )))
+                }
+            };
+            if let Some(message) = parse_message(line_no, line, &self.name, Utc::now()) {
+                return Some(message.map(|message| (line_no, message)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn received() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, 1, 13, 0, 0).unwrap()
+    }
+
+    fn parse(line: &str) -> Message {
+        parse_message(1, line, "R1", received()).unwrap().unwrap()
+    }
+
+    #[test]
+    fn tag_reads() {
+        let read = match parse("#9EA488C030426A179000000000000331&2/05/01/24 12:00:00.250\r\n") {
+            Message::Tag(read) => read,
+            m => panic!("unexpected message {:?}", m),
+        };
+        assert_eq!(read.tag.car_number(), 3088);
+        assert_eq!(read.antenna, Some(2));
+        assert_eq!(read.reader, "R1");
+        assert_eq!(
+            read.timestamp,
+            Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap()
+                + chrono::Duration::milliseconds(250)
+        );
+
+        // Without antenna, timestamp nor start of message
+        match parse("9EA488C5320CC01B9000000000000331") {
+            Message::Tag(read) => {
+                assert_eq!(read.antenna, None);
+                assert_eq!(read.timestamp, received());
+            }
+            m => panic!("unexpected message {:?}", m),
+        }
+    }
+
+    #[test]
+    fn invalid_timestamps() {
+        let e = parse_message(
+            3,
+            "#9EA488C030426A179000000000000331&2/13/45/24 12:00:00",
+            "R1",
+            received(),
+        )
+        .unwrap()
+        .unwrap_err();
+
+        assert!(matches!(e, LineError::Timestamp(3, ref t, _) if t == "13/45/24 12:00:00"));
+        assert!(e
+            .to_string()
+            .starts_with("line 3 : invalid timestamp 13/45/24 12:00:00 : "));
+    }
+
+    #[test]
+    fn buffered_reads() {
+        match parse("#BUF 2F3E06C007DB1E139000000000000331/05/01/24 11:59:58") {
+            Message::Buffered(read) => {
+                assert_eq!(read.tag.car_number(), 502);
+                assert_eq!(
+                    read.timestamp,
+                    Utc.with_ymd_and_hms(2024, 5, 1, 11, 59, 58).unwrap()
+                );
+            }
+            m => panic!("unexpected message {:?}", m),
+        }
+    }
+
+    #[test]
+    fn other_messages() {
+        assert_eq!(
+            parse("#Model E4 Series Ver 4.14"),
+            Message::SignOn(String::from("Model E4 Series Ver 4.14"))
+        );
+        assert_eq!(
+            parse("#PRESENCE ON&1"),
+            Message::Presence {
+                present: true,
+                antenna: Some(1)
+            }
+        );
+        assert_eq!(
+            parse("#presence off"),
+            Message::Presence {
+                present: false,
+                antenna: None
+            }
+        );
+        assert_eq!(parse("#Error 5"), Message::Status(String::from("Error 5")));
+        assert_eq!(parse("#Done"), Message::Status(String::from("Done")));
+        assert_eq!(
+            parse("#RSSI 12"),
+            Message::Unknown(String::from("#RSSI 12"))
+        );
+        // A tag of another length isn't mistaken for an AEI tag
+        assert_eq!(
+            parse("#9EA488C030426A17"),
+            Message::Unknown(String::from("#9EA488C030426A17"))
+        );
+        assert!(parse_message(1, "#\r\n", "R1", received()).is_none());
+    }
+
+    #[test]
+    fn stream() {
+        let data = "#Model E4 Series Ver 4.14\r\n\r\n#PRESENCE ON\r\n#9EA488C030426A179000000000000331&1\r\n#BUF 9EA488C5320CC01B9000000000000331\r\n#BUF ZZ\r\n#PRESENCE OFF\r\n";
+        let stream = MessageStream::new(data.as_bytes(), "R1");
+        let reads: Vec<_> = stream.tag_reads().collect();
+
+        assert_eq!(reads.len(), 3);
+        assert_eq!(reads[0].as_ref().unwrap().0, 4);
+        assert_eq!(reads[1].as_ref().unwrap().1.tag.car_number(), 85123);
+        assert!(matches!(reads[2], Err(LineError::Tag(6, ref tag, _)) if tag == "ZZ"));
+    }
+
+    #[test]
+    fn long_lines() {
+        let data = format!(
+            "#Model {}\r\n#PRESENCE ON\r\n",
+            "E".repeat(crate::stream::MAX_LINE_LENGTH)
+        );
+        let messages: Vec<_> = MessageStream::new(data.as_bytes(), "R1").collect();
+
+        assert!(matches!(messages[0], Err(LineError::Io(1, _))));
+        assert_eq!(
+            messages[1].as_ref().unwrap(),
+            &(
+                2,
+                Message::Presence {
+                    present: true,
+                    antenna: None
+                }
+            )
+        );
+    }
+}
@@ -0,0 +1,297 @@
+//! Reconciliation of a fleet roster with the tags read in the field
+//!
+//! Every tag of a roster car is compared field by field with the roster, and the result lists the
+//! roster cars never seen as well as the tags seen which aren't on the roster.
+//!
+//! ```rust
+//! use aei_tag_parser::{reconcile::Reconciler, roster::Roster, AEITagData};
+//!
+//! let roster = Roster::from_csv("mark;number;length_dm\nIOCC;3088;110\nIOCC;1;\n").unwrap();
+//! let tags = vec![AEITagData::new("9EA488C030426A179000000000000331").unwrap()];
+//!
+//! let result = Reconciler::new(&roster).reconcile(tags);
+//! assert_eq!(result.mismatches.len(), 1);
+//! assert_eq!(result.unseen.len(), 1);
+//! ```
+
+use crate::{
+    roster::{Roster, RosterCar},
+    AEITagData, Side,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+
+/// Field of a tag checked against the roster
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Field {
+    Length,
+    Axles,
+    Group,
+    Bearing,
+}
+
+impl Display for Field {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Field::Length => write!(f, "length"),
+            Field::Axles => write!(f, "axles"),
+            Field::Group => write!(f, "group"),
+            Field::Bearing => write!(f, "bearing"),
+        }
+    }
+}
+
+/// Field whose value on the tag differs from the roster
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct FieldMismatch {
+    pub field: Field,
+    pub roster: u32,
+    pub tag: u32,
+}
+
+impl Display for FieldMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} roster {}, tag {}", self.field, self.roster, self.tag)
+    }
+}
+
+/// Tag seen in the field
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct SeenTag {
+    pub mark: String,
+    pub number: u32,
+    pub side: Side,
+    /// Raw value of the tag in hexadecimal
+    pub tag: String,
+}
+
+impl From<&AEITagData> for SeenTag {
+    fn from(tag: &AEITagData) -> Self {
+        SeenTag {
+            mark: String::from(tag.equipment_initial().trim_end()),
+            number: tag.car_number(),
+            side: tag.side_indicator(),
+            tag: hex::encode_upper(tag.raw()),
+        }
+    }
+}
+
+impl Display for SeenTag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} ({})", self.mark, self.number, self.side)
+    }
+}
+
+/// Tag of a roster car which disagrees with the roster
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct CarMismatch {
+    pub seen: SeenTag,
+    pub fields: Vec<FieldMismatch>,
+}
+
+impl Display for CarMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fields: Vec<String> = self.fields.iter().map(|field| field.to_string()).collect();
+        write!(f, "{} : {}", self.seen, fields.join("; "))
+    }
+}
+
+/// Result of the reconciliation
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct Reconciliation {
+    /// Number of tags matching their roster car
+    pub matched: usize,
+    /// Tags disagreeing with their roster car
+    pub mismatches: Vec<CarMismatch>,
+    /// Roster cars none of the tags belong to
+    pub unseen: Vec<RosterCar>,
+    /// Tags which don't belong to any roster car
+    pub unknown: Vec<SeenTag>,
+}
+
+impl Reconciliation {
+    /// Check if the tags and the roster fully agree
+    pub fn is_clean(&self) -> bool {
+        self.mismatches.is_empty() && self.unseen.is_empty() && self.unknown.is_empty()
+    }
+}
+
+/// Compares the tags with a roster
+pub struct Reconciler<'a> {
+    roster: &'a Roster,
+    /// Index of the first car of each mark and number in the roster
+    index: HashMap<(&'a str, u32), usize>,
+    length_tolerance_dm: u16,
+}
+
+impl<'a> Reconciler<'a> {
+    pub fn new(roster: &'a Roster) -> Reconciler<'a> {
+        let mut index = HashMap::new();
+        for (i, car) in roster.cars().iter().enumerate() {
+            index.entry((car.mark.as_str(), car.number)).or_insert(i);
+        }
+        Reconciler {
+            roster,
+            index,
+            length_tolerance_dm: 0,
+        }
+    }
+
+    /// Largest difference of length, in decimeters, which is not reported
+    pub fn length_tolerance(mut self, length_tolerance_dm: u16) -> Reconciler<'a> {
+        self.length_tolerance_dm = length_tolerance_dm;
+        self
+    }
+
+    /// Fields of the tag which disagree with the roster car
+    pub fn compare(&self, car: &RosterCar, tag: &AEITagData) -> Vec<FieldMismatch> {
+        let mut fields = Vec::new();
+        let mut check = |field, roster: Option<u32>, value: u32, tolerance: u32| {
+            if let Some(roster) = roster.filter(|roster| roster.abs_diff(value) > tolerance) {
+                fields.push(FieldMismatch {
+                    field,
+                    roster,
+                    tag: value,
+                });
+            }
+        };
+
+        check(
+            Field::Length,
+            car.length_dm.map(u32::from),
+            u32::from(tag.length_dm()),
+            u32::from(self.length_tolerance_dm),
+        );
+        check(
+            Field::Axles,
+            car.axles.map(u32::from),
+            u32::from(tag.number_axles()),
+            0,
+        );
+        check(
+            Field::Group,
+            car.group.map(u32::from),
+            u32::from(tag.equipment_group_code()),
+            0,
+        );
+        check(
+            Field::Bearing,
+            car.bearing.map(u32::from),
+            u32::from(tag.bearing_type_code()),
+            0,
+        );
+        fields
+    }
+
+    /// Reconciles the tags with the roster. A tag read several times is reported once
+    pub fn reconcile<I: IntoIterator<Item = AEITagData>>(&self, tags: I) -> Reconciliation {
+        let mut result = Reconciliation::default();
+        let mut seen_raw: HashSet<[u8; 16]> = HashSet::new();
+        let mut seen_cars = vec![false; self.roster.cars().len()];
+
+        for tag in tags {
+            if !seen_raw.insert(*tag.raw()) {
+                continue;
+            }
+
+            let mark = tag.equipment_initial();
+            match self.index.get(&(mark.trim_end(), tag.car_number())) {
+                Some(&index) => {
+                    seen_cars[index] = true;
+                    let fields = self.compare(&self.roster.cars()[index], &tag);
+                    if fields.is_empty() {
+                        result.matched += 1;
+                    } else {
+                        result.mismatches.push(CarMismatch {
+                            seen: SeenTag::from(&tag),
+                            fields,
+                        });
+                    }
+                }
+                None => result.unknown.push(SeenTag::from(&tag)),
+            }
+        }
+
+        result.unseen = self
+            .roster
+            .cars()
+            .iter()
+            .zip(seen_cars)
+            .filter(|(_, seen)| !seen)
+            .map(|(car, _)| car.clone())
+            .collect();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// QNSL 502 RIGHT Locomotive(5) 94' 0" 4 axles
+    static TAG1: &str = "2F3E06C007DB1E139000000000000331";
+    /// IOCC 3088 RIGHT Railcar(19) 35' 0" 4 axles
+    static TAG2: &str = "9EA488C030426A179000000000000331";
+    /// IOCC 85123 LEFT Railcar(19) 63' 0" 4 axles
+    static TAG3: &str = "9EA488C5320CC01B9000000000000331";
+
+    static ROSTER: &str = "mark;number;length_dm;axles;group;bearing
+IOCC;3088;106;4;19;1
+IOCC;85123;190;6;19;
+IOCC;99999;;;;
+";
+
+    fn tags(tags: &[&str]) -> Vec<AEITagData> {
+        tags.iter()
+            .map(|tag| AEITagData::new(tag).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn field_by_field() {
+        let roster = Roster::from_csv(ROSTER).unwrap();
+        let result = Reconciler::new(&roster).reconcile(tags(&[TAG1, TAG2, TAG3, TAG2]));
+
+        assert_eq!(result.matched, 1);
+        assert_eq!(result.mismatches.len(), 1);
+        assert_eq!(
+            result.mismatches[0].to_string(),
+            "IOCC 85123 (Left) : length roster 190, tag 192; axles roster 6, tag 4"
+        );
+        assert_eq!(result.unseen.len(), 1);
+        assert_eq!(result.unseen[0].number, 99999);
+        assert_eq!(result.unknown.len(), 1);
+        assert_eq!(result.unknown[0].to_string(), "QNSL 502 (Right)");
+        assert!(!result.is_clean());
+    }
+
+    #[test]
+    fn length_tolerance() {
+        let roster = Roster::from_csv(ROSTER).unwrap();
+        let result = Reconciler::new(&roster)
+            .length_tolerance(2)
+            .reconcile(tags(&[TAG3]));
+
+        assert_eq!(
+            result.mismatches[0].fields,
+            vec![FieldMismatch {
+                field: Field::Axles,
+                roster: 6,
+                tag: 4
+            }]
+        );
+    }
+
+    #[test]
+    fn clean_reconciliation() {
+        let roster = Roster::from_csv("mark;number;length_dm\nIOCC;3088;106\n").unwrap();
+        let result = Reconciler::new(&roster).reconcile(tags(&[TAG2]));
+
+        assert!(result.is_clean());
+        assert_eq!(result.matched, 1);
+    }
+}
@@ -0,0 +1,351 @@
+//! Local registry of the reporting marks
+//!
+//! A [`MarkRegistry`] holds the owner of the reporting marks, loaded from a CSV or TOML file
+//! supplied by the user. Once applied to a tag, the owner shows up in every output format and
+//! marks missing from the registry are flagged as unknown.
+//!
+//! CSV files need a header line, the delimiter being `;` or `,` :
+//! ```text
+//! mark;owner;country;kind;active_from;active_to
+//! IOCC;Iron Ore Company of Canada;CA;railroad;1954-01-01;
+//! GATX;GATX Corporation;US;private;;
+//! ```
+//!
+//! TOML files list the marks in a `marks` array :
+//! ```toml
+//! [[marks]]
+//! mark = "IOCC"
+//! owner = "Iron Ore Company of Canada"
+//! country = "CA"
+//! kind = "railroad"
+//! active_from = "1954-01-01"
+//! ```
+
+use crate::AEITagData;
+use chrono::{NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, error, fmt::Display, fs, io, path::Path};
+
+#[derive(Debug)]
+pub enum RegistryError {
+    Io(io::Error),
+    Csv(csv::Error),
+    Toml(toml::de::Error),
+}
+
+impl From<io::Error> for RegistryError {
+    fn from(err: io::Error) -> Self {
+        RegistryError::Io(err)
+    }
+}
+
+impl From<csv::Error> for RegistryError {
+    fn from(err: csv::Error) -> Self {
+        RegistryError::Csv(err)
+    }
+}
+
+impl From<toml::de::Error> for RegistryError {
+    fn from(err: toml::de::Error) -> Self {
+        RegistryError::Toml(err)
+    }
+}
+
+impl Display for RegistryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegistryError::Io(e) => write!(f, "the registry couldn't be read : {}", e),
+            RegistryError::Csv(e) => write!(f, "the registry isn't valid CSV : {}", e),
+            RegistryError::Toml(e) => write!(f, "the registry isn't valid TOML : {}", e),
+        }
+    }
+}
+
+impl error::Error for RegistryError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            RegistryError::Io(e) => Some(e),
+            RegistryError::Csv(e) => Some(e),
+            RegistryError::Toml(e) => Some(e),
+        }
+    }
+}
+
+/// Kind of owner of a mark
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OwnerKind {
+    Railroad,
+    Private,
+}
+
+impl Display for OwnerKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            OwnerKind::Railroad => write!(f, "Railroad"),
+            OwnerKind::Private => write!(f, "Private"),
+        }
+    }
+}
+
+/// Owner of a mark over a period of time
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct MarkOwner {
+    pub mark: String,
+    pub owner: String,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub country: Option<String>,
+    #[serde(default)]
+    pub kind: Option<OwnerKind>,
+    #[serde(default)]
+    pub active_from: Option<NaiveDate>,
+    #[serde(default)]
+    pub active_to: Option<NaiveDate>,
+}
+
+impl MarkOwner {
+    /// Check if the owner held the mark on the given date. Missing dates are unbounded
+    pub fn active_on(&self, date: NaiveDate) -> bool {
+        self.active_from.is_none_or(|from| from <= date)
+            && self.active_to.is_none_or(|to| date <= to)
+    }
+}
+
+impl Display for MarkOwner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.owner)?;
+        if let Some(country) = &self.country {
+            write!(f, " ({})", country)?;
+        }
+        Ok(())
+    }
+}
+
+fn empty_as_none<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    let value = Option::<String>::deserialize(deserializer)?;
+    Ok(value.filter(|value| !value.is_empty()))
+}
+
+/// Result of the lookup of a tag mark in the registry
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MarkLookup {
+    Known(MarkOwner),
+    Unknown,
+}
+
+impl Display for MarkLookup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MarkLookup::Known(owner) => write!(f, "{}", owner),
+            MarkLookup::Unknown => write!(f, "Unknown mark"),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TomlRegistry {
+    #[serde(default)]
+    marks: Vec<MarkOwner>,
+}
+
+/// Owners of the reporting marks, keyed by mark
+#[derive(Clone, Debug, Default)]
+pub struct MarkRegistry {
+    marks: HashMap<String, Vec<MarkOwner>>,
+}
+
+impl MarkRegistry {
+    pub fn new() -> MarkRegistry {
+        MarkRegistry::default()
+    }
+
+    /// Loads a registry file, TOML when its extension is `.toml` and CSV otherwise
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<MarkRegistry, RegistryError> {
+        let data = fs::read_to_string(&path)?;
+        match path.as_ref().extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => MarkRegistry::from_toml(&data),
+            _ => MarkRegistry::from_csv(&data),
+        }
+    }
+
+    /// Loads the marks from CSV data with a header line
+    pub fn from_csv(data: &str) -> Result<MarkRegistry, RegistryError> {
+        let mut registry = MarkRegistry::new();
+        for owner in crate::csv_input::records(data)? {
+            registry.insert(owner);
+        }
+        Ok(registry)
+    }
+
+    /// Loads the marks from the `marks` array of a TOML document
+    pub fn from_toml(data: &str) -> Result<MarkRegistry, RegistryError> {
+        let mut registry = MarkRegistry::new();
+        for owner in toml::from_str::<TomlRegistry>(data)?.marks {
+            registry.insert(owner);
+        }
+        Ok(registry)
+    }
+
+    /// Adds an owner of a mark
+    pub fn insert(&mut self, owner: MarkOwner) {
+        self.marks
+            .entry(owner.mark.trim().to_uppercase())
+            .or_default()
+            .push(owner);
+    }
+
+    /// Number of marks in the registry
+    pub fn len(&self) -> usize {
+        self.marks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.marks.is_empty()
+    }
+
+    /// Owner of the mark on the given date
+    pub fn lookup_at(&self, mark: &str, date: NaiveDate) -> Option<&MarkOwner> {
+        self.marks
+            .get(&mark.trim().to_uppercase())?
+            .iter()
+            .find(|owner| owner.active_on(date))
+    }
+
+    /// Current owner of the mark
+    pub fn lookup(&self, mark: &str) -> Option<&MarkOwner> {
+        self.lookup_at(mark, Utc::now().date_naive())
+    }
+
+    /// Sets the current owner of the tag mark, or flags the mark as unknown
+    pub fn apply(&self, tag: &mut AEITagData) {
+        tag.mark_owner = Some(match self.lookup(&tag.equipment_initial()) {
+            Some(owner) => MarkLookup::Known(owner.clone()),
+            None => MarkLookup::Unknown,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static CSV: &str = "mark;owner;country;kind;active_from;active_to
+# Current owner
+IOCC;Iron Ore Company of Canada;CA;railroad;1954-01-01;
+QNSL;Old owner;CA;railroad;1954-01-01;1990-12-31
+QNSL;Quebec North Shore and Labrador Railway;CA;railroad;1991-01-01;
+GATX;GATX Corporation;;private;;
+";
+
+    #[test]
+    fn csv_registry() {
+        let registry = MarkRegistry::from_csv(CSV).unwrap();
+
+        assert_eq!(registry.len(), 3);
+        let gatx = registry.lookup("gatx").unwrap();
+        assert_eq!(gatx.kind, Some(OwnerKind::Private));
+        assert_eq!(gatx.country, None);
+        assert_eq!(
+            registry.lookup("QNSL").unwrap().owner,
+            "Quebec North Shore and Labrador Railway"
+        );
+        assert_eq!(
+            registry
+                .lookup_at("QNSL", NaiveDate::from_ymd_opt(1980, 6, 1).unwrap())
+                .unwrap()
+                .owner,
+            "Old owner"
+        );
+        assert!(registry.lookup("UP").is_none());
+    }
+
+    #[test]
+    fn comma_separated_registry() {
+        let registry =
+            MarkRegistry::from_csv("mark,owner\nIOCC,\"Iron Ore Company of Canada, Ltd\"\n")
+                .unwrap();
+
+        assert_eq!(
+            registry.lookup("IOCC").unwrap().owner,
+            "Iron Ore Company of Canada, Ltd"
+        );
+    }
+
+    #[test]
+    fn quoted_owner() {
+        let registry =
+            MarkRegistry::from_csv("mark,owner\nIOCC,\"Iron Ore Company; \"\"IOC\"\"\"\n").unwrap();
+        let mut tag = AEITagData::new("9EA488C030426A179000000000000331").unwrap();
+        registry.apply(&mut tag);
+
+        let csv = tag.to_csv();
+        assert!(csv.ends_with(";\"Iron Ore Company; \"\"IOC\"\"\""));
+        let record = csv::ReaderBuilder::new()
+            .delimiter(b';')
+            .has_headers(false)
+            .from_reader(csv.as_bytes())
+            .records()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.len(), 9);
+        assert_eq!(&record[8], "Iron Ore Company; \"IOC\"");
+    }
+
+    #[test]
+    fn toml_registry() {
+        let registry = MarkRegistry::from_toml(
+            r#"
+            [[marks]]
+            mark = "IOCC"
+            owner = "Iron Ore Company of Canada"
+            country = "CA"
+            kind = "railroad"
+            active_from = "1954-01-01"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            registry.lookup("IOCC").unwrap().to_string(),
+            "Iron Ore Company of Canada (CA)"
+        );
+    }
+
+    #[test]
+    fn invalid_registry() {
+        let err = MarkRegistry::from_csv("mark;owner;kind\nIOCC;IOC;railway\n").unwrap_err();
+        assert!(matches!(err, RegistryError::Csv(..)));
+    }
+
+    #[test]
+    fn tag_owner() {
+        let registry = MarkRegistry::from_csv(CSV).unwrap();
+        let mut known = AEITagData::new("9EA488C030426A179000000000000331").unwrap();
+        let mut unknown = known.clone();
+        unknown.with_equipment_initial("UP").unwrap();
+        registry.apply(&mut known);
+        registry.apply(&mut unknown);
+
+        assert!(known
+            .to_short_string()
+            .ends_with("\tOwner : Iron Ore Company of Canada (CA)"));
+        assert!(known
+            .to_csv()
+            .ends_with(";Railroad;Iron Ore Company of Canada"));
+        assert!(known.to_json().contains(
+            r#""mark_owner":{"known":{"mark":"IOCC","owner":"Iron Ore Company of Canada""#
+        ));
+        assert!(unknown
+            .to_short_string()
+            .ends_with("\tOwner : Unknown mark"));
+        assert!(unknown.to_json().contains(r#""mark_owner":"unknown""#));
+
+        // Re-marking the tag discards the owner of the former mark
+        known.with_equipment_initial("GATX").unwrap();
+        assert_eq!(known.mark_owner(), None);
+    }
+}
@@ -0,0 +1,156 @@
+//! Fleet roster
+//!
+//! A [`Roster`] lists the cars of a fleet with the data expected on their tags. It is read from a
+//! CSV file with a header line, the delimiter being `;` or `,`. Only the `mark` and `number`
+//! columns are required :
+//! ```text
+//! mark;number;length_dm;axles;group;bearing
+//! IOCC;3088;106;4;19;1
+//! IOCC;85123;192;4;19;
+//! ```
+
+use crate::AEITagData;
+use serde::{Deserialize, Serialize};
+use std::{error, fmt::Display, fs, io, path::Path};
+
+#[derive(Debug)]
+pub enum RosterError {
+    Io(io::Error),
+    Csv(csv::Error),
+}
+
+impl From<io::Error> for RosterError {
+    fn from(err: io::Error) -> Self {
+        RosterError::Io(err)
+    }
+}
+
+impl From<csv::Error> for RosterError {
+    fn from(err: csv::Error) -> Self {
+        RosterError::Csv(err)
+    }
+}
+
+impl Display for RosterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RosterError::Io(e) => write!(f, "the roster couldn't be read : {}", e),
+            RosterError::Csv(e) => write!(f, "the roster isn't valid CSV : {}", e),
+        }
+    }
+}
+
+impl error::Error for RosterError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            RosterError::Io(e) => Some(e),
+            RosterError::Csv(e) => Some(e),
+        }
+    }
+}
+
+/// Car of the roster. A missing value is not checked
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RosterCar {
+    pub mark: String,
+    pub number: u32,
+    #[serde(default)]
+    pub length_dm: Option<u16>,
+    #[serde(default)]
+    pub axles: Option<u8>,
+    #[serde(default)]
+    pub group: Option<u8>,
+    #[serde(default)]
+    pub bearing: Option<u8>,
+}
+
+impl RosterCar {
+    /// Check if the tag belongs to this car, comparing the equipment initial and the car number
+    pub fn matches(&self, tag: &AEITagData) -> bool {
+        self.number == tag.car_number() && self.mark == tag.equipment_initial().trim_end()
+    }
+}
+
+impl Display for RosterCar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.mark, self.number)
+    }
+}
+
+/// Cars of a fleet
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct Roster {
+    cars: Vec<RosterCar>,
+}
+
+impl Roster {
+    pub fn new(cars: Vec<RosterCar>) -> Roster {
+        Roster {
+            cars: cars
+                .into_iter()
+                .map(|car| RosterCar {
+                    mark: car.mark.trim().to_uppercase(),
+                    ..car
+                })
+                .collect(),
+        }
+    }
+
+    /// Loads a roster CSV file
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Roster, RosterError> {
+        Roster::from_csv(&fs::read_to_string(path)?)
+    }
+
+    /// Loads a roster from CSV data with a header line
+    pub fn from_csv(data: &str) -> Result<Roster, RosterError> {
+        Ok(Roster::new(crate::csv_input::records(data)?))
+    }
+
+    /// Cars of the roster, in the order of the file
+    pub fn cars(&self) -> &[RosterCar] {
+        &self.cars
+    }
+
+    /// Car the tag belongs to
+    pub fn find(&self, tag: &AEITagData) -> Option<&RosterCar> {
+        self.cars.iter().find(|car| car.matches(tag))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roster_parsing() {
+        let roster = Roster::from_csv(
+            "mark,number,length_dm,axles,group,bearing\niocc,3088,106,4,19,1\nIOCC,85123,,,,\n",
+        )
+        .unwrap();
+
+        assert_eq!(roster.cars().len(), 2);
+        assert_eq!(
+            roster.cars()[0],
+            RosterCar {
+                mark: String::from("IOCC"),
+                number: 3088,
+                length_dm: Some(106),
+                axles: Some(4),
+                group: Some(19),
+                bearing: Some(1),
+            }
+        );
+        assert_eq!(roster.cars()[1].length_dm, None);
+
+        let tag = AEITagData::new("9EA488C030426A179000000000000331").unwrap();
+        assert_eq!(roster.find(&tag), Some(&roster.cars()[0]));
+    }
+
+    #[test]
+    fn minimal_roster() {
+        let roster = Roster::from_csv("mark;number\nUP;12\n").unwrap();
+
+        assert_eq!(roster.cars()[0].to_string(), "UP 12");
+        assert!(Roster::from_csv("mark;number\nUP;twelve\n").is_err());
+    }
+}
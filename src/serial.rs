@@ -0,0 +1,78 @@
+//! Serial port input, available with the `serial` feature
+//!
+//! The legacy readers are connected over RS-232, 8 data bits, no parity and 1 stop bit. The frames
+//! they send are extracted by a [`FrameReader`] as they arrive.
+//!
+//! ```rust,no_run
+//! use aei_tag_parser::{framing::Framing, serial, stream::TagStream};
+//!
+//! let port = serial::open("/dev/ttyS0", 9600, Framing::default()).unwrap();
+//! for read in TagStream::new(port) {
+//!     match read {
+//!         Ok((_, tag)) => println!("{}", tag.to_short_string()),
+//!         Err(e) => eprintln!("{}", e),
+//!     }
+//! }
+//! ```
+
+use crate::framing::{FrameReader, Framing};
+use serialport::SerialPort;
+use std::time::Duration;
+
+pub use serialport::Error;
+
+/// Time waited for data before the read is retried
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Opens the serial port at the given speed
+pub fn open(
+    path: &str,
+    baud_rate: u32,
+    framing: Framing,
+) -> Result<FrameReader<Box<dyn SerialPort>>, Error> {
+    let port = serialport::new(path, baud_rate)
+        .data_bits(serialport::DataBits::Eight)
+        .parity(serialport::Parity::None)
+        .stop_bits(serialport::StopBits::One)
+        .flow_control(serialport::FlowControl::None)
+        .timeout(READ_TIMEOUT)
+        .open()?;
+    Ok(FrameReader::new(port, framing))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::stream::TagStream;
+    use serialport::TTYPort;
+    use std::{io::Write, thread};
+
+    #[test]
+    fn pseudo_terminal() {
+        // The master side stands in for the reader
+        let (mut master, slave) = TTYPort::pair().unwrap();
+        let path = slave.name().unwrap();
+        let port = open(&path, 9600, "stx,etx".parse().unwrap()).unwrap();
+
+        let writer = thread::spawn(move || {
+            for chunk in [
+                &b"\x029EA488C030"[..],
+                b"426A179000000000000331\x03\r\n\x02ZZ\x03",
+                b"\x022F3E06C007DB1E13",
+                b"\x02",
+                b"9EA488C5320CC01B9000000000000331\x03",
+            ] {
+                master.write_all(chunk).unwrap();
+                master.flush().unwrap();
+                thread::sleep(Duration::from_millis(20));
+            }
+            master
+        });
+
+        let reads: Vec<_> = TagStream::new(port).take(3).collect();
+        assert_eq!(reads[0].as_ref().unwrap().1.car_number(), 3088);
+        assert!(reads[1].is_err());
+        assert_eq!(reads[2].as_ref().unwrap().1.car_number(), 85123);
+        drop(writer.join().unwrap());
+    }
+}
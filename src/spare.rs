@@ -0,0 +1,225 @@
+//! Decoding of the owner defined spare bits
+//!
+//! Bits 81-93 are available for the owner's use and bits 106-117 are reserved for security or
+//! limited owner's use. Their content is not standardized, so every owner can describe it with a
+//! [`SpareFieldDecoder`] registered for its equipment initial in a [`SpareFieldRegistry`].
+//!
+//! ```rust
+//! use aei_tag_parser::spare::{BitFieldDecoder, SpareFieldRegistry, SpareValue};
+//!
+//! let mut registry = SpareFieldRegistry::new();
+//! registry.register("IOCC", BitFieldDecoder::new().field("fleet", &[81..=88]));
+//!
+//! let tag = registry.decode("9EA488C030426A179000000000000331").unwrap();
+//! assert_eq!(tag.spare_fields()[0].name, "fleet");
+//! assert_eq!(tag.spare_fields()[0].value, SpareValue::Unsigned(0));
+//! ```
+
+use crate::{AEITagData, NewTagError};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt::Display, ops::RangeInclusive};
+
+/// Bits available for the owner's use
+pub const OWNER_BITS: RangeInclusive<usize> = 81..=93;
+/// Bits reserved for security or limited owner's use
+pub const SECURITY_BITS: RangeInclusive<usize> = 106..=117;
+
+/// Typed value of an owner defined field
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SpareValue {
+    Bool(bool),
+    Unsigned(u64),
+    Signed(i64),
+    Text(String),
+}
+
+impl Display for SpareValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpareValue::Bool(value) => write!(f, "{}", value),
+            SpareValue::Unsigned(value) => write!(f, "{}", value),
+            SpareValue::Signed(value) => write!(f, "{}", value),
+            SpareValue::Text(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// Named owner defined field
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct SpareField {
+    pub name: String,
+    pub value: SpareValue,
+}
+
+impl SpareField {
+    pub fn new(name: &str, value: SpareValue) -> SpareField {
+        SpareField {
+            name: String::from(name),
+            value,
+        }
+    }
+}
+
+impl Display for SpareField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={}", self.name, self.value)
+    }
+}
+
+/// Turns the spare bits of a tag into owner defined fields
+pub trait SpareFieldDecoder: Send + Sync {
+    fn decode(&self, tag: &AEITagData) -> Vec<SpareField>;
+}
+
+/// Decoder extracting each field as an unsigned integer from a list of bit ranges
+#[derive(Clone, Debug, Default)]
+pub struct BitFieldDecoder {
+    fields: Vec<(String, Vec<RangeInclusive<usize>>)>,
+}
+
+impl BitFieldDecoder {
+    pub fn new() -> BitFieldDecoder {
+        BitFieldDecoder::default()
+    }
+
+    /// Adds a field made of the given bit ranges, see [`AEITagData::bits_multi`]. A field whose
+    /// ranges are out of the tag or wider than 64 bits is left out of the decoded fields
+    pub fn field(mut self, name: &str, ranges: &[RangeInclusive<usize>]) -> BitFieldDecoder {
+        self.fields.push((String::from(name), ranges.to_vec()));
+        self
+    }
+}
+
+impl SpareFieldDecoder for BitFieldDecoder {
+    fn decode(&self, tag: &AEITagData) -> Vec<SpareField> {
+        self.fields
+            .iter()
+            .filter_map(|(name, ranges)| {
+                let value = tag.bits_multi(ranges).ok()?;
+                Some(SpareField::new(
+                    name,
+                    SpareValue::Unsigned(u64::try_from(value).ok()?),
+                ))
+            })
+            .collect()
+    }
+}
+
+/// Spare field decoders keyed by equipment initial
+#[derive(Default)]
+pub struct SpareFieldRegistry {
+    decoders: HashMap<String, Box<dyn SpareFieldDecoder>>,
+}
+
+impl SpareFieldRegistry {
+    pub fn new() -> SpareFieldRegistry {
+        SpareFieldRegistry::default()
+    }
+
+    /// Registers the decoder used for the tags of the given equipment initial. A decoder
+    /// previously registered for the same initial is replaced
+    pub fn register<D: SpareFieldDecoder + 'static>(
+        &mut self,
+        equipment_initial: &str,
+        decoder: D,
+    ) {
+        self.decoders
+            .insert(normalize_initial(equipment_initial), Box::new(decoder));
+    }
+
+    /// Decoder registered for the given equipment initial
+    pub fn get(&self, equipment_initial: &str) -> Option<&dyn SpareFieldDecoder> {
+        self.decoders
+            .get(&normalize_initial(equipment_initial))
+            .map(|decoder| decoder.as_ref())
+    }
+
+    /// Fills the spare fields of the tag with the decoder registered for its equipment initial.
+    /// The spare fields are cleared when there is none
+    pub fn apply(&self, tag: &mut AEITagData) {
+        tag.spare_fields = match self.get(&tag.equipment_initial()) {
+            Some(decoder) => decoder.decode(tag),
+            None => Vec::new(),
+        };
+    }
+
+    /// Parses a tag and decodes its spare fields
+    pub fn decode(&self, tag: &str) -> Result<AEITagData, NewTagError> {
+        let mut tag = AEITagData::new(tag)?;
+        self.apply(&mut tag);
+        Ok(tag)
+    }
+}
+
+fn normalize_initial(equipment_initial: &str) -> String {
+    equipment_initial.trim().to_uppercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// IOCC 3088 RIGHT Railcar(19) 35' 0" 4 axles, with 0xA5 in bits 81-88 and 0x5 in bits 106-109
+    static TAG_WITH_SPARE: &str = "9EA488C030426A179000528000140339";
+
+    struct FleetDecoder;
+
+    impl SpareFieldDecoder for FleetDecoder {
+        fn decode(&self, tag: &AEITagData) -> Vec<SpareField> {
+            let fleet = tag.bits(81..=88).unwrap();
+            vec![
+                SpareField::new("fleet", SpareValue::Text(format!("F{:02X}", fleet))),
+                SpareField::new(
+                    "leased",
+                    SpareValue::Bool(tag.bits(106..=106).unwrap() == 0),
+                ),
+            ]
+        }
+    }
+
+    #[test]
+    fn bit_field_decoder() {
+        let mut registry = SpareFieldRegistry::new();
+        registry.register(
+            "iocc",
+            BitFieldDecoder::new()
+                .field("fleet", &[81..=88])
+                .field("security", &[106..=109]),
+        );
+
+        let tag = registry.decode(TAG_WITH_SPARE).unwrap();
+        assert_eq!(
+            tag.spare_fields(),
+            &[
+                SpareField::new("fleet", SpareValue::Unsigned(0xA5)),
+                SpareField::new("security", SpareValue::Unsigned(0x5)),
+            ]
+        );
+        assert!(tag.to_csv().ends_with(";fleet=165,security=5"));
+        assert!(tag
+            .to_json()
+            .contains(r#""spare_fields":[{"name":"fleet","value":165}"#));
+    }
+
+    #[test]
+    fn custom_decoder() {
+        let mut registry = SpareFieldRegistry::new();
+        registry.register("IOCC", FleetDecoder);
+
+        let tag = registry.decode(TAG_WITH_SPARE).unwrap();
+        assert!(tag
+            .to_short_string()
+            .ends_with("\tOwner data : fleet=FA5,leased=true"));
+    }
+
+    #[test]
+    fn unregistered_initial() {
+        let mut registry = SpareFieldRegistry::new();
+        registry.register("QNSL", FleetDecoder);
+
+        let tag = registry.decode(TAG_WITH_SPARE).unwrap();
+        assert!(tag.spare_fields().is_empty());
+        assert!(!tag.to_json().contains("spare_fields"));
+    }
+}
@@ -0,0 +1,300 @@
+//! Streaming decoding of tags
+//!
+//! A [`TagStream`] decodes the tags of any [`BufRead`], one per line, as they are read. Only the
+//! current line, up to [`MAX_LINE_LENGTH`] bytes, is kept in memory so that logs of any size can be
+//! piped through. Blank lines and lines starting with `#` are skipped.
+//!
+//! ```rust
+//! use aei_tag_parser::stream::TagStream;
+//!
+//! let data = "# Sept-Iles\n9EA488C030426A179000000000000331\n\n9EA4\n";
+//! let mut stream = TagStream::new(data.as_bytes());
+//!
+//! let (line, tag) = stream.next().unwrap().unwrap();
+//! assert_eq!((line, tag.car_number()), (2, 3088));
+//! assert_eq!(stream.next().unwrap().unwrap_err().line(), 4);
+//! assert!(stream.next().is_none());
+//! ```
+
+use crate::{AEITagData, NewTagError};
+use std::{error, fmt::Display, io, io::BufRead};
+
+/// Error on a line of the stream
+#[derive(Debug)]
+pub enum LineError {
+    /// The line couldn't be read. The stream ends after such an error, except when the line
+    /// isn't valid UTF-8 or is longer than [`MAX_LINE_LENGTH`]
+    Io(usize, io::Error),
+    /// The line isn't a valid tag
+    Tag(usize, String, NewTagError),
+    /// The timestamp sent with the tag isn't valid
+    Timestamp(usize, String, chrono::ParseError),
+    /// The tag given as an argument of the command line, numbered from 1, isn't valid
+    Argument(usize, String, NewTagError),
+}
+
+impl LineError {
+    /// Number of the line, or of the argument, starting at 1
+    pub fn line(&self) -> usize {
+        match self {
+            LineError::Io(line, _)
+            | LineError::Tag(line, _, _)
+            | LineError::Timestamp(line, _, _)
+            | LineError::Argument(line, _, _) => *line,
+        }
+    }
+}
+
+impl Display for LineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LineError::Io(line, e) => write!(f, "line {} : {}", line, e),
+            LineError::Tag(line, tag, e) => write!(f, "line {} : {} : {}", line, tag, e),
+            LineError::Timestamp(line, timestamp, e) => {
+                write!(f, "line {} : invalid timestamp {} : {}", line, timestamp, e)
+            }
+            LineError::Argument(arg, tag, e) => write!(f, "argument {} : {} : {}", arg, tag, e),
+        }
+    }
+}
+
+impl error::Error for LineError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            LineError::Io(_, e) => Some(e),
+            LineError::Tag(_, _, e) | LineError::Argument(_, _, e) => Some(e),
+            LineError::Timestamp(_, _, e) => Some(e),
+        }
+    }
+}
+
+/// Decodes a line, returning `None` for blank lines and comments
+pub(crate) fn parse_line(
+    line_no: usize,
+    line: &[u8],
+) -> Option<Result<(usize, AEITagData), LineError>> {
+    let line = match std::str::from_utf8(line) {
+        Ok(line) => line.trim(),
+        Err(e) => {
+            return Some(Err(LineError::Io(
+                line_no,
+                io::Error::new(io::ErrorKind::InvalidData, e),
+            )))
+        }
+    };
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    Some(match AEITagData::new(line) {
+        Ok(tag) => Ok((line_no, tag)),
+        Err(e) => Err(LineError::Tag(line_no, String::from(line), e)),
+    })
+}
+
+/// Longest line kept in memory, in bytes, the longer lines being reported as errors
+pub const MAX_LINE_LENGTH: usize = 1024;
+
+/// Reads the lines of a [`BufRead`] keeping at most [`MAX_LINE_LENGTH`] bytes of each, so that an
+/// input without line breaks doesn't grow the memory. The stream ends after a read error
+pub(crate) struct LineReader<R: BufRead> {
+    reader: R,
+    buffer: Vec<u8>,
+    line: usize,
+    done: bool,
+}
+
+impl<R: BufRead> LineReader<R> {
+    pub(crate) fn new(reader: R) -> LineReader<R> {
+        LineReader {
+            reader,
+            buffer: Vec::new(),
+            line: 0,
+            done: false,
+        }
+    }
+
+    /// Number of the last line read
+    pub(crate) fn line(&self) -> usize {
+        self.line
+    }
+
+    pub(crate) fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Next line with its number, line break included. A line longer than the limit is skipped up
+    /// to its end and reported as invalid data
+    pub(crate) fn next_line(&mut self) -> Option<Result<(usize, &[u8]), LineError>> {
+        if self.done {
+            return None;
+        }
+        self.buffer.clear();
+        let mut length = 0;
+        loop {
+            let available = match self.reader.fill_buf() {
+                Ok(available) => available,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(LineError::Io(self.line + 1, e)));
+                }
+            };
+            if available.is_empty() {
+                self.done = true;
+                if length == 0 {
+                    return None;
+                }
+                break;
+            }
+
+            let (chunk, end) = match available.iter().position(|b| *b == b'\n') {
+                Some(i) => (&available[..=i], true),
+                None => (available, false),
+            };
+            let room = MAX_LINE_LENGTH.saturating_sub(self.buffer.len());
+            self.buffer
+                .extend_from_slice(&chunk[..chunk.len().min(room)]);
+            let used = chunk.len();
+            length += used;
+            self.reader.consume(used);
+            if end {
+                break;
+            }
+        }
+
+        self.line += 1;
+        if length > MAX_LINE_LENGTH {
+            return Some(Err(LineError::Io(
+                self.line,
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line longer than {} bytes", MAX_LINE_LENGTH),
+                ),
+            )));
+        }
+        Some(Ok((self.line, &self.buffer)))
+    }
+}
+
+/// Iterator over the tags of a reader, yielding each tag with its line number
+pub struct TagStream<R: BufRead> {
+    lines: LineReader<R>,
+}
+
+impl<R: BufRead> TagStream<R> {
+    pub fn new(reader: R) -> TagStream<R> {
+        TagStream {
+            lines: LineReader::new(reader),
+        }
+    }
+
+    /// Number of the last line read
+    pub fn line(&self) -> usize {
+        self.lines.line()
+    }
+
+    /// Gives back the underlying reader
+    pub fn into_inner(self) -> R {
+        self.lines.into_inner()
+    }
+}
+
+impl<R: BufRead> Iterator for TagStream<R> {
+    type Item = Result<(usize, AEITagData), LineError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (line, data) = match self.lines.next_line()? {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e)),
+            };
+            if let Some(read) = parse_line(line, data) {
+                return Some(read);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufReader, Read};
+
+    #[test]
+    fn skips_blanks_and_comments() {
+        let data = "2F3E06C007DB1E139000000000000331\r\n  \n# comment\n  9EA488C5320CC01B9000000000000331  \n9EA488C030426A179000000000000331";
+        let lines: Vec<(usize, u32)> = TagStream::new(data.as_bytes())
+            .map(|read| read.map(|(line, tag)| (line, tag.car_number())).unwrap())
+            .collect();
+
+        assert_eq!(lines, vec![(1, 502), (4, 85123), (5, 3088)]);
+    }
+
+    #[test]
+    fn invalid_lines() {
+        let data: &[u8] = b"ZZ\n\xFF\xFE\n9EA488C030426A179000000000000331\n";
+        let mut stream = TagStream::new(data);
+
+        let e = stream.next().unwrap().unwrap_err();
+        assert!(matches!(e, LineError::Tag(1, ref tag, _) if tag == "ZZ"));
+        let e = stream.next().unwrap().unwrap_err();
+        assert!(matches!(e, LineError::Io(2, _)));
+        assert_eq!(stream.next().unwrap().unwrap().0, 3);
+        assert!(stream.next().is_none());
+        assert_eq!(stream.line(), 3);
+    }
+
+    /// Reader failing after its data
+    struct Failing<'a>(&'a [u8]);
+
+    impl Read for Failing<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() {
+                return Err(io::Error::other("disconnected"));
+            }
+            self.0.read(buf)
+        }
+    }
+
+    #[test]
+    fn long_lines() {
+        let data = format!(
+            "{}\n9EA488C030426A179000000000000331\n{}",
+            "F".repeat(MAX_LINE_LENGTH + 10),
+            "0".repeat(MAX_LINE_LENGTH * 3)
+        );
+        let reader = BufReader::with_capacity(64, data.as_bytes());
+        let results: Vec<_> = TagStream::new(reader).collect();
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(
+            results[0].as_ref().unwrap_err().to_string(),
+            format!("line 1 : line longer than {} bytes", MAX_LINE_LENGTH)
+        );
+        assert_eq!(results[1].as_ref().unwrap().0, 2);
+        assert_eq!(results[2].as_ref().unwrap_err().line(), 3);
+    }
+
+    #[test]
+    fn stops_on_read_error() {
+        let reader = BufReader::with_capacity(8, Failing(b"9EA488C030426A179000000000000331\n"));
+        let results: Vec<_> = TagStream::new(reader).collect();
+
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        assert_eq!(
+            results[1].as_ref().unwrap_err().to_string(),
+            "line 2 : disconnected"
+        );
+    }
+
+    #[test]
+    fn argument_errors() {
+        let e = AEITagData::new("9EA4").unwrap_err();
+        let e = LineError::Argument(2, String::from("9EA4"), e);
+
+        assert_eq!(e.line(), 2);
+        assert!(e.to_string().starts_with("argument 2 : 9EA4 : "));
+    }
+}
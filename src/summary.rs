@@ -0,0 +1,309 @@
+//! Totals of a train passage
+//!
+//! A [`Summary`] counts the vehicles of a consist by equipment group and adds up their axles and
+//! lengths as written on their tags, so that the train can be compared with its train list and
+//! with the length of the sidings. Only the railcars, locomotives and rail-compatible multimodal
+//! equipment are vehicles : the end-of-train device is only reported as present, and the other
+//! tags read on the train, such as those of the intermodal containers, railcar covers or train
+//! number tags, are counted apart without adding to the axles or the length.
+//!
+//! ```rust
+//! use aei_tag_parser::{consist::ConsistBuilder, read::TagRead, summary::Summary, AEITagData};
+//! use chrono::Utc;
+//!
+//! let mut builder = ConsistBuilder::new();
+//! for tag in ["2F3E06C007DB1E139000000000000331", "9EA488C030426A179000000000000331"] {
+//!     builder.push(TagRead::new(AEITagData::new(tag).unwrap(), Utc::now(), "R1"));
+//! }
+//! let summary = Summary::from_consist(&builder.build());
+//!
+//! assert_eq!((summary.vehicles, summary.locomotives, summary.axles), (2, 1, 8));
+//! assert_eq!(summary.length_m(), 39.2);
+//! ```
+
+use crate::{
+    consist::Consist,
+    encode::RAILCAR_GROUP,
+    length::{Units, DM_PER_FOOT},
+    orientation::LOCOMOTIVE_GROUP,
+    train::Passage,
+    AEITagData,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+/// Equipment group code of the end-of-train devices
+pub const EOT_GROUP: u8 = 6;
+/// Equipment group code of the rail-compatible multimodal equipment
+pub const MULTIMODAL_GROUP: u8 = 24;
+
+/// Check if the equipment group is that of a vehicle of the train : a railcar, a locomotive or
+/// rail-compatible multimodal equipment. The tags of the other groups are carried by a vehicle
+/// or by the train and have no axles of their own.
+pub fn is_vehicle_group(code: u8) -> bool {
+    matches!(code, LOCOMOTIVE_GROUP | RAILCAR_GROUP | MULTIMODAL_GROUP)
+}
+
+/// Number of vehicles or tags of an equipment group
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct GroupCount {
+    pub code: u8,
+    pub group: String,
+    pub count: usize,
+}
+
+/// Totals of a train
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct Summary {
+    /// First read of the passage
+    pub start: Option<DateTime<Utc>>,
+    /// Last read of the passage
+    pub end: Option<DateTime<Utc>>,
+    /// Number of vehicles, see [`is_vehicle_group`]
+    pub vehicles: usize,
+    /// Vehicles by equipment group, ordered by group code
+    pub groups: Vec<GroupCount>,
+    pub locomotives: usize,
+    pub axles: u32,
+    /// Length of the train, in decimeters
+    pub length_dm: u32,
+    /// An end-of-train device was read
+    pub eot: bool,
+    /// Tags other than those of vehicles and of the end-of-train device, such as containers or
+    /// railcar covers, by equipment group
+    #[serde(default)]
+    pub other_tags: Vec<GroupCount>,
+}
+
+impl Summary {
+    /// Totals of the vehicles of a consist
+    pub fn from_consist(consist: &Consist) -> Summary {
+        let mut summary = Summary {
+            start: consist.vehicles.iter().map(|v| v.first_seen()).min(),
+            end: consist.vehicles.iter().map(|v| v.last_seen).max(),
+            ..Summary::default()
+        };
+
+        for vehicle in &consist.vehicles {
+            let tag = vehicle.tag();
+            let code = tag.equipment_group_code();
+            if code == EOT_GROUP {
+                summary.eot = true;
+                continue;
+            }
+            if !is_vehicle_group(code) {
+                add_group(&mut summary.other_tags, code, tag);
+                continue;
+            }
+
+            summary.vehicles += 1;
+            summary.axles += u32::from(tag.number_axles());
+            summary.length_dm += u32::from(tag.length_dm());
+            if code == LOCOMOTIVE_GROUP {
+                summary.locomotives += 1;
+            }
+            add_group(&mut summary.groups, code, tag);
+        }
+        summary.groups.sort_by_key(|g| g.code);
+        summary.other_tags.sort_by_key(|g| g.code);
+        summary
+    }
+
+    /// Totals of the vehicles of a passage, its repeated reads being merged
+    pub fn from_passage(passage: &Passage) -> Summary {
+        Summary {
+            start: Some(passage.start),
+            end: Some(passage.end),
+            ..Summary::from_consist(&Consist::from_passage(passage))
+        }
+    }
+
+    /// Length of the train in meters
+    pub fn length_m(&self) -> f64 {
+        f64::from(self.length_dm) / 10.0
+    }
+
+    /// Length of the train in decimal feet
+    pub fn length_ft(&self) -> f64 {
+        f64::from(self.length_dm) / DM_PER_FOOT
+    }
+
+    /// Formats the length in the given unit system, meters or feet with one decimal
+    pub fn format_length(&self, units: Units) -> String {
+        match units {
+            Units::Metric => format!("{:.1} m", self.length_m()),
+            Units::Imperial => format!("{:.1} ft", self.length_ft()),
+        }
+    }
+
+    /// Returns the summary serialized in JSON, with the length in meters and feet
+    pub fn to_json(&self) -> String {
+        let mut value = serde_json::to_value(self).expect("a summary can always be serialized");
+        value["length_m"] = serde_json::json!(self.length_m());
+        value["length_ft"] = serde_json::json!((self.length_ft() * 10.0).round() / 10.0);
+        value.to_string()
+    }
+}
+
+/// Counts one more tag of the equipment group
+fn add_group(groups: &mut Vec<GroupCount>, code: u8, tag: &AEITagData) {
+    match groups.iter_mut().find(|g| g.code == code) {
+        Some(group) => group.count += 1,
+        None => groups.push(GroupCount {
+            code,
+            group: tag.equipment_group(),
+            count: 1,
+        }),
+    }
+}
+
+/// Count followed by the noun, in the plural unless the count is 1
+fn count(count: u64, noun: &str) -> String {
+    if count == 1 {
+        format!("1 {}", noun)
+    } else {
+        format!("{} {}s", count, noun)
+    }
+}
+
+impl Display for Summary {
+    /// Formats the summary on a line, followed by the vehicles of each group and the other tags
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let (Some(start), Some(end)) = (self.start, self.end) {
+            write!(
+                f,
+                "{} - {} : ",
+                start.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+                end.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+            )?;
+        }
+        write!(
+            f,
+            "{}, {}, {}, {} ({}), EOT {}",
+            count(self.vehicles as u64, "vehicle"),
+            count(self.locomotives as u64, "locomotive"),
+            count(u64::from(self.axles), "axle"),
+            self.format_length(Units::Metric),
+            self.format_length(Units::Imperial),
+            if self.eot { "present" } else { "missing" }
+        )?;
+        for group in &self.groups {
+            write!(f, "\n\t{}({}) : {}", group.group, group.code, group.count)?;
+        }
+        for group in &self.other_tags {
+            write!(
+                f,
+                "\n\t{}({}) : {}, not a vehicle",
+                group.group,
+                group.code,
+                count(group.count as u64, "tag")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{consist::ConsistBuilder, encode::TagBuilder, read::TagRead, AEITagData};
+    use chrono::{Duration, TimeZone};
+
+    static TAG1: &str = "2F3E06C007DB1E139000000000000331";
+    static TAG1_LEFT: &str = "2F3E06C007D91E1B9000000000000331";
+    static TAG2: &str = "9EA488C030426A179000000000000331";
+    static TAG3: &str = "9EA488C5320CC01B9000000000000331";
+
+    fn consist(tags: &[AEITagData]) -> Consist {
+        let start = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        let mut builder = ConsistBuilder::new();
+        for (i, tag) in tags.iter().enumerate() {
+            builder.push(TagRead::new(
+                tag.clone(),
+                start + Duration::seconds(i as i64),
+                "R1",
+            ));
+        }
+        builder.build()
+    }
+
+    #[test]
+    fn totals() {
+        let eot = TagBuilder::new("QNSL", 9001)
+            .group(EOT_GROUP)
+            .length_dm(0)
+            .build()
+            .unwrap();
+        let tags: Vec<AEITagData> = [TAG1, TAG1_LEFT, TAG2, TAG3]
+            .iter()
+            .map(|tag| AEITagData::new(tag).unwrap())
+            .chain([eot])
+            .collect();
+        let summary = Summary::from_consist(&consist(&tags));
+
+        assert_eq!(summary.vehicles, 3);
+        assert_eq!(summary.locomotives, 1);
+        assert_eq!(summary.axles, 12);
+        assert_eq!(summary.length_dm, 584);
+        assert!(summary.eot);
+        assert_eq!(
+            summary.groups,
+            vec![
+                GroupCount {
+                    code: 5,
+                    group: String::from("Locomotive"),
+                    count: 1
+                },
+                GroupCount {
+                    code: 19,
+                    group: String::from("Railcar"),
+                    count: 2
+                }
+            ]
+        );
+        assert_eq!(summary.length_m(), 58.4);
+        assert_eq!(summary.format_length(Units::Imperial), "191.6 ft");
+        assert_eq!(
+            summary.to_string(),
+            "2024-05-01T12:00:00Z - 2024-05-01T12:00:04Z : 3 vehicles, 1 locomotive, 12 axles, 58.4 m (191.6 ft), EOT present\n\tLocomotive(5) : 1\n\tRailcar(19) : 2"
+        );
+    }
+
+    #[test]
+    fn container_on_railcar() {
+        let container = AEITagData::new("56A488C030426A179000000000000331").unwrap();
+        let summary = Summary::from_consist(&consist(&[AEITagData::new(TAG2).unwrap(), container]));
+
+        assert_eq!(summary.vehicles, 1);
+        assert_eq!(summary.axles, 4);
+        assert_eq!(summary.length_dm, 106);
+        assert_eq!(summary.groups.len(), 1);
+        assert_eq!(
+            summary.other_tags,
+            vec![GroupCount {
+                code: 10,
+                group: String::from("Intermodal container"),
+                count: 1
+            }]
+        );
+        assert!(summary.to_string().ends_with(
+            " : 1 vehicle, 0 locomotives, 4 axles, 10.6 m (34.8 ft), EOT missing\n\tRailcar(19) : 1\n\tIntermodal container(10) : 1 tag, not a vehicle"
+        ));
+    }
+
+    #[test]
+    fn json() {
+        let summary = Summary::from_consist(&consist(&[AEITagData::new(TAG2).unwrap()]));
+        let json: serde_json::Value = serde_json::from_str(&summary.to_json()).unwrap();
+
+        assert_eq!(json["vehicles"], 1);
+        assert_eq!(json["eot"], false);
+        assert_eq!(json["length_m"], 10.6);
+        assert_eq!(json["length_ft"], 34.8);
+        assert_eq!(json["groups"][0]["group"], "Railcar");
+        assert!(summary
+            .to_string()
+            .ends_with(" : 1 vehicle, 0 locomotives, 4 axles, 10.6 m (34.8 ft), EOT missing\n\tRailcar(19) : 1"));
+    }
+}
@@ -0,0 +1,731 @@
+//! AAR T-94 style train consist reports
+//!
+//! A report is made of fixed-width records of 80 columns, padded with spaces : a train header, one
+//! equipment record per vehicle in the order of the train and a trailer. The timestamps are UTC,
+//! written as `YYYYMMDDHHMMSS`, and the numbers are padded with zeros.
+//!
+//! Train header :
+//!
+//! | Columns | Field                                                     |
+//! |---------|-----------------------------------------------------------|
+//! | 1-2     | `TH`                                                      |
+//! | 3-9     | Site ID, left justified                                   |
+//! | 10-23   | Arrival, first read of the train                          |
+//! | 24-37   | Departure, last read of the train                         |
+//! | 38      | Direction, `R` reference, `V` reverse or blank if unknown |
+//! | 39-41   | Vehicle count                                             |
+//! | 42-46   | Total axles                                               |
+//! | 47-53   | Total length in decimeters                                |
+//!
+//! Equipment record :
+//!
+//! | Columns | Field                                                  |
+//! |---------|--------------------------------------------------------|
+//! | 1-2     | `EQ`                                                   |
+//! | 3-5     | Position in the train                                  |
+//! | 6-7     | Equipment group code                                   |
+//! | 8-11    | Mark, left justified                                   |
+//! | 12-17   | Number                                                 |
+//! | 18      | Orientation, `A` or `B` end leading, blank if unknown  |
+//! | 19      | Tags read, `B` both, `L` left or `R` right             |
+//! | 20-21   | Axles                                                  |
+//! | 22-25   | Length in decimeters                                   |
+//! | 26-57   | Tag in hexadecimal, the left one if both were read     |
+//! | 58-71   | First read of the vehicle                              |
+//!
+//! Trailer :
+//!
+//! | Columns | Field                                       |
+//! |---------|---------------------------------------------|
+//! | 1-2     | `TT`                                        |
+//! | 3-5     | Vehicle count                               |
+//! | 6-10    | Record count, header and trailer included   |
+//!
+//! The tags of a parsed report are decoded again so that [`T94Report::validate`] can check the
+//! records against them.
+//! The reports written one after the other, one per train passage, are parsed with
+//! [`T94Report::parse_all`].
+
+use crate::{
+    consist::{Consist, Sides},
+    orientation::{Direction, Orientation},
+    AEITagData, NewTagError,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::{error, fmt::Display, fs, io, ops::Range, path::Path, str::FromStr};
+
+/// Length of the records, without the line feed
+pub const RECORD_LENGTH: usize = 80;
+/// Most vehicles of a report
+pub const MAX_VEHICLES: usize = 999;
+
+const TIMESTAMP_FORMAT: &str = "%Y%m%d%H%M%S";
+
+#[derive(Debug)]
+pub enum T94Error {
+    Io(io::Error),
+    /// The site ID isn't 1 to 7 letters or digits
+    SiteId(String),
+    /// The consist is empty or holds more than [`MAX_VEHICLES`] vehicles
+    ConsistSize(usize),
+    /// A record is malformed, at the given line
+    Format(usize, String),
+    /// The tag of the equipment record at the given line couldn't be decoded
+    Tag(usize, NewTagError),
+}
+
+impl From<io::Error> for T94Error {
+    fn from(err: io::Error) -> Self {
+        T94Error::Io(err)
+    }
+}
+
+impl Display for T94Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            T94Error::Io(e) => write!(f, "the report couldn't be read : {}", e),
+            T94Error::SiteId(site) => write!(
+                f,
+                "invalid site ID {}, expected 1 to 7 letters or digits",
+                site
+            ),
+            T94Error::ConsistSize(size) => write!(
+                f,
+                "a report holds 1 to {} vehicles, the consist has {}",
+                MAX_VEHICLES, size
+            ),
+            T94Error::Format(line, message) => write!(f, "line {} : {}", line, message),
+            T94Error::Tag(line, e) => write!(f, "line {} : {}", line, e),
+        }
+    }
+}
+
+impl error::Error for T94Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            T94Error::Io(e) => Some(e),
+            T94Error::Tag(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Disagreement found by [`T94Report::validate`]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum T94Issue {
+    /// A field of an equipment record differs from its tag
+    TagMismatch {
+        position: usize,
+        field: &'static str,
+        record: String,
+        tag: String,
+    },
+    /// The checksums of the tag of a vehicle are invalid
+    Checksum(usize),
+    /// The vehicles aren't numbered from 1 in order
+    Position { expected: usize, found: usize },
+    /// A total of the header differs from the sum of the vehicles
+    Total {
+        field: &'static str,
+        header: u32,
+        vehicles: u32,
+    },
+}
+
+impl Display for T94Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            T94Issue::TagMismatch {
+                position,
+                field,
+                record,
+                tag,
+            } => write!(
+                f,
+                "vehicle {} : {} record {}, tag {}",
+                position, field, record, tag
+            ),
+            T94Issue::Checksum(position) => {
+                write!(
+                    f,
+                    "vehicle {} : the checksums of the tag are invalid",
+                    position
+                )
+            }
+            T94Issue::Position { expected, found } => {
+                write!(f, "vehicle {} found at position {}", found, expected)
+            }
+            T94Issue::Total {
+                field,
+                header,
+                vehicles,
+            } => write!(
+                f,
+                "total {} : header {}, vehicles {}",
+                field, header, vehicles
+            ),
+        }
+    }
+}
+
+/// Equipment record of a report
+#[derive(Clone, Debug)]
+pub struct T94Vehicle {
+    pub position: usize,
+    pub group: u8,
+    pub mark: String,
+    pub number: u32,
+    pub orientation: Option<Orientation>,
+    pub sides: Sides,
+    pub axles: u8,
+    pub length_dm: u16,
+    pub tag: AEITagData,
+    pub first_seen: DateTime<Utc>,
+}
+
+impl T94Vehicle {
+    fn to_record(&self) -> String {
+        let orientation = match self.orientation {
+            Some(Orientation::AEndLeading) => 'A',
+            Some(Orientation::BEndLeading) => 'B',
+            None => ' ',
+        };
+        let sides = match self.sides {
+            Sides::Both => 'B',
+            Sides::Left => 'L',
+            Sides::Right => 'R',
+        };
+        pad(format!(
+            "EQ{:03}{:02}{:<4}{:06}{}{}{:02}{:04}{}{}",
+            self.position,
+            self.group,
+            self.mark,
+            self.number,
+            orientation,
+            sides,
+            self.axles,
+            self.length_dm,
+            hex::encode_upper(self.tag.raw()),
+            self.first_seen.format(TIMESTAMP_FORMAT)
+        ))
+    }
+
+    fn parse(record: &Record) -> Result<T94Vehicle, T94Error> {
+        let tag = record.field(25..57, "tag")?;
+        Ok(T94Vehicle {
+            position: record.number(2..5, "position")?,
+            group: record.number(5..7, "equipment group")?,
+            mark: String::from(record.field(7..11, "mark")?.trim_end()),
+            number: record.number(11..17, "number")?,
+            orientation: match record.field(17..18, "orientation")? {
+                "A" => Some(Orientation::AEndLeading),
+                "B" => Some(Orientation::BEndLeading),
+                " " => None,
+                o => return Err(record.error(format!("invalid orientation {}", o))),
+            },
+            sides: match record.field(18..19, "tags read")? {
+                "B" => Sides::Both,
+                "L" => Sides::Left,
+                "R" => Sides::Right,
+                s => return Err(record.error(format!("invalid tags read {}", s))),
+            },
+            axles: record.number(19..21, "axles")?,
+            length_dm: record.number(21..25, "length")?,
+            tag: AEITagData::new(tag).map_err(|e| T94Error::Tag(record.line, e))?,
+            first_seen: record.timestamp(57..71, "first read")?,
+        })
+    }
+}
+
+/// Train consist report
+#[derive(Clone, Debug)]
+pub struct T94Report {
+    pub site: String,
+    pub arrival: DateTime<Utc>,
+    pub departure: DateTime<Utc>,
+    pub direction: Option<Direction>,
+    /// Total axles of the header
+    pub total_axles: u32,
+    /// Total length of the header, in decimeters
+    pub total_length_dm: u32,
+    pub vehicles: Vec<T94Vehicle>,
+}
+
+impl T94Report {
+    /// Report of a consist read at the site `site`
+    pub fn from_consist(consist: &Consist, site: &str) -> Result<T94Report, T94Error> {
+        if site.is_empty() || site.len() > 7 || !site.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(T94Error::SiteId(String::from(site)));
+        }
+        if consist.vehicles.is_empty() || consist.vehicles.len() > MAX_VEHICLES {
+            return Err(T94Error::ConsistSize(consist.vehicles.len()));
+        }
+
+        let vehicles: Vec<T94Vehicle> = consist
+            .vehicles
+            .iter()
+            .map(|vehicle| {
+                let tag = vehicle.tag();
+                T94Vehicle {
+                    position: vehicle.position,
+                    group: vehicle.id.group,
+                    mark: vehicle.id.mark.clone(),
+                    number: vehicle.id.number,
+                    orientation: vehicle.orientation,
+                    sides: vehicle.sides(),
+                    axles: tag.number_axles(),
+                    length_dm: tag.length_dm(),
+                    tag: tag.clone(),
+                    first_seen: vehicle.first_seen(),
+                }
+            })
+            .collect();
+        let arrival = consist.vehicles.iter().map(|v| v.first_seen()).min();
+        let departure = consist.vehicles.iter().map(|v| v.last_seen).max();
+        Ok(T94Report {
+            site: site.to_uppercase(),
+            arrival: arrival.expect("the consist isn't empty"),
+            departure: departure.expect("the consist isn't empty"),
+            direction: consist.direction,
+            total_axles: vehicles.iter().map(|v| v.axles as u32).sum(),
+            total_length_dm: vehicles.iter().map(|v| v.length_dm as u32).sum(),
+            vehicles,
+        })
+    }
+
+    /// Loads a report file
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<T94Report, T94Error> {
+        T94Report::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Parses a report, the records being ended by a line feed or CR LF. Fails when records
+    /// follow the trailer, the reports written one after the other being parsed by
+    /// [`T94Report::parse_all`]
+    pub fn parse(data: &str) -> Result<T94Report, T94Error> {
+        let mut records = records(data);
+        let header = records
+            .next()
+            .ok_or_else(|| T94Error::Format(1, String::from("the report is empty")))?;
+        let report = T94Report::parse_records(header, &mut records)?;
+        match records.next() {
+            Some(record) => Err(record.error(String::from(
+                "record after the trailer record TT, expected a single report",
+            ))),
+            None => Ok(report),
+        }
+    }
+
+    /// Parses the reports written one after the other, such as the reports of the passages
+    /// exported together
+    pub fn parse_all(data: &str) -> Result<Vec<T94Report>, T94Error> {
+        let mut records = records(data);
+        let mut reports = Vec::new();
+        while let Some(header) = records.next() {
+            reports.push(T94Report::parse_records(header, &mut records)?);
+        }
+        if reports.is_empty() {
+            return Err(T94Error::Format(1, String::from("the report is empty")));
+        }
+        Ok(reports)
+    }
+
+    /// Parses the records of a report up to its trailer
+    fn parse_records<'a, I>(header: Record<'a>, records: &mut I) -> Result<T94Report, T94Error>
+    where
+        I: Iterator<Item = Record<'a>>,
+    {
+        if header.kind() != "TH" {
+            return Err(header.error(String::from("expected the train header record TH")));
+        }
+        let mut report = T94Report {
+            site: String::from(header.field(2..9, "site ID")?.trim_end()),
+            arrival: header.timestamp(9..23, "arrival")?,
+            departure: header.timestamp(23..37, "departure")?,
+            direction: match header.field(37..38, "direction")? {
+                "R" => Some(Direction::Reference),
+                "V" => Some(Direction::Reverse),
+                " " => None,
+                d => return Err(header.error(format!("invalid direction {}", d))),
+            },
+            total_axles: header.number(41..46, "total axles")?,
+            total_length_dm: header.number(46..53, "total length")?,
+            vehicles: Vec::new(),
+        };
+        let header_vehicles: usize = header.number(38..41, "vehicle count")?;
+
+        let mut record_count = 1;
+        let mut last_line = header.line;
+        for record in records {
+            record_count += 1;
+            last_line = record.line;
+            match record.kind() {
+                "EQ" => report.vehicles.push(T94Vehicle::parse(&record)?),
+                "TT" => {
+                    let vehicles: usize = record.number(2..5, "vehicle count")?;
+                    let records: usize = record.number(5..10, "record count")?;
+                    if vehicles != report.vehicles.len() || vehicles != header_vehicles {
+                        return Err(record.error(format!(
+                            "{} vehicles announced, {} found",
+                            vehicles,
+                            report.vehicles.len()
+                        )));
+                    }
+                    if records != record_count {
+                        return Err(record.error(format!(
+                            "{} records announced, {} found",
+                            records, record_count
+                        )));
+                    }
+                    return Ok(report);
+                }
+                kind => return Err(record.error(format!("unexpected record {}", kind))),
+            }
+        }
+        Err(T94Error::Format(
+            last_line + 1,
+            String::from("missing trailer record TT, the report is truncated"),
+        ))
+    }
+
+    /// Checks the records against their tags and the totals of the header
+    pub fn validate(&self) -> Vec<T94Issue> {
+        let mut issues = Vec::new();
+        for (i, vehicle) in self.vehicles.iter().enumerate() {
+            if vehicle.position != i + 1 {
+                issues.push(T94Issue::Position {
+                    expected: i + 1,
+                    found: vehicle.position,
+                });
+            }
+            let tag = &vehicle.tag;
+            let fields = [
+                (
+                    "mark",
+                    vehicle.mark.clone(),
+                    String::from(tag.equipment_initial().trim_end()),
+                ),
+                (
+                    "number",
+                    vehicle.number.to_string(),
+                    tag.car_number().to_string(),
+                ),
+                (
+                    "equipment group",
+                    vehicle.group.to_string(),
+                    tag.equipment_group_code().to_string(),
+                ),
+                (
+                    "axles",
+                    vehicle.axles.to_string(),
+                    tag.number_axles().to_string(),
+                ),
+                (
+                    "length",
+                    vehicle.length_dm.to_string(),
+                    tag.length_dm().to_string(),
+                ),
+            ];
+            for (field, record, tag) in fields {
+                if record != tag {
+                    issues.push(T94Issue::TagMismatch {
+                        position: vehicle.position,
+                        field,
+                        record,
+                        tag,
+                    });
+                }
+            }
+            if !tag.checksums_valid() {
+                issues.push(T94Issue::Checksum(vehicle.position));
+            }
+        }
+
+        let totals = [
+            (
+                "axles",
+                self.total_axles,
+                self.vehicles.iter().map(|v| v.axles as u32).sum(),
+            ),
+            (
+                "length",
+                self.total_length_dm,
+                self.vehicles.iter().map(|v| v.length_dm as u32).sum(),
+            ),
+        ];
+        for (field, header, vehicles) in totals {
+            if header != vehicles {
+                issues.push(T94Issue::Total {
+                    field,
+                    header,
+                    vehicles,
+                });
+            }
+        }
+        issues
+    }
+
+    /// Returns the records of the report, each one followed by a line feed
+    pub fn to_records(&self) -> String {
+        let direction = match self.direction {
+            Some(Direction::Reference) => 'R',
+            Some(Direction::Reverse) => 'V',
+            None => ' ',
+        };
+        let mut out = pad(format!(
+            "TH{:<7}{}{}{}{:03}{:05}{:07}",
+            self.site,
+            self.arrival.format(TIMESTAMP_FORMAT),
+            self.departure.format(TIMESTAMP_FORMAT),
+            direction,
+            self.vehicles.len(),
+            self.total_axles,
+            self.total_length_dm
+        ));
+        for vehicle in &self.vehicles {
+            out.push_str(&vehicle.to_record());
+        }
+        out.push_str(&pad(format!(
+            "TT{:03}{:05}",
+            self.vehicles.len(),
+            self.vehicles.len() + 2
+        )));
+        out
+    }
+}
+
+/// Pads a record to [`RECORD_LENGTH`] and ends it with a line feed
+fn pad(record: String) -> String {
+    format!("{:<width$}\n", record, width = RECORD_LENGTH)
+}
+
+/// Records of the data, the blank lines being skipped
+fn records(data: &str) -> impl Iterator<Item = Record<'_>> {
+    data.lines()
+        .enumerate()
+        .map(|(i, line)| Record {
+            line: i + 1,
+            data: line.trim_end_matches('\r'),
+        })
+        .filter(|record| !record.data.trim().is_empty())
+}
+
+/// Record being parsed
+struct Record<'a> {
+    line: usize,
+    data: &'a str,
+}
+
+impl Record<'_> {
+    fn kind(&self) -> &str {
+        self.data.get(0..2).unwrap_or(self.data)
+    }
+
+    fn error(&self, message: String) -> T94Error {
+        T94Error::Format(self.line, message)
+    }
+
+    /// Field at the given 0-based columns
+    fn field(&self, columns: Range<usize>, name: &str) -> Result<&str, T94Error> {
+        let (start, end) = (columns.start + 1, columns.end);
+        self.data
+            .get(columns)
+            .ok_or_else(|| self.error(format!("missing {} in columns {}-{}", name, start, end)))
+    }
+
+    fn number<T: FromStr>(&self, columns: Range<usize>, name: &str) -> Result<T, T94Error> {
+        let field = self.field(columns, name)?;
+        field
+            .trim()
+            .parse()
+            .map_err(|_| self.error(format!("invalid {} {}", name, field)))
+    }
+
+    fn timestamp(&self, columns: Range<usize>, name: &str) -> Result<DateTime<Utc>, T94Error> {
+        let field = self.field(columns, name)?;
+        NaiveDateTime::parse_from_str(field, TIMESTAMP_FORMAT)
+            .map(|t| t.and_utc())
+            .map_err(|_| self.error(format!("invalid {} {}", name, field)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{consist::ConsistBuilder, encode::TagBuilder, read::TagRead};
+    use chrono::{Duration, TimeZone};
+
+    /// QNSL 502 right and left
+    static TAG1: &str = "2F3E06C007DB1E139000000000000331";
+    static TAG1_LEFT: &str = "2F3E06C007D91E1B9000000000000331";
+    /// IOCC 85123 left
+    static TAG3: &str = "9EA488C5320CC01B9000000000000331";
+
+    fn consist() -> Consist {
+        let start = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        let mut builder = ConsistBuilder::new();
+        for (tag, seconds) in [(TAG1, 0), (TAG1_LEFT, 1), (TAG3, 5)] {
+            builder.push(TagRead::new(
+                AEITagData::new(tag).unwrap(),
+                start + Duration::seconds(seconds),
+                "R1",
+            ));
+        }
+        let mut consist = builder.build();
+        consist.direction = Some(Direction::Reverse);
+        consist.vehicles[0].orientation = Some(Orientation::AEndLeading);
+        consist
+    }
+
+    #[test]
+    fn write() {
+        let records = T94Report::from_consist(&consist(), "sept1")
+            .unwrap()
+            .to_records();
+        let lines: Vec<&str> = records.lines().collect();
+
+        assert_eq!(lines.len(), 4);
+        assert!(lines.iter().all(|line| line.len() == RECORD_LENGTH));
+        assert_eq!(
+            lines[0].trim_end(),
+            "THSEPT1  2024050112000020240501120005V002000080000478"
+        );
+        assert_eq!(
+            lines[1].trim_end(),
+            format!("EQ00105QNSL000502AB040286{}20240501120000", TAG1_LEFT)
+        );
+        assert_eq!(
+            lines[2].trim_end(),
+            format!("EQ00219IOCC085123 L040192{}20240501120005", TAG3)
+        );
+        assert_eq!(lines[3].trim_end(), "TT00200004");
+    }
+
+    #[test]
+    fn round_trip() {
+        let records = T94Report::from_consist(&consist(), "SEPT1")
+            .unwrap()
+            .to_records()
+            .replace('\n', "\r\n");
+        let report = T94Report::parse(&records).unwrap();
+
+        assert_eq!(report.site, "SEPT1");
+        assert_eq!(report.direction, Some(Direction::Reverse));
+        assert_eq!(report.vehicles.len(), 2);
+        assert_eq!(
+            report.vehicles[0].orientation,
+            Some(Orientation::AEndLeading)
+        );
+        assert_eq!(report.vehicles[1].sides, Sides::Left);
+        assert_eq!(report.vehicles[1].tag.car_number(), 85123);
+        assert_eq!(report.validate(), vec![]);
+        assert_eq!(report.to_records(), records.replace("\r\n", "\n"));
+    }
+
+    #[test]
+    fn short_marks() {
+        let tag = TagBuilder::new("UP", 1234).length_dm(180).build().unwrap();
+        let mut builder = ConsistBuilder::new();
+        builder.push(TagRead::new(
+            tag,
+            Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap(),
+            "R1",
+        ));
+        let records = T94Report::from_consist(&builder.build(), "SEPT1")
+            .unwrap()
+            .to_records();
+        assert_eq!(&records.lines().nth(1).unwrap()[..17], "EQ00119UP  001234");
+
+        let report = T94Report::parse(&records).unwrap();
+        assert_eq!(report.vehicles[0].mark, "UP");
+        assert_eq!(report.validate(), vec![]);
+    }
+
+    #[test]
+    fn validation() {
+        // The number of the second vehicle and the total length were altered
+        let records = T94Report::from_consist(&consist(), "SEPT1")
+            .unwrap()
+            .to_records()
+            .replace("085123", "085124")
+            .replace("0000478", "0000480");
+        let issues = T94Report::parse(&records).unwrap().validate();
+
+        assert_eq!(
+            issues,
+            vec![
+                T94Issue::TagMismatch {
+                    position: 2,
+                    field: "number",
+                    record: String::from("85124"),
+                    tag: String::from("85123"),
+                },
+                T94Issue::Total {
+                    field: "length",
+                    header: 480,
+                    vehicles: 478,
+                }
+            ]
+        );
+        assert_eq!(
+            issues[0].to_string(),
+            "vehicle 2 : number record 85124, tag 85123"
+        );
+    }
+
+    #[test]
+    fn several_reports() {
+        let report = T94Report::from_consist(&consist(), "SEPT1").unwrap();
+        let mut second = report.clone();
+        second.site = String::from("ARNAUD");
+        let records = format!("{}\n{}", report.to_records(), second.to_records());
+
+        let reports = T94Report::parse_all(&records).unwrap();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[1].site, "ARNAUD");
+        assert_eq!(reports[1].vehicles.len(), 2);
+        assert_eq!(
+            T94Report::parse(&records).unwrap_err().to_string(),
+            "line 6 : record after the trailer record TT, expected a single report"
+        );
+        assert!(matches!(
+            T94Report::parse_all(&records[..records.len() - RECORD_LENGTH - 1]),
+            Err(T94Error::Format(9, _))
+        ));
+        assert!(matches!(
+            T94Report::parse_all("\n"),
+            Err(T94Error::Format(1, _))
+        ));
+    }
+
+    #[test]
+    fn invalid_reports() {
+        let records = T94Report::from_consist(&consist(), "SEPT1")
+            .unwrap()
+            .to_records();
+        let lines: Vec<&str> = records.lines().collect();
+
+        let truncated = lines[..3].join("\n");
+        assert!(matches!(
+            T94Report::parse(&truncated),
+            Err(T94Error::Format(4, _))
+        ));
+        let bad_tag = records.replace(TAG3, &TAG3.replace('9', "Z"));
+        assert!(matches!(
+            T94Report::parse(&bad_tag),
+            Err(T94Error::Tag(3, _))
+        ));
+        let bad_axles = records.replace("L04019", "LXX019");
+        assert_eq!(
+            T94Report::parse(&bad_axles).unwrap_err().to_string(),
+            "line 3 : invalid axles XX"
+        );
+        assert!(matches!(
+            T94Report::from_consist(&consist(), "TOO LONG SITE"),
+            Err(T94Error::SiteId(_))
+        ));
+        assert!(matches!(
+            T94Report::from_consist(&Consist::default(), "SEPT1"),
+            Err(T94Error::ConsistSize(0))
+        ));
+    }
+}
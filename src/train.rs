@@ -0,0 +1,257 @@
+//! Segmentation of the reads of a wayside reader into train passages
+//!
+//! A reader sends a continuous stream of reads. A [`Segmenter`] groups them into [`Passage`]s
+//! using two rules :
+//! - a passage ends when no tag has been read for longer than the maximum gap
+//! - when the reader has a presence detector, a passage lasts while the detector is on, the gap
+//!   being ignored while a stopped train stands in front of the reader, and ends when it turns
+//!   off
+//!
+//! Passages with fewer reads than the minimum, a car parked at the edge of the antenna field for
+//! instance, are dropped. Each segmenter handles the reads of a single reader.
+//!
+//! ```rust
+//! use aei_tag_parser::{read::TagRead, train, AEITagData};
+//! use chrono::{Duration, TimeZone, Utc};
+//!
+//! let start = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+//! let tag = AEITagData::new("9EA488C030426A179000000000000331").unwrap();
+//! let reads = [0, 2, 4, 300, 302].map(|s| TagRead::new(tag.clone(), start + Duration::seconds(s), "R1"));
+//! let passages = train::segment(reads, train::Rules::default());
+//!
+//! assert_eq!(passages.len(), 2);
+//! assert_eq!(passages[0].reads.len(), 3);
+//! assert_eq!(passages[1].start, start + Duration::seconds(300));
+//! ```
+
+use crate::{read::TagRead, AEITagData};
+use chrono::{DateTime, Duration, Utc};
+
+/// Rules deciding where a passage ends
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Rules {
+    /// Longest time without a read within a passage
+    pub max_gap: Duration,
+    /// Fewest reads of a passage, the passages with less reads are dropped
+    pub min_reads: usize,
+}
+
+impl Default for Rules {
+    /// Passages separated by 30 seconds without reads, of at least one read
+    fn default() -> Self {
+        Rules {
+            max_gap: Duration::seconds(30),
+            min_reads: 1,
+        }
+    }
+}
+
+/// Reads of a train passing in front of a reader
+#[derive(Clone, PartialEq, Debug)]
+pub struct Passage {
+    /// Timestamp of the first read
+    pub start: DateTime<Utc>,
+    /// Timestamp of the last read
+    pub end: DateTime<Utc>,
+    /// Reads of the passage, ordered by timestamp
+    pub reads: Vec<TagRead>,
+}
+
+impl Passage {
+    fn new(read: TagRead) -> Passage {
+        Passage {
+            start: read.timestamp,
+            end: read.timestamp,
+            reads: vec![read],
+        }
+    }
+
+    /// Time between the first and the last read
+    pub fn duration(&self) -> Duration {
+        self.end - self.start
+    }
+
+    /// Tags read, in the order they were read
+    pub fn tags(&self) -> impl Iterator<Item = &AEITagData> {
+        self.reads.iter().map(|read| &read.tag)
+    }
+
+    /// Adds a read, keeping the reads ordered as the buffered reads may arrive late. The reads of
+    /// the same timestamp are kept in the order they arrived
+    fn push(&mut self, read: TagRead) {
+        self.start = self.start.min(read.timestamp);
+        self.end = self.end.max(read.timestamp);
+        let index = self
+            .reads
+            .partition_point(|r| r.timestamp <= read.timestamp);
+        self.reads.insert(index, read);
+    }
+}
+
+/// Groups the reads of a reader into passages as they arrive
+#[derive(Clone, Debug)]
+pub struct Segmenter {
+    rules: Rules,
+    current: Option<Passage>,
+    present: bool,
+}
+
+impl Segmenter {
+    pub fn new(rules: Rules) -> Segmenter {
+        Segmenter {
+            rules,
+            current: None,
+            present: false,
+        }
+    }
+
+    /// Adds a read, returning the previous passage when the read starts a new one
+    pub fn push(&mut self, read: TagRead) -> Option<Passage> {
+        let ended = match &self.current {
+            Some(passage) => !self.present && read.timestamp - passage.end > self.rules.max_gap,
+            None => false,
+        };
+        let previous = if ended { self.take() } else { None };
+
+        match &mut self.current {
+            Some(passage) => passage.push(read),
+            None => self.current = Some(Passage::new(read)),
+        }
+        previous
+    }
+
+    /// Updates the state of the presence detector, returning the passage ended when it turns off
+    pub fn presence(&mut self, present: bool) -> Option<Passage> {
+        let ended = self.present && !present;
+        self.present = present;
+        if ended {
+            self.take()
+        } else {
+            None
+        }
+    }
+
+    /// Returns the current passage if no tag has been read for longer than the maximum gap at
+    /// `now`, so that a live stream doesn't wait for the next train to report a passage
+    pub fn tick(&mut self, now: DateTime<Utc>) -> Option<Passage> {
+        match &self.current {
+            Some(passage) if !self.present && now - passage.end > self.rules.max_gap => self.take(),
+            _ => None,
+        }
+    }
+
+    /// Returns the current passage, at the end of the stream
+    pub fn finish(&mut self) -> Option<Passage> {
+        self.take()
+    }
+
+    /// Current passage, if it has enough reads
+    fn take(&mut self) -> Option<Passage> {
+        self.current
+            .take()
+            .filter(|passage| passage.reads.len() >= self.rules.min_reads)
+    }
+}
+
+/// Groups the reads of a reader, ordered by timestamp, into passages
+pub fn segment(reads: impl IntoIterator<Item = TagRead>, rules: Rules) -> Vec<Passage> {
+    let mut segmenter = Segmenter::new(rules);
+    let mut passages: Vec<Passage> = reads
+        .into_iter()
+        .filter_map(|read| segmenter.push(read))
+        .collect();
+    passages.extend(segmenter.finish());
+    passages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    static TAG1: &str = "2F3E06C007DB1E139000000000000331";
+    static TAG2: &str = "9EA488C030426A179000000000000331";
+    static TAG3: &str = "9EA488C5320CC01B9000000000000331";
+
+    fn read(tag: &str, seconds: i64) -> TagRead {
+        let start = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        TagRead::new(
+            AEITagData::new(tag).unwrap(),
+            start + Duration::seconds(seconds),
+            "R1",
+        )
+    }
+
+    fn numbers(passage: &Passage) -> Vec<u32> {
+        passage.tags().map(|tag| tag.car_number()).collect()
+    }
+
+    #[test]
+    fn gaps() {
+        let reads = vec![
+            read(TAG1, 0),
+            read(TAG2, 10),
+            read(TAG3, 40),
+            read(TAG1, 71),
+            read(TAG2, 75),
+        ];
+        let passages = segment(reads, Rules::default());
+
+        assert_eq!(passages.len(), 2);
+        assert_eq!(numbers(&passages[0]), vec![502, 3088, 85123]);
+        assert_eq!(passages[0].duration(), Duration::seconds(40));
+        assert_eq!(numbers(&passages[1]), vec![502, 3088]);
+        assert_eq!(passages[1].start, read(TAG1, 71).timestamp);
+        assert_eq!(passages[1].end, read(TAG1, 75).timestamp);
+    }
+
+    #[test]
+    fn presence() {
+        let mut segmenter = Segmenter::new(Rules::default());
+        assert_eq!(segmenter.presence(true), None);
+        assert_eq!(segmenter.push(read(TAG1, 0)), None);
+        // The train stopped in front of the reader
+        assert_eq!(segmenter.push(read(TAG2, 120)), None);
+        assert_eq!(segmenter.tick(read(TAG2, 300).timestamp), None);
+
+        let passage = segmenter.presence(false).unwrap();
+        assert_eq!(numbers(&passage), vec![502, 3088]);
+        assert_eq!(segmenter.finish(), None);
+    }
+
+    #[test]
+    fn live_stream() {
+        let mut segmenter = Segmenter::new(Rules::default());
+        segmenter.push(read(TAG1, 0));
+        segmenter.push(read(TAG2, 5));
+
+        assert_eq!(segmenter.tick(read(TAG2, 20).timestamp), None);
+        assert_eq!(
+            segmenter
+                .tick(read(TAG2, 40).timestamp)
+                .map(|p| p.reads.len()),
+            Some(2)
+        );
+        assert_eq!(segmenter.finish(), None);
+    }
+
+    #[test]
+    fn late_reads_and_noise() {
+        let rules = Rules {
+            min_reads: 2,
+            ..Rules::default()
+        };
+        // A buffered read arriving after the later ones, then a lone read
+        let reads = vec![
+            read(TAG1, 10),
+            read(TAG3, 20),
+            read(TAG2, 5),
+            read(TAG1, 100),
+        ];
+        let passages = segment(reads, rules);
+
+        assert_eq!(passages.len(), 1);
+        assert_eq!(numbers(&passages[0]), vec![3088, 502, 85123]);
+        assert_eq!(passages[0].start, read(TAG2, 5).timestamp);
+    }
+}
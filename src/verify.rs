@@ -0,0 +1,321 @@
+//! Verification of a consist against its train list
+//!
+//! The train list gives the cars planned in a train, in order, with the columns of a roster. A
+//! [`Verifier`] aligns the vehicles read with the list : the planned cars which weren't read are
+//! missing, the vehicles read which aren't on the list are extra, and the fewest cars to move to
+//! restore the planned order are out of order. The tags of the cars read are also compared with
+//! the lengths, axles, groups and bearings given by the list. Only the vehicles are compared : the
+//! end-of-train device and the other tags read on the train, such as those of the containers,
+//! are left out.
+//!
+//! ```rust
+//! use aei_tag_parser::{consist::ConsistBuilder, read::TagRead, roster::Roster, verify::Verifier, AEITagData};
+//! use chrono::{Duration, Utc};
+//!
+//! let plan = Roster::from_csv("mark;number\nIOCC;85123\nIOCC;3088\nIOCC;1\n").unwrap();
+//! let start = Utc::now();
+//! let mut builder = ConsistBuilder::new();
+//! for (i, tag) in ["9EA488C030426A179000000000000331", "9EA488C5320CC01B9000000000000331"].iter().enumerate() {
+//!     builder.push(TagRead::new(AEITagData::new(tag).unwrap(), start + Duration::seconds(i as i64), "R1"));
+//! }
+//!
+//! let result = Verifier::new(&plan).verify(&builder.build());
+//! assert_eq!(result.missing.len(), 1);
+//! assert_eq!(result.out_of_order.len(), 1);
+//! ```
+
+use crate::{
+    align::{align, Op},
+    consist::{Consist, Vehicle, VehicleId},
+    reconcile::{FieldMismatch, Reconciler},
+    roster::{Roster, RosterCar},
+    summary::is_vehicle_group,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+/// Planned car which wasn't read
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct MissingCar {
+    /// Position in the train list, starting at 1
+    pub planned: usize,
+    pub car: RosterCar,
+}
+
+impl Display for MissingCar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} planned #{}", self.car, self.planned)
+    }
+}
+
+/// Vehicle read which isn't on the train list
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ExtraVehicle {
+    /// Position in the consist, starting at 1
+    pub position: usize,
+    pub vehicle: VehicleId,
+}
+
+impl Display for ExtraVehicle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} read #{}", self.vehicle, self.position)
+    }
+}
+
+/// Car read out of the planned order
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct MovedCar {
+    pub vehicle: VehicleId,
+    pub planned: usize,
+    pub position: usize,
+}
+
+impl Display for MovedCar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} planned #{}, read #{}",
+            self.vehicle, self.planned, self.position
+        )
+    }
+}
+
+/// Car read whose tag disagrees with the train list
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct DataMismatch {
+    pub vehicle: VehicleId,
+    pub position: usize,
+    pub fields: Vec<FieldMismatch>,
+}
+
+impl Display for DataMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fields: Vec<String> = self.fields.iter().map(|field| field.to_string()).collect();
+        write!(
+            f,
+            "{} read #{} : {}",
+            self.vehicle,
+            self.position,
+            fields.join("; ")
+        )
+    }
+}
+
+/// Result of the verification
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct Verification {
+    /// Number of cars read in the planned order
+    pub in_order: usize,
+    pub missing: Vec<MissingCar>,
+    pub extra: Vec<ExtraVehicle>,
+    /// Cars to move to restore the planned order
+    pub out_of_order: Vec<MovedCar>,
+    pub mismatches: Vec<DataMismatch>,
+}
+
+impl Verification {
+    /// Check if the consist read is the planned one
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty()
+            && self.extra.is_empty()
+            && self.out_of_order.is_empty()
+            && self.mismatches.is_empty()
+    }
+}
+
+impl Display for Verification {
+    /// Formats a line per difference, followed by the totals
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for car in &self.missing {
+            writeln!(f, "MISSING  : {}", car)?;
+        }
+        for vehicle in &self.extra {
+            writeln!(f, "EXTRA    : {}", vehicle)?;
+        }
+        for car in &self.out_of_order {
+            writeln!(f, "MOVED    : {}", car)?;
+        }
+        for mismatch in &self.mismatches {
+            writeln!(f, "MISMATCH : {}", mismatch)?;
+        }
+        write!(
+            f,
+            "{} car(s) in order, {} missing, {} extra, {} out of order, {} mismatch(es)",
+            self.in_order,
+            self.missing.len(),
+            self.extra.len(),
+            self.out_of_order.len(),
+            self.mismatches.len()
+        )
+    }
+}
+
+/// Compares the consists with a train list
+pub struct Verifier<'a> {
+    plan: &'a Roster,
+    reconciler: Reconciler<'a>,
+}
+
+impl<'a> Verifier<'a> {
+    pub fn new(plan: &'a Roster) -> Verifier<'a> {
+        Verifier {
+            plan,
+            reconciler: Reconciler::new(plan),
+        }
+    }
+
+    /// Largest difference of length, in decimeters, which is not reported
+    pub fn length_tolerance(mut self, length_tolerance_dm: u16) -> Verifier<'a> {
+        self.reconciler = self.reconciler.length_tolerance(length_tolerance_dm);
+        self
+    }
+
+    /// Compares the vehicles of the consist with the train list
+    pub fn verify(&self, consist: &Consist) -> Verification {
+        let vehicles: Vec<&Vehicle> = consist
+            .vehicles
+            .iter()
+            .filter(|vehicle| is_vehicle_group(vehicle.id.group))
+            .collect();
+        let planned: Vec<(&str, u32)> = self
+            .plan
+            .cars()
+            .iter()
+            .map(|car| (car.mark.as_str(), car.number))
+            .collect();
+        let read: Vec<(&str, u32)> = vehicles
+            .iter()
+            .map(|vehicle| (vehicle.id.mark.as_str(), vehicle.id.number))
+            .collect();
+
+        let mut result = Verification::default();
+        for op in align(&planned, &read) {
+            match op {
+                Op::Keep { from, to } | Op::Move { from, to } => {
+                    let (car, vehicle) = (&self.plan.cars()[from], vehicles[to]);
+                    if matches!(op, Op::Keep { .. }) {
+                        result.in_order += 1;
+                    } else {
+                        result.out_of_order.push(MovedCar {
+                            vehicle: vehicle.id.clone(),
+                            planned: from + 1,
+                            position: vehicle.position,
+                        });
+                    }
+                    let fields = self.reconciler.compare(car, vehicle.tag());
+                    if !fields.is_empty() {
+                        result.mismatches.push(DataMismatch {
+                            vehicle: vehicle.id.clone(),
+                            position: vehicle.position,
+                            fields,
+                        });
+                    }
+                }
+                Op::Delete { from } => result.missing.push(MissingCar {
+                    planned: from + 1,
+                    car: self.plan.cars()[from].clone(),
+                }),
+                Op::Insert { to } => result.extra.push(ExtraVehicle {
+                    position: vehicles[to].position,
+                    vehicle: vehicles[to].id.clone(),
+                }),
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        consist::ConsistBuilder, encode::TagBuilder, read::TagRead, summary::EOT_GROUP, AEITagData,
+    };
+    use chrono::{Duration, TimeZone, Utc};
+
+    static TAG1: &str = "2F3E06C007DB1E139000000000000331";
+    static TAG2: &str = "9EA488C030426A179000000000000331";
+    static TAG3: &str = "9EA488C5320CC01B9000000000000331";
+
+    fn consist(tags: &[AEITagData]) -> Consist {
+        let start = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        let mut builder = ConsistBuilder::new();
+        for (i, tag) in tags.iter().enumerate() {
+            builder.push(TagRead::new(
+                tag.clone(),
+                start + Duration::seconds(i as i64),
+                "R1",
+            ));
+        }
+        builder.build()
+    }
+
+    fn tags(tags: &[&str]) -> Vec<AEITagData> {
+        tags.iter()
+            .map(|tag| AEITagData::new(tag).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn planned_consist() {
+        let plan =
+            Roster::from_csv("mark;number;length_dm;axles\nQNSL;502;286;4\nIOCC;3088;;\n").unwrap();
+        let result = Verifier::new(&plan).verify(&consist(&tags(&[TAG1, TAG2])));
+
+        assert!(result.is_clean());
+        assert_eq!(result.in_order, 2);
+    }
+
+    #[test]
+    fn differences() {
+        let plan = Roster::from_csv(
+            "mark;number;length_dm;axles\nIOCC;85123;;\nQNSL;502;;\nIOCC;1;;\nIOCC;3088;110;4\n",
+        )
+        .unwrap();
+        let result = Verifier::new(&plan).verify(&consist(&tags(&[TAG1, TAG3, TAG2, TAG2])));
+
+        assert_eq!(result.in_order, 2);
+        assert_eq!(
+            result.missing,
+            vec![MissingCar {
+                planned: 3,
+                car: plan.cars()[2].clone()
+            }]
+        );
+        assert!(result.extra.is_empty());
+        assert_eq!(result.out_of_order.len(), 1);
+        assert_eq!(
+            result.to_string(),
+            "MISSING  : IOCC 1 planned #3\nMOVED    : IOCC 85123 planned #1, read #2\nMISMATCH : IOCC 3088 read #3 : length roster 110, tag 106\n2 car(s) in order, 1 missing, 0 extra, 1 out of order, 1 mismatch(es)"
+        );
+
+        let tolerant = Verifier::new(&plan).length_tolerance(5);
+        assert!(tolerant
+            .verify(&consist(&tags(&[TAG1, TAG3, TAG2])))
+            .mismatches
+            .is_empty());
+    }
+
+    #[test]
+    fn extra_vehicles() {
+        let eot = TagBuilder::new("QNSL", 9001)
+            .group(EOT_GROUP)
+            .build()
+            .unwrap();
+        let container = TagBuilder::new("TTNU", 1234).group(10).build().unwrap();
+        let plan = Roster::from_csv("mark;number\nIOCC;3088\n").unwrap();
+        let result = Verifier::new(&plan).verify(&consist(&[
+            AEITagData::new(TAG1).unwrap(),
+            AEITagData::new(TAG2).unwrap(),
+            container,
+            eot,
+        ]));
+
+        assert_eq!(result.in_order, 1);
+        assert_eq!(result.extra.len(), 1);
+        assert_eq!(result.extra[0].to_string(), "QNSL 502 read #1");
+
+        let json: serde_json::Value = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["extra"][0]["vehicle"]["number"], 502);
+    }
+}